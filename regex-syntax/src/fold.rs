@@ -0,0 +1,242 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {CharClass, ClassRange, Expr, simple_case_fold};
+use unicode::case_folding;
+
+impl Expr {
+    /// Rewrites the case insensitive parts of the expression so that they
+    /// match with full case folding instead of simple case folding.
+    ///
+    /// Simple case folding maps every character to a single character, so
+    /// `(?i)ß` doesn't match `SS` and `(?i)fi` doesn't match `ﬁ`. Full case
+    /// folding also maps some characters to several (e.g., `ß` to `ss`),
+    /// and two strings match if their full case foldings are the same.
+    ///
+    /// Since a case insensitive literal or class can then match a different
+    /// number of characters, each one affected by a multi-character folding
+    /// is rewritten into an alternation of every way to spell it. Other
+    /// parts of the expression are left alone.
+    ///
+    /// Negated classes are left alone too, so `(?i)[^s]` doesn't match
+    /// `ss` even though it matches `ß`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use regex_syntax::Expr;
+    /// let e = Expr::parse("(?i)aß").unwrap().full_case_fold();
+    /// assert_eq!(e.to_string(), "(?:(?i:a)(?:(?i:ss)|[ß-ßẞ-ẞ]))");
+    /// ```
+    pub fn full_case_fold(self) -> Expr {
+        match self {
+            Expr::Literal { chars, casei: true } => {
+                let folded: Vec<char> =
+                    chars.iter().flat_map(|&c| full_fold(c)).collect();
+                let unaffected = (0..folded.len()).all(|i| {
+                    spellings(&folded[i..]).is_empty()
+                });
+                if unaffected {
+                    Expr::Literal { chars: chars, casei: true }
+                } else {
+                    group(folded_literal(&folded))
+                }
+            }
+            Expr::Class(cls) => {
+                // Negating a class is what makes it reach the last code
+                // point, and `[^s]` shouldn't match `ss` just because it
+                // matches `ß`.
+                if !cls.is_case_insensitive() || cls.matches('\u{10FFFF}') {
+                    return Expr::Class(cls)
+                }
+                let mut folds: Vec<&[char]> = vec![];
+                for &(c, folded) in case_folding::F_table {
+                    if cls.matches(c) && !folds.contains(&folded) {
+                        folds.push(folded);
+                    }
+                }
+                if folds.is_empty() {
+                    return Expr::Class(cls)
+                }
+                let mut alts = vec![Expr::Class(cls)];
+                alts.extend(folds.into_iter().map(folded_literal));
+                group(Expr::Alternate(alts))
+            }
+            Expr::Group { e, i, name } => {
+                let e = Box::new(e.full_case_fold());
+                Expr::Group { e: e, i: i, name: name }
+            }
+            Expr::Repeat { e, r, greedy } => {
+                Expr::Repeat {
+                    e: Box::new(e.full_case_fold()),
+                    r: r,
+                    greedy: greedy,
+                }
+            }
+            Expr::Concat(es) => {
+                Expr::Concat(es.into_iter()
+                               .map(Expr::full_case_fold)
+                               .collect())
+            }
+            Expr::Alternate(es) => {
+                Expr::Alternate(es.into_iter()
+                                  .map(Expr::full_case_fold)
+                                  .collect())
+            }
+            e => e,
+        }
+    }
+}
+
+fn group(e: Expr) -> Expr {
+    Expr::Group { e: Box::new(e), i: None, name: None }
+}
+
+/// Returns the full case folding of `c`.
+fn full_fold(c: char) -> Vec<char> {
+    match case_folding::F_table.binary_search_by(|&(x, _)| x.cmp(&c)) {
+        Ok(i) => case_folding::F_table[i].1.to_vec(),
+        Err(_) => vec![simple_case_fold(c)],
+    }
+}
+
+/// Returns every multi-character folding that `folded` starts with, along
+/// with the characters that fold to it.
+fn spellings(folded: &[char]) -> Vec<(&'static [char], Vec<char>)> {
+    let mut found: Vec<(&'static [char], Vec<char>)> = vec![];
+    for &(c, f) in case_folding::F_table {
+        if !folded.starts_with(f) {
+            continue
+        }
+        match found.iter().position(|&(g, _)| g == f) {
+            Some(i) => found[i].1.push(c),
+            None => found.push((f, vec![c])),
+        }
+    }
+    found
+}
+
+/// Returns an expression matching every string whose full case folding is
+/// `folded`.
+fn folded_literal(folded: &[char]) -> Expr {
+    let mut jumps = vec![];
+    for i in 0..folded.len() {
+        for (f, cs) in spellings(&folded[i..]) {
+            let ranges = cs.into_iter().map(|c| ClassRange::new(c, c));
+            let cls = CharClass::new(ranges.collect()).canonicalize();
+            jumps.push((i, i + f.len(), cls));
+        }
+    }
+    spell(folded, &jumps, 0, folded.len())
+}
+
+/// Returns an expression matching every spelling of `folded[start..end]`,
+/// where each of `jumps` spells `folded[i..j]` with a single character.
+///
+/// A spelling either passes through the middle of the range or jumps over
+/// it, so splitting there shares each half between the spellings instead
+/// of writing out every combination, which would be exponential in runs of
+/// overlapping foldings like `ssss…`.
+fn spell(
+    folded: &[char],
+    jumps: &[(usize, usize, CharClass)],
+    start: usize,
+    end: usize,
+) -> Expr {
+    if !jumps.iter().any(|&(i, j, _)| start <= i && j <= end) {
+        let chars = folded[start..end].to_vec();
+        return Expr::Literal { chars: chars, casei: true }
+    }
+    let mid = (start + end) / 2;
+    let mut alts = vec![concat(vec![
+        spell(folded, jumps, start, mid),
+        spell(folded, jumps, mid, end),
+    ])];
+    for &(i, j, ref cls) in jumps {
+        if start <= i && i < mid && mid < j && j <= end {
+            alts.push(concat(vec![
+                spell(folded, jumps, start, i),
+                Expr::Class(cls.clone()),
+                spell(folded, jumps, j, end),
+            ]));
+        }
+    }
+    match alts.len() {
+        1 => alts.pop().unwrap(),
+        _ => group(Expr::Alternate(alts)),
+    }
+}
+
+/// Concatenates expressions, merging adjacent case insensitive literals.
+fn concat(es: Vec<Expr>) -> Expr {
+    let mut new_es: Vec<Expr> = vec![];
+    for e in es {
+        let es = match e {
+            Expr::Empty => vec![],
+            Expr::Concat(es) => es,
+            e => vec![e],
+        };
+        for e in es {
+            match (new_es.last_mut(), e) {
+                (Some(&mut Expr::Literal { ref mut chars, casei: true }),
+                 Expr::Literal { chars: ref more, casei: true }) => {
+                    chars.extend(more.iter().cloned());
+                }
+                (_, Expr::Literal { ref chars, .. }) if chars.is_empty() => {}
+                (_, e) => new_es.push(e),
+            }
+        }
+    }
+    match new_es.len() {
+        0 => Expr::Empty,
+        1 => new_es.pop().unwrap(),
+        _ => Expr::Concat(new_es),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Expr;
+
+    fn fold(re: &str) -> String {
+        Expr::parse(re).unwrap().full_case_fold().to_string()
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(fold("(?i)abc"), "(?i:abc)");
+        assert_eq!(fold("ß"), "ß");
+        assert_eq!(fold("(?i)ß"), "(?:(?:(?i:ss)|[ß-ßẞ-ẞ]))");
+        assert_eq!(fold("(?i)fi"), "(?:(?:(?i:fi)|[ﬁ-ﬁ]))");
+        // Runs of overlapping foldings are spelled every way.
+        assert_eq!(
+            fold("(?i)sss"),
+            "(?:(?:(?i:s)(?:(?i:ss)|[ß-ßẞ-ẞ])|[ß-ßẞ-ẞ](?i:s)))");
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(fold("(?i)[a-c]"), "(?i:[a-c])");
+        assert_eq!(fold("[ß]"), "[ß-ß]");
+        let negated = Expr::parse("(?i)[^ß]").unwrap();
+        assert_eq!(fold("(?i)[^ß]"), negated.to_string());
+        assert_eq!(fold("(?i)[ß]x"),
+                   "(?:(?i:[ß-ß])|(?:(?i:ss)|[ß-ßẞ-ẞ]))(?i:x)");
+    }
+
+    #[test]
+    fn roundtrips() {
+        for re in &["(?i)straße|(ﬃ)+", "(?i)[ﬀ-ﬆ]*x", "(?i)(?P<n>ǰ)"] {
+            let e = Expr::parse(re).unwrap().full_case_fold();
+            assert_eq!(Expr::parse(&e.to_string()).unwrap().simplify(),
+                       e.simplify());
+        }
+    }
+}
@@ -0,0 +1,510 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::char;
+use std::cmp::{max, min};
+
+use rand::Rng;
+
+use {CharClass, ClassRange, Expr, Repeater};
+use {dec_char, inc_char, is_word_char, simple_case_fold};
+use unicode::case_folding;
+
+/// The default maximum number of times an unbounded repeat is repeated.
+const DEFAULT_MAX_REPEAT: u32 = 8;
+
+/// The number of strings generated before giving up on one that satisfies
+/// every assertion.
+const MAX_ATTEMPTS: usize = 100;
+
+/// Generates random strings matched by a regular expression.
+///
+/// Every string generated is matched by the expression *in full*, i.e., as
+/// if the expression were surrounded by `^` and `$`. Characters are picked
+/// from each character class, and case insensitive literals and classes use
+/// every case variant of their characters.
+///
+/// Zero-width assertions (like `\b` or `$`) are satisfied by generating
+/// strings until one happens to satisfy all of them, so `generate` can fail
+/// for expressions whose assertions rarely (or never) hold.
+///
+/// This is only available when the `rand` feature is enabled.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate rand; extern crate regex_syntax;
+/// # use regex_syntax::{Expr, Generator};
+/// # fn main() {
+/// let expr = Expr::parse(r"(?i)id-[0-9]{3}").unwrap();
+/// let gen = Generator::new(expr);
+/// let s = gen.generate(&mut rand::thread_rng()).unwrap();
+/// assert_eq!(s.len(), 6);
+/// assert!(s.starts_with("id-") || s.starts_with("ID-")
+///         || s.starts_with("iD-") || s.starts_with("Id-"));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Generator {
+    expr: Expr,
+    max_repeat: u32,
+    ascii_bias: bool,
+}
+
+impl Generator {
+    /// Creates a generator of strings matched by `expr`.
+    pub fn new(expr: Expr) -> Generator {
+        Generator {
+            expr: expr,
+            max_repeat: DEFAULT_MAX_REPEAT,
+            ascii_bias: true,
+        }
+    }
+
+    /// Sets the maximum number of times a repeat without an upper bound
+    /// (like `*` or `{2,}`) is repeated. A repeat is never repeated fewer
+    /// times than its lower bound, even if that exceeds this limit.
+    ///
+    /// The default is `8`.
+    pub fn max_repeat(mut self, n: u32) -> Generator {
+        self.max_repeat = n;
+        self
+    }
+
+    /// Sets whether characters are usually picked from the ASCII part of a
+    /// class (or of `.`), when it has one. Otherwise, characters are picked
+    /// uniformly from all the characters a class contains.
+    ///
+    /// This is enabled by default, since uniformly random characters are
+    /// almost never ASCII.
+    pub fn ascii_bias(mut self, yes: bool) -> Generator {
+        self.ascii_bias = yes;
+        self
+    }
+
+    /// Returns a random string matched by the expression.
+    ///
+    /// `None` is returned if no string satisfying every assertion was found
+    /// after a number of attempts.
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        for _ in 0..MAX_ATTEMPTS {
+            let mut run = Run::new(self, rng, None);
+            run.expr(&self.expr);
+            if run.asserts_hold() {
+                return Some(run.out)
+            }
+        }
+        None
+    }
+
+    /// Returns a random string that is *almost* matched by the expression.
+    ///
+    /// The string is generated like a match, except that exactly one part
+    /// of the expression is violated: a literal character is replaced or
+    /// dropped, a character is picked from outside a class (or `\n` for
+    /// `.`), or a bounded repeat is repeated too few or too many times.
+    ///
+    /// This usually gives a string that isn't matched, but not always
+    /// (e.g., a replaced character may be matched by another alternative),
+    /// so a near miss should be checked with a regex if that matters.
+    ///
+    /// `None` is returned if the expression has no part that can be
+    /// violated, e.g. `.*`.
+    pub fn near_miss<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        let targets = count_targets(&self.expr);
+        if targets == 0 {
+            return None
+        }
+        for _ in 0..MAX_ATTEMPTS {
+            let mut run = Run::new(self, rng, Some(targets));
+            run.expr(&self.expr);
+            if run.missed {
+                return Some(run.out)
+            }
+        }
+        None
+    }
+}
+
+/// The state of generating a single string.
+struct Run<'g, R: 'g> {
+    gen: &'g Generator,
+    rng: &'g mut R,
+    out: String,
+    // Every assertion generated, with the offset in `out` it applies to.
+    asserts: Vec<(usize, &'g Expr)>,
+    // When generating a near miss, the number of parts of the expression
+    // that can be violated. Each one is violated with an equal chance.
+    targets: Option<u32>,
+    missed: bool,
+}
+
+impl<'g, R: Rng> Run<'g, R> {
+    fn new(gen: &'g Generator, rng: &'g mut R, targets: Option<u32>)
+          -> Run<'g, R> {
+        Run {
+            gen: gen,
+            rng: rng,
+            out: String::new(),
+            asserts: vec![],
+            targets: targets,
+            missed: false,
+        }
+    }
+
+    fn expr(&mut self, e: &'g Expr) {
+        match *e {
+            Expr::Empty => {}
+            Expr::Literal { ref chars, casei } => {
+                if self.miss() {
+                    let i = self.rng.gen_range(0, chars.len());
+                    for (j, &c) in chars.iter().enumerate() {
+                        if j != i {
+                            self.case_variant(c, casei);
+                        } else if self.rng.gen() {
+                            // Drop the character.
+                        } else {
+                            let other = self.other_char(c, casei);
+                            self.out.push(other);
+                        }
+                    }
+                } else {
+                    for &c in chars {
+                        self.case_variant(c, casei);
+                    }
+                }
+            }
+            Expr::AnyChar => {
+                let c = self.pick(&[ClassRange::new('\0', char::MAX)]);
+                self.out.push(c);
+            }
+            Expr::AnyCharNoNL => {
+                let c = if self.miss() {
+                    '\n'
+                } else {
+                    self.pick(&[
+                        ClassRange::new('\0', '\x09'),
+                        ClassRange::new('\x0B', char::MAX),
+                    ])
+                };
+                self.out.push(c);
+            }
+            Expr::Class(ref cls) => {
+                let outside = complement(cls);
+                let missed = if !outside.is_empty() && self.miss() {
+                    self.pick_outside(cls, &outside)
+                } else {
+                    None
+                };
+                let c = match missed {
+                    Some(c) => c,
+                    None => {
+                        let c = self.pick(cls);
+                        if cls.is_case_insensitive() {
+                            self.pick_variant(c)
+                        } else {
+                            c
+                        }
+                    }
+                };
+                self.out.push(c);
+            }
+            Expr::StartLine | Expr::EndLine
+            | Expr::StartText | Expr::EndText
+            | Expr::WordBoundary | Expr::NotWordBoundary => {
+                self.asserts.push((self.out.len(), e));
+            }
+            Expr::Group { ref e, .. } => self.expr(e),
+            Expr::Repeat { ref e, r, .. } => {
+                let (lo, hi) = bounds(r);
+                let n = if is_target(e, r) && self.miss() {
+                    match hi {
+                        Some(hi) if lo == 0 || self.rng.gen() => hi + 1,
+                        _ => lo - 1,
+                    }
+                } else {
+                    let hi = hi.unwrap_or(max(lo, self.gen.max_repeat));
+                    self.rng.gen_range(lo, hi + 1)
+                };
+                for _ in 0..n {
+                    self.expr(e);
+                }
+            }
+            Expr::Concat(ref es) => {
+                for e in es {
+                    self.expr(e);
+                }
+            }
+            Expr::Alternate(ref es) => {
+                let i = self.rng.gen_range(0, es.len());
+                self.expr(&es[i]);
+            }
+        }
+    }
+
+    /// Returns true if the part of the expression being generated should
+    /// be violated.
+    fn miss(&mut self) -> bool {
+        match self.targets {
+            Some(n) if !self.missed => {
+                self.missed = self.rng.gen_range(0, n) == 0;
+                self.missed
+            }
+            _ => false,
+        }
+    }
+
+    fn asserts_hold(&self) -> bool {
+        self.asserts.iter().all(|&(at, e)| {
+            let prev = self.out[..at].chars().next_back();
+            let next = self.out[at..].chars().next();
+            let is_word = |c: Option<char>| c.map_or(false, is_word_char);
+            match *e {
+                Expr::StartLine => prev.map_or(true, |c| c == '\n'),
+                Expr::EndLine => next.map_or(true, |c| c == '\n'),
+                Expr::StartText => prev.is_none(),
+                Expr::EndText => next.is_none(),
+                Expr::WordBoundary => is_word(prev) != is_word(next),
+                Expr::NotWordBoundary => is_word(prev) == is_word(next),
+                _ => unreachable!(),
+            }
+        })
+    }
+
+    /// Pushes `c`, or one of its case variants if `casei` is set.
+    fn case_variant(&mut self, c: char, casei: bool) {
+        let c = if casei { self.pick_variant(c) } else { c };
+        self.out.push(c);
+    }
+
+    /// Returns a character that isn't `c` (or a case variant of it, if
+    /// `casei` is set).
+    fn other_char(&mut self, c: char, casei: bool) -> char {
+        let all = [ClassRange::new('\0', char::MAX)];
+        loop {
+            let other = self.pick(&all);
+            let same = if casei {
+                simple_case_fold(other) == simple_case_fold(c)
+            } else {
+                other == c
+            };
+            if !same {
+                return other
+            }
+        }
+    }
+
+    /// Returns a character in `outside` that `cls` doesn't match.
+    ///
+    /// Characters outside of a case insensitive class may still fold into
+    /// it. If no such character is found, then the class isn't violated
+    /// after all.
+    fn pick_outside(&mut self, cls: &CharClass, outside: &[ClassRange])
+                   -> Option<char> {
+        for _ in 0..MAX_ATTEMPTS {
+            let c = self.pick(outside);
+            if !cls.matches(c) {
+                return Some(c)
+            }
+        }
+        self.missed = false;
+        None
+    }
+
+    /// Returns a random character with the same simple case folding as `c`.
+    fn pick_variant(&mut self, c: char) -> char {
+        let folded = simple_case_fold(c);
+        let mut variants = vec![folded];
+        variants.extend(case_folding::C_plus_S_table.iter()
+                        .filter(|&&(_, to)| to == folded)
+                        .map(|&(from, _)| from));
+        if self.prefer_ascii() && variants.iter().any(|c| c.is_ascii()) {
+            variants.retain(|c| c.is_ascii());
+        }
+        variants[self.rng.gen_range(0, variants.len())]
+    }
+
+    /// Returns a random character from the given (non-empty) ranges.
+    fn pick(&mut self, ranges: &[ClassRange]) -> char {
+        if self.prefer_ascii() {
+            let ascii: Vec<ClassRange> = ranges.iter()
+                .take_while(|r| r.start <= '\x7F')
+                .map(|r| ClassRange::new(r.start, min(r.end, '\x7F')))
+                .collect();
+            if !ascii.is_empty() {
+                return uniform(self.rng, &ascii)
+            }
+        }
+        uniform(self.rng, ranges)
+    }
+
+    fn prefer_ascii(&mut self) -> bool {
+        self.gen.ascii_bias && self.rng.gen_range(0, 10) != 0
+    }
+}
+
+/// Returns a character picked uniformly from the given (non-empty) ranges.
+fn uniform<R: Rng>(rng: &mut R, ranges: &[ClassRange]) -> char {
+    let size = |r: &ClassRange| r.end as u32 - r.start as u32 + 1;
+    let total = ranges.iter().fold(0, |n, r| n + size(r));
+    loop {
+        let mut n = rng.gen_range(0, total);
+        for r in ranges {
+            if n < size(r) {
+                // Ranges may cover surrogates, which are picked again.
+                if let Some(c) = char::from_u32(r.start as u32 + n) {
+                    return c
+                }
+                break
+            }
+            n -= size(r);
+        }
+    }
+}
+
+/// Returns the ranges of every character that isn't in the given sorted,
+/// non-overlapping ranges.
+fn complement(ranges: &[ClassRange]) -> Vec<ClassRange> {
+    let mut out = vec![];
+    let mut next = Some('\0');
+    for r in ranges {
+        if let Some(n) = next {
+            if n < r.start {
+                out.push(ClassRange::new(n, dec_char(r.start)));
+            }
+        }
+        next = if r.end == char::MAX { None } else { Some(inc_char(r.end)) };
+    }
+    if let Some(n) = next {
+        out.push(ClassRange::new(n, char::MAX));
+    }
+    out
+}
+
+/// Returns the lower and upper bounds of a repeat.
+fn bounds(r: Repeater) -> (u32, Option<u32>) {
+    match r {
+        Repeater::ZeroOrOne => (0, Some(1)),
+        Repeater::ZeroOrMore => (0, None),
+        Repeater::OneOrMore => (1, None),
+        Repeater::Range { min, max } => (min, max),
+    }
+}
+
+/// Returns true if a repeat can be violated by repeating it too few or too
+/// many times. Repeating something empty can't change anything.
+fn is_target(e: &Expr, r: Repeater) -> bool {
+    let (lo, hi) = bounds(r);
+    (lo > 0 || hi.is_some()) && *e != Expr::Empty
+}
+
+/// Returns the number of parts of an expression that `near_miss` can
+/// violate.
+fn count_targets(e: &Expr) -> u32 {
+    match *e {
+        Expr::Literal { .. } | Expr::AnyCharNoNL => 1,
+        Expr::Class(ref cls) => {
+            if complement(cls).is_empty() { 0 } else { 1 }
+        }
+        Expr::Group { ref e, .. } => count_targets(e),
+        Expr::Repeat { ref e, r, .. } => {
+            let own = if is_target(e, r) { 1 } else { 0 };
+            own + count_targets(e)
+        }
+        Expr::Concat(ref es) | Expr::Alternate(ref es) => {
+            es.iter().map(count_targets).fold(0, |a, b| a + b)
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, XorShiftRng};
+
+    use Expr;
+    use super::Generator;
+
+    fn rng() -> XorShiftRng {
+        XorShiftRng::from_seed([1, 2, 3, 4])
+    }
+
+    fn gen(re: &str) -> Generator {
+        Generator::new(Expr::parse(re).unwrap())
+    }
+
+    #[test]
+    fn literals_and_alternates() {
+        let mut rng = rng();
+        for _ in 0..100 {
+            let s = gen("ab|cd").generate(&mut rng).unwrap();
+            assert!(s == "ab" || s == "cd");
+        }
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let mut rng = rng();
+        let g = gen("(?i)k").ascii_bias(false);
+        let mut seen: Vec<String> = (0..200).map(|_| {
+            g.generate(&mut rng).unwrap()
+        }).collect();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen, vec!["K", "k", "\u{212A}"]);
+    }
+
+    #[test]
+    fn classes() {
+        let mut rng = rng();
+        for _ in 0..100 {
+            let s = gen("[a-c][^a-z]").generate(&mut rng).unwrap();
+            let cs: Vec<char> = s.chars().collect();
+            assert_eq!(cs.len(), 2);
+            assert!('a' <= cs[0] && cs[0] <= 'c');
+            assert!(!('a' <= cs[1] && cs[1] <= 'z'));
+        }
+    }
+
+    #[test]
+    fn repeats_are_capped() {
+        let mut rng = rng();
+        let g = gen("a*b{2,}c{3}").max_repeat(2);
+        for _ in 0..100 {
+            let s = g.generate(&mut rng).unwrap();
+            let count = |c| s.chars().filter(|&x| x == c).count();
+            assert!(count('a') <= 2);
+            assert_eq!((count('b'), count('c')), (2, 3));
+        }
+    }
+
+    #[test]
+    fn assertions() {
+        let mut rng = rng();
+        for _ in 0..100 {
+            let s = gen(r"[a ]\b[a ]").generate(&mut rng).unwrap();
+            assert!(s == "a " || s == " a");
+        }
+        assert_eq!(gen(r"a^b").generate(&mut rng), None);
+        assert_eq!(gen(r"(?m)a$\nb").generate(&mut rng),
+                   Some("a\nb".to_string()));
+    }
+
+    #[test]
+    fn near_misses() {
+        let mut rng = rng();
+        for _ in 0..100 {
+            let s = gen(r"ab{2}").near_miss(&mut rng).unwrap();
+            assert!(s != "abb");
+        }
+        assert_eq!(gen(r"(?s).*").near_miss(&mut rng), None);
+        assert_eq!(gen(r"x?").near_miss(&mut rng).map(|s| s.len()), Some(2));
+    }
+}
@@ -63,9 +63,14 @@ assert_eq!(err.kind(), &ErrorKind::UnclosedParen);
 #![deny(missing_docs)]
 
 #[cfg(test)] extern crate quickcheck;
-#[cfg(test)] extern crate rand;
+#[cfg(any(test, feature = "rand"))] extern crate rand;
 
+#[cfg(feature = "rand")]
+mod generate;
+mod fold;
+mod normalize;
 mod parser;
+mod simplify;
 mod unicode;
 
 use std::char;
@@ -82,11 +87,14 @@ use self::Expr::*;
 use self::Repeater::*;
 
 pub use parser::is_punct;
+pub use normalize::{MAX_MARK_ORDERS, combining_class};
+#[cfg(feature = "rand")]
+pub use generate::Generator;
 
 /// A regular expression abstract syntax tree.
 ///
 /// An `Expr` represents the abstract syntax of a regular expression.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Expr {
     /// An empty regex (which never matches any text).
     Empty,
@@ -154,7 +162,7 @@ type CaptureIndex = Option<usize>;
 type CaptureName = Option<String>;
 
 /// The type of a repeat operator expression.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Repeater {
     /// Match zero or one (`?`).
     ZeroOrOne,
@@ -196,7 +204,7 @@ pub enum Repeater {
 /// 1. Simple case folding has been applied to all ranges.
 /// 2. Simple case folding must be applied to a character before testing
 ///    whether it matches the character class.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct CharClass {
     ranges: Vec<ClassRange>,
     casei: bool,
@@ -210,7 +218,7 @@ pub struct CharClass {
 ///
 /// Note that this has a few convenient impls on `PartialEq` and `PartialOrd`
 /// for testing whether a character is contained inside a given range.
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct ClassRange {
     /// The start character of the range.
     ///
@@ -226,7 +234,17 @@ pub struct ClassRange {
 impl Expr {
     /// Parses a string in a regular expression syntax tree.
     pub fn parse(s: &str) -> Result<Expr> {
-        parser::Parser::parse(s).map(|e| e.simplify())
+        ExprBuilder::new().parse(s)
+    }
+
+    /// Parses a string in a regular expression syntax tree, and also
+    /// returns the span of each capture group in `s`.
+    ///
+    /// Spans are pairs of byte offsets `(start, end)` into `s`, indexed by
+    /// the number of the capture group. A group's span covers its
+    /// parentheses. The span at index `0` covers all of `s`.
+    pub fn parse_with_spans(s: &str) -> Result<(Expr, Vec<(usize, usize)>)> {
+        ExprBuilder::new().parse_with_spans(s)
     }
 
     /// Returns true iff the expression can be repeated by a quantifier.
@@ -244,7 +262,10 @@ impl Expr {
         }
     }
 
-    fn simplify(self) -> Expr {
+    /// Merges adjacent literals and removes non-capturing groups around
+    /// expressions that can be repeated. This is all the parser does; see
+    /// `simplify` for more.
+    fn tidy(self) -> Expr {
         fn combine_literals(es: &mut Vec<Expr>, e: Expr) {
             match (es.pop(), e) {
                 (None, e) => es.push(e),
@@ -266,12 +287,12 @@ impl Expr {
         }
         match self {
             Repeat { e, r, greedy } => Repeat {
-                e: Box::new(e.simplify()),
+                e: Box::new(e.tidy()),
                 r: r,
                 greedy: greedy,
             },
             Group { e, i, name } => {
-                let e = e.simplify();
+                let e = e.tidy();
                 if i.is_none() && name.is_none() && e.can_repeat() {
                     e
                 } else {
@@ -281,7 +302,7 @@ impl Expr {
             Concat(es) => {
                 let mut new_es = Vec::with_capacity(es.len());
                 for e in es {
-                    combine_literals(&mut new_es, e.simplify());
+                    combine_literals(&mut new_es, e.tidy());
                 }
                 if new_es.len() == 1 {
                     new_es.pop().unwrap()
@@ -290,13 +311,87 @@ impl Expr {
                 }
             }
             Alternate(es) => Alternate(es.into_iter()
-                                         .map(|e| e.simplify())
+                                         .map(|e| e.tidy())
                                          .collect()),
             e => e,
         }
     }
 }
 
+/// A builder for parsing expressions with some flags set from the start.
+///
+/// Setting a flag here is the same as starting the pattern with it (e.g.,
+/// `(?i)`), except that the pattern is left as is. So the positions in
+/// errors and the spans of capture groups are positions in the pattern
+/// given. A flag set here can still be cleared in the pattern, e.g., with
+/// `(?-i)`.
+///
+/// # Example
+///
+/// ```rust
+/// use regex_syntax::{Expr, ExprBuilder};
+///
+/// let e = ExprBuilder::new().case_insensitive(true).parse("a").unwrap();
+/// assert_eq!(e, Expr::parse("(?i)a").unwrap());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ExprBuilder {
+    flags: parser::Flags,
+}
+
+impl ExprBuilder {
+    /// Returns a builder with every flag cleared, which parses the same
+    /// way as `Expr::parse`.
+    pub fn new() -> ExprBuilder {
+        ExprBuilder { flags: parser::Flags::default() }
+    }
+
+    /// Sets the case insensitive flag (`i`).
+    pub fn case_insensitive(mut self, yes: bool) -> ExprBuilder {
+        self.flags.casei = yes;
+        self
+    }
+
+    /// Sets the multi-line flag (`m`).
+    pub fn multi_line(mut self, yes: bool) -> ExprBuilder {
+        self.flags.multi = yes;
+        self
+    }
+
+    /// Sets the flag that makes `.` match new lines (`s`).
+    pub fn dot_matches_new_line(mut self, yes: bool) -> ExprBuilder {
+        self.flags.dotnl = yes;
+        self
+    }
+
+    /// Sets the flag that swaps the meaning of greedy and non-greedy
+    /// repetition operators (`U`).
+    pub fn swap_greed(mut self, yes: bool) -> ExprBuilder {
+        self.flags.swap_greed = yes;
+        self
+    }
+
+    /// Sets the flag that ignores whitespace and allows comments (`x`).
+    pub fn ignore_whitespace(mut self, yes: bool) -> ExprBuilder {
+        self.flags.ignore_space = yes;
+        self
+    }
+
+    /// Parses a string in a regular expression syntax tree.
+    pub fn parse(self, s: &str) -> Result<Expr> {
+        self.parse_with_spans(s).map(|(e, _)| e)
+    }
+
+    /// Parses a string in a regular expression syntax tree, and also
+    /// returns the span of each capture group in `s`, as in
+    /// `Expr::parse_with_spans`.
+    pub fn parse_with_spans(self, s: &str)
+                           -> Result<(Expr, Vec<(usize, usize)>)> {
+        parser::Parser::parse_with_spans(s, self.flags)
+            .map(|(e, spans)| (e.tidy(), spans))
+    }
+}
+
 impl Deref for CharClass {
     type Target = Vec<ClassRange>;
     fn deref(&self) -> &Vec<ClassRange> { &self.ranges }
@@ -320,8 +415,8 @@ impl CharClass {
         CharClass { ranges: ranges, casei: false }
     }
 
-    /// Create an empty class.
-    fn empty() -> CharClass {
+    /// Create an empty class, which doesn't match any character.
+    pub fn empty() -> CharClass {
         CharClass::new(Vec::new())
     }
 
@@ -615,6 +710,11 @@ impl fmt::Display for Repeater {
 
 impl fmt::Display for CharClass {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            // `[]` isn't valid syntax, so write the negation of every
+            // character instead.
+            return write!(f, "[^\\x00-\\x{{10FFFF}}]");
+        }
         if self.casei {
             try!(write!(f, "(?i:"));
         }
@@ -898,6 +998,15 @@ pub fn simple_case_fold(c: char) -> char {
         .unwrap_or(c)
 }
 
+/// Returns every character whose simple case folding isn't itself, paired
+/// with its folding. The pairs are sorted by their first character.
+///
+/// N.B. This is hidden for the same reason as `simple_case_fold`.
+#[doc(hidden)]
+pub fn simple_case_folding_table() -> &'static [(char, char)] {
+    case_folding::C_plus_S_table
+}
+
 /// The result of binary search on the simple case folding table.
 ///
 /// This level of detail is exposed so that we can do case folding on a
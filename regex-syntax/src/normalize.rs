@@ -0,0 +1,540 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::char;
+
+use {CharClass, ClassRange, Expr};
+use unicode::normalization::{Combining_Class_table, Decomposition_table};
+
+// Hangul syllables are decomposed algorithmically, see section 3.12 of the
+// Unicode standard.
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// The most orders that the combining marks after a character may be
+/// written in for `Expr::canonical_equivalence` to spell them all, e.g.,
+/// six marks of different classes.
+pub const MAX_MARK_ORDERS: usize = 720;
+
+impl Expr {
+    /// Rewrites the literals and classes of the expression so that they
+    /// also match text that is canonically equivalent to them.
+    ///
+    /// Canonically equivalent strings are the same text written with
+    /// different code points, e.g., `é` (U+00E9) and `e` followed by a
+    /// combining acute accent (U+0301), or combining marks in a different
+    /// order. Two strings are canonically equivalent if their NFD
+    /// normalizations are the same.
+    ///
+    /// Each literal or class affected is rewritten into an alternation of
+    /// the ways to spell it: precomposed or decomposed, and with its
+    /// combining marks in any order that doesn't change its meaning.
+    /// Other parts of the expression are left alone, so `.` and negated
+    /// classes still match a single character, which may be part of a
+    /// decomposed sequence. Classes don't match decomposed Hangul
+    /// syllables.
+    ///
+    /// The expression still matches a character without the combining
+    /// marks after it (e.g., `e` in `e\u{301}`), so a matcher that keeps
+    /// characters whole should also check that a match doesn't end before
+    /// a combining mark (see `combining_class`).
+    ///
+    /// Since the number of orders grows factorially with the number of
+    /// marks, `None` is returned if a literal has a character whose marks
+    /// can be written in more than `MAX_MARK_ORDERS` orders.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use regex_syntax::Expr;
+    /// let e = Expr::parse("año").unwrap().canonical_equivalence().unwrap();
+    /// assert_eq!(e.to_string(), "(?:a(?:n\u{303}|ñ)o)");
+    /// ```
+    pub fn canonical_equivalence(self) -> Option<Expr> {
+        Some(match self {
+            Expr::Literal { chars, casei } => {
+                let nfd = decompose(&chars);
+                let orders = clusters(&nfd).iter().map(|c| orders(&c.1))
+                                                  .max().unwrap_or(1);
+                if orders > MAX_MARK_ORDERS {
+                    return None
+                }
+                match equivalent_literal(&nfd, casei) {
+                    e @ Expr::Literal { .. } => e,
+                    e => group(e),
+                }
+            }
+            Expr::Class(cls) => equivalent_class(cls),
+            Expr::Group { e, i, name } => {
+                let e = match e.canonical_equivalence() {
+                    None => return None,
+                    Some(e) => Box::new(e),
+                };
+                Expr::Group { e: e, i: i, name: name }
+            }
+            Expr::Repeat { e, r, greedy } => {
+                let e = match e.canonical_equivalence() {
+                    None => return None,
+                    Some(e) => Box::new(e),
+                };
+                Expr::Repeat { e: e, r: r, greedy: greedy }
+            }
+            Expr::Concat(es) => {
+                let es = es.into_iter().map(Expr::canonical_equivalence);
+                match es.collect() {
+                    None => return None,
+                    Some(es) => Expr::Concat(es),
+                }
+            }
+            Expr::Alternate(es) => {
+                let es = es.into_iter().map(Expr::canonical_equivalence);
+                match es.collect() {
+                    None => return None,
+                    Some(es) => Expr::Alternate(es),
+                }
+            }
+            e => e,
+        })
+    }
+}
+
+/// A starter (a character with combining class 0) and the combining marks
+/// after it, in canonical order. Marks at the start of a string have no
+/// starter.
+type Cluster = (Option<char>, Vec<char>);
+
+/// One way to spell the start of a sequence of clusters: some characters,
+/// each of which spells the first `span` clusters except for the marks in
+/// `rest`.
+struct Head {
+    span: usize,
+    rest: Vec<char>,
+    chars: Vec<char>,
+}
+
+fn group(e: Expr) -> Expr {
+    Expr::Group { e: Box::new(e), i: None, name: None }
+}
+
+/// Returns the canonical combining class of `c`.
+///
+/// It's `0` for starters, i.e., characters that aren't combining marks.
+pub fn combining_class(c: char) -> u8 {
+    let found = Combining_Class_table.binary_search_by(|&(s, e, _)| {
+        if c < s {
+            ::std::cmp::Ordering::Greater
+        } else if c > e {
+            ::std::cmp::Ordering::Less
+        } else {
+            ::std::cmp::Ordering::Equal
+        }
+    });
+    match found {
+        Ok(i) => Combining_Class_table[i].2,
+        Err(_) => 0,
+    }
+}
+
+/// Returns the NFD normalization of `chars`.
+fn decompose(chars: &[char]) -> Vec<char> {
+    let mut nfd = vec![];
+    for &c in chars {
+        let n = c as u32;
+        if S_BASE <= n && n < S_BASE + S_COUNT {
+            let s = n - S_BASE;
+            nfd.push(jamo(L_BASE + s / N_COUNT));
+            nfd.push(jamo(V_BASE + (s % N_COUNT) / T_COUNT));
+            if s % T_COUNT != 0 {
+                nfd.push(jamo(T_BASE + s % T_COUNT));
+            }
+            continue
+        }
+        match Decomposition_table.binary_search_by(|&(x, _)| x.cmp(&c)) {
+            Ok(i) => nfd.extend(Decomposition_table[i].1.iter().cloned()),
+            Err(_) => nfd.push(c),
+        }
+    }
+    // The sort is stable, so marks with the same class keep their order.
+    let mut i = 0;
+    while i < nfd.len() {
+        let n = marks(&nfd[i..]);
+        nfd[i..i + n].sort_by_key(|&c| combining_class(c));
+        i += n + 1;
+    }
+    nfd
+}
+
+fn jamo(n: u32) -> char {
+    char::from_u32(n).unwrap()
+}
+
+/// Returns the number of combining marks at the start of `chars`.
+fn marks(chars: &[char]) -> usize {
+    chars.iter().take_while(|&&c| combining_class(c) != 0).count()
+}
+
+fn clusters(nfd: &[char]) -> Vec<Cluster> {
+    let mut cs = vec![];
+    let mut i = 0;
+    while i < nfd.len() {
+        let starter = if combining_class(nfd[i]) == 0 {
+            i += 1;
+            Some(nfd[i - 1])
+        } else {
+            None
+        };
+        let n = marks(&nfd[i..]);
+        cs.push((starter, nfd[i..i + n].to_vec()));
+        i += n;
+    }
+    cs
+}
+
+/// Returns `c` and the characters that decompose to just `c`.
+fn equivalents(c: char) -> Vec<char> {
+    let mut cs = vec![c];
+    for &(x, d) in Decomposition_table {
+        if d == &[c] {
+            cs.push(x);
+        }
+    }
+    cs
+}
+
+/// Removes `used` from `marks`, if the marks left over can follow `used`
+/// without changing their canonical order.
+fn remove_marks(marks: &[char], used: &[char]) -> Option<Vec<char>> {
+    let mut rest = marks.to_vec();
+    for &u in used {
+        let class = combining_class(u);
+        match rest.iter().position(|&m| combining_class(m) == class) {
+            Some(i) if rest[i] == u => { rest.remove(i); }
+            _ => return None,
+        }
+    }
+    Some(rest)
+}
+
+/// Returns every way to spell the start of `cs`.
+fn heads(cs: &[Cluster]) -> Vec<Head> {
+    let mut heads: Vec<Head> = vec![];
+    {
+        let mut add = |span: usize, rest: Vec<char>, c: char| {
+            match heads.iter().position(|h| h.span == span && h.rest == rest) {
+                Some(i) => heads[i].chars.push(c),
+                None => heads.push(Head {
+                    span: span,
+                    rest: rest,
+                    chars: vec![c],
+                }),
+            }
+        };
+        let s = match cs[0] {
+            (Some(s), ref marks) => {
+                for c in equivalents(s) {
+                    add(1, marks.clone(), c);
+                }
+                s
+            }
+            (None, ref marks) => {
+                let chars = vec![];
+                return vec![Head { span: 1, rest: marks.clone(), chars: chars }]
+            }
+        };
+        for &(c, d) in Decomposition_table {
+            if d.len() < 2 || d[0] != s {
+                continue
+            }
+            let dcs = clusters(d);
+            let last = dcs.len() - 1;
+            if dcs.len() > cs.len()
+                || dcs[..last] != cs[..last]
+                || dcs[last].0 != cs[last].0 {
+                continue
+            }
+            if let Some(rest) = remove_marks(&cs[last].1, &dcs[last].1) {
+                add(dcs.len(), rest, c);
+            }
+        }
+        // A leading and a vowel jamo make a syllable, which a trailing jamo
+        // may be added to.
+        if cs.len() >= 2 && cs[0].1.is_empty() {
+            let (l, v) = (s as u32, cs[1].0.unwrap_or('\0') as u32);
+            if L_BASE <= l && l < L_BASE + L_COUNT
+                && V_BASE <= v && v < V_BASE + V_COUNT {
+                let lv = S_BASE + (l - L_BASE) * N_COUNT
+                         + (v - V_BASE) * T_COUNT;
+                add(2, cs[1].1.clone(), jamo(lv));
+                let t = cs.get(2).and_then(|c| c.0).unwrap_or('\0') as u32;
+                if cs[1].1.is_empty()
+                    && T_BASE < t && t < T_BASE + T_COUNT {
+                    add(3, cs[2].1.clone(), jamo(lv + t - T_BASE));
+                }
+            }
+        }
+    }
+    heads
+}
+
+/// Returns an expression matching every string whose NFD normalization is
+/// `nfd`.
+fn equivalent_literal(nfd: &[char], casei: bool) -> Expr {
+    let cs = clusters(nfd);
+    let heads: Vec<Vec<Head>> = (0..cs.len()).map(|i| heads(&cs[i..]))
+                                             .collect();
+    // Spell the clusters in pieces that no head spans the end of, so that
+    // the rest of the literal isn't repeated for every alternative.
+    let mut es = vec![];
+    let (mut start, mut reach) = (0, 0);
+    for i in 0..cs.len() {
+        for h in &heads[i] {
+            reach = ::std::cmp::max(reach, i + h.span);
+        }
+        if reach == i + 1 {
+            es.push(spell(&heads, start, i + 1, casei));
+            start = i + 1;
+        }
+    }
+    concat(es)
+}
+
+/// Returns an expression matching every spelling of the clusters from
+/// `start` to `end`.
+fn spell(heads: &[Vec<Head>], start: usize, end: usize, casei: bool) -> Expr {
+    if start == end {
+        return Expr::Empty
+    }
+    alternate(heads[start].iter().map(|h| {
+        concat(vec![
+            one_of(h.chars.clone(), casei),
+            any_order(&h.rest, casei),
+            spell(heads, start + h.span, end, casei),
+        ])
+    }).collect())
+}
+
+/// Returns the number of orders `marks` can be written in without changing
+/// their canonical ordering, or a number over `MAX_MARK_ORDERS` if there
+/// are more than that.
+fn orders(marks: &[char]) -> usize {
+    // Marks with the same class keep their order, so this is the
+    // multinomial coefficient of the number of marks of each class.
+    let mut classes = vec![];
+    let mut orders = 1;
+    for (n, &m) in marks.iter().enumerate() {
+        let class = combining_class(m);
+        classes.push(class);
+        let same = classes.iter().filter(|&&c| c == class).count();
+        orders = orders * (n + 1) / same;
+        if orders > MAX_MARK_ORDERS {
+            break
+        }
+    }
+    orders
+}
+
+/// Returns an expression matching the combining marks in any order with
+/// the same canonical ordering.
+fn any_order(marks: &[char], casei: bool) -> Expr {
+    let mut alts = vec![];
+    for (i, &m) in marks.iter().enumerate() {
+        // Marks with the same class can't be swapped.
+        let class = combining_class(m);
+        if marks[..i].iter().any(|&n| combining_class(n) == class) {
+            continue
+        }
+        let mut rest = marks.to_vec();
+        rest.remove(i);
+        alts.push(concat(vec![
+            one_of(equivalents(m), casei),
+            any_order(&rest, casei),
+        ]));
+    }
+    alternate(alts)
+}
+
+fn equivalent_class(cls: CharClass) -> Expr {
+    let casei = cls.is_case_insensitive();
+    let mut nfds: Vec<&[char]> = vec![];
+    for &(c, d) in Decomposition_table {
+        let matched = cls.matches(c) || (d.len() == 1 && cls.matches(d[0]));
+        if matched && !nfds.contains(&d) {
+            nfds.push(d);
+        }
+    }
+    if nfds.is_empty() {
+        return Expr::Class(cls)
+    }
+    // Characters equivalent to a character in the class are added to it.
+    // Otherwise, spellings that end with the same marks are grouped, so
+    // that big classes like `\w` don't become huge alternations.
+    let mut ranges = cls.ranges.clone();
+    let mut tails: Vec<(Vec<char>, Vec<char>)> = vec![];
+    let mut others = vec![];
+    for d in nfds {
+        let cs = clusters(d);
+        if cs.len() > 1 {
+            others.push(equivalent_literal(d, casei));
+            continue
+        }
+        for h in heads(&cs) {
+            if h.rest.is_empty() {
+                ranges.extend(h.chars.into_iter()
+                                     .map(|c| ClassRange::new(c, c)));
+                continue
+            }
+            match tails.iter().position(|t| t.0 == h.rest) {
+                Some(i) => tails[i].1.extend(h.chars),
+                None => tails.push((h.rest, h.chars)),
+            }
+        }
+    }
+    let mut alts = vec![Expr::Class(class(ranges, casei))];
+    for (rest, chars) in tails {
+        alts.push(concat(vec![
+            one_of(chars, casei),
+            any_order(&rest, casei),
+        ]));
+    }
+    alts.extend(others);
+    group(Expr::Alternate(alts))
+}
+
+fn class(ranges: Vec<ClassRange>, casei: bool) -> CharClass {
+    let cls = CharClass::new(ranges).canonicalize();
+    if casei { cls.case_fold() } else { cls }
+}
+
+/// Returns an expression matching any one of `chars`.
+fn one_of(mut chars: Vec<char>, casei: bool) -> Expr {
+    chars.sort();
+    chars.dedup();
+    match chars.len() {
+        0 => Expr::Empty,
+        1 => Expr::Literal { chars: chars, casei: casei },
+        _ => {
+            let ranges = chars.into_iter().map(|c| ClassRange::new(c, c));
+            Expr::Class(class(ranges.collect(), casei))
+        }
+    }
+}
+
+fn alternate(mut es: Vec<Expr>) -> Expr {
+    match es.len() {
+        0 => Expr::Empty,
+        1 => es.pop().unwrap(),
+        _ => group(Expr::Alternate(es)),
+    }
+}
+
+/// Concatenates expressions, merging adjacent literals.
+fn concat(es: Vec<Expr>) -> Expr {
+    let mut new_es: Vec<Expr> = vec![];
+    for e in es {
+        let es = match e {
+            Expr::Empty => vec![],
+            Expr::Concat(es) => es,
+            e => vec![e],
+        };
+        for e in es {
+            match (new_es.last_mut(), e) {
+                (Some(&mut Expr::Literal { ref mut chars, casei }),
+                 Expr::Literal { chars: ref more, casei: casei2 })
+                if casei == casei2 => {
+                    chars.extend(more.iter().cloned());
+                }
+                (_, e) => new_es.push(e),
+            }
+        }
+    }
+    match new_es.len() {
+        0 => Expr::Empty,
+        1 => new_es.pop().unwrap(),
+        _ => Expr::Concat(new_es),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Expr;
+    use super::decompose;
+
+    fn equiv(re: &str) -> String {
+        Expr::parse(re).unwrap().canonical_equivalence().unwrap().to_string()
+    }
+
+    #[test]
+    fn decomposes() {
+        assert_eq!(decompose(&['é']), vec!['e', '\u{301}']);
+        assert_eq!(decompose(&['Å']), vec!['A', '\u{30a}']);
+        assert_eq!(decompose(&['각']), vec!['\u{1100}', '\u{1161}',
+                                            '\u{11a8}']);
+        // Marks are put in canonical order.
+        assert_eq!(decompose(&['q', '\u{301}', '\u{323}']),
+                   vec!['q', '\u{323}', '\u{301}']);
+        assert_eq!(decompose(&['ệ']), vec!['e', '\u{323}', '\u{302}']);
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(equiv("abc"), "abc");
+        assert_eq!(equiv("(?i)abc"), "(?i:abc)");
+        // U+0341 decomposes to U+0301.
+        assert_eq!(equiv("e\u{301}"),
+                   "(?:(?:e[\u{301}-\u{301}\u{341}-\u{341}]|é))");
+        assert_eq!(
+            equiv("(?i)é"),
+            "(?:(?:(?i:e)(?i:[\u{301}-\u{301}\u{341}-\u{341}])|(?i:é)))");
+        // The marks can be written in either order, and either one can be
+        // precomposed.
+        assert_eq!(equiv("ệ"),
+                   "(?:(?:e(?:\u{323}\u{302}|\u{302}\u{323})|ê\u{323}\
+                    |ẹ\u{302}|ệ))");
+    }
+
+    #[test]
+    fn hangul() {
+        assert_eq!(equiv("가"), "(?:(?:\u{1100}\u{1161}|가))");
+        assert_eq!(equiv("각"),
+                   "(?:(?:\u{1100}\u{1161}\u{11a8}|가\u{11a8}|각))");
+    }
+
+    #[test]
+    fn several_starters() {
+        assert_eq!(equiv("ো"), "(?:(?:\u{9c7}\u{9be}|ো))");
+        assert_eq!(equiv("[ো]"), "(?:[ো-ো]|(?:\u{9c7}\u{9be}|ো))");
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(equiv("[a-z]"), "[a-z]");
+        assert_eq!(equiv("[\u{2126}]"),
+                   "(?:[\u{3a9}-\u{3a9}\u{2126}-\u{2126}])");
+        assert_eq!(equiv("[é]"),
+                   "(?:[é-é]|e[\u{301}-\u{301}\u{341}-\u{341}])");
+    }
+
+    #[test]
+    fn roundtrips() {
+        for re in &["(?i)café|(ñ)+", "[à-ä]*x", "(?P<n>각)", r"\w"] {
+            let e = Expr::parse(re).unwrap().canonical_equivalence()
+                                             .unwrap();
+            assert_eq!(Expr::parse(&e.to_string()).unwrap().simplify(),
+                       e.simplify());
+        }
+    }
+}
@@ -30,6 +30,7 @@ pub struct Parser {
     stack: Vec<Build>,
     caps: usize,
     names: Vec<String>, // to check for duplicates
+    spans: Vec<(usize, usize)>, // char offsets of each capture group
     flags: Flags,
 }
 
@@ -51,31 +52,30 @@ enum Build {
 }
 
 /// Flag state.
-#[derive(Clone, Copy, Debug)]
-struct Flags {
-    casei: bool,
-    multi: bool,
-    dotnl: bool,
-    swap_greed: bool,
-    ignore_space: bool,
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Flags {
+    pub casei: bool,
+    pub multi: bool,
+    pub dotnl: bool,
+    pub swap_greed: bool,
+    pub ignore_space: bool,
 }
 
 // Primary expression parsing routines.
 impl Parser {
-    pub fn parse(s: &str) -> Result<Expr> {
+    // Parses an expression, starting with the flags given, and also returns
+    // the byte span of each capture group in `s`, indexed by the group's
+    // number. The span at index `0` is all of `s`.
+    pub fn parse_with_spans(s: &str, flags: Flags)
+                           -> Result<(Expr, Vec<(usize, usize)>)> {
         Parser {
             chars: s.chars().collect(),
             chari: 0,
             stack: vec![],
             caps: 0,
             names: vec![],
-            flags: Flags {
-                casei: false,
-                multi: false,
-                dotnl: false,
-                swap_greed: false,
-                ignore_space: false,
-            },
+            spans: vec![],
+            flags: flags,
         }.parse_expr()
     }
 
@@ -83,7 +83,7 @@ impl Parser {
     //
     // Starts at the beginning of the input and consumes until either the end
     // of input or an error.
-    fn parse_expr(mut self) -> Result<Expr> {
+    fn parse_expr(mut self) -> Result<(Expr, Vec<(usize, usize)>)> {
         while !self.eof() {
             let build_expr = match self.cur() {
                 '\\' => try!(self.parse_escape()),
@@ -134,7 +134,23 @@ impl Parser {
                 self.stack.push(build_expr);
             }
         }
-        self.finish_concat()
+        let e = try!(self.finish_concat());
+        Ok((e, self.byte_spans()))
+    }
+
+    // Converts the char offsets of the capture groups to byte offsets, and
+    // adds the span of the entire expression at index `0`.
+    fn byte_spans(&self) -> Vec<(usize, usize)> {
+        let mut offsets = Vec::with_capacity(self.chars.len() + 1);
+        let mut offset = 0;
+        for c in &self.chars {
+            offsets.push(offset);
+            offset += c.len_utf8();
+        }
+        offsets.push(offset);
+        let mut spans = vec![(0, offset)];
+        spans.extend(self.spans.iter().map(|&(s, e)| (offsets[s], offsets[e])));
+        spans
     }
 
     // Parses an escape sequence, e.g., \Ax
@@ -212,6 +228,8 @@ impl Parser {
             return self.parse_group_flags(chari);
         }
         self.caps = checkadd(self.caps, 1);
+        // The end of the span is filled in when the group is closed.
+        self.spans.push((chari, chari));
         Ok(Build::LeftParen {
             i: Some(self.caps),
             name: name,
@@ -927,6 +945,7 @@ impl Parser {
                         // e.g., ()
                         return Err(self.err(ErrorKind::EmptyGroup));
                     }
+                    self.close_span(i);
                     return Ok((old_flags, Build::Expr(Expr::Group {
                         e: Box::new(rev_concat(concat)),
                         i: i,
@@ -944,6 +963,7 @@ impl Parser {
                         None => return Err(self.err(ErrorKind::UnopenedParen)),
                         Some(Build::Expr(_)) => unreachable!(),
                         Some(Build::LeftParen { i, name, old_flags, .. }) => {
+                            self.close_span(i);
                             return Ok((old_flags, Build::Expr(Expr::Group {
                                 e: Box::new(Expr::Alternate(es)),
                                 i: i,
@@ -957,6 +977,14 @@ impl Parser {
         }
     }
 
+    // Records the end of the span of capture group `i`, which is closed by
+    // the parenthesis at the cursor.
+    fn close_span(&mut self, i: CaptureIndex) {
+        if let Some(i) = i {
+            self.spans[i - 1].1 = self.chari + 1;
+        }
+    }
+
     // Called only when the parser reaches the end of input.
     //
     // This pops the expression stack until:
@@ -1151,16 +1179,20 @@ const XDIGIT: Class = &[('0', '9'), ('A', 'F'), ('a', 'f')];
 
 #[cfg(test)]
 mod tests {
-    use { CharClass, ClassRange, Expr, Repeater, ErrorKind };
+    use { CharClass, ClassRange, Expr, ExprBuilder, Repeater, ErrorKind };
     use unicode::regex::{PERLD, PERLS, PERLW};
-    use super::Parser;
+    use { Result };
+    use super::{Flags, Parser};
     use super::{LOWER, UPPER};
 
     static YI: &'static [(char, char)] = &[
         ('\u{a000}', '\u{a48c}'), ('\u{a490}', '\u{a4c6}'),
     ];
 
-    fn p(s: &str) -> Expr { Parser::parse(s).unwrap() }
+    fn parse(s: &str) -> Result<Expr> {
+        Parser::parse_with_spans(s, Flags::default()).map(|(e, _)| e)
+    }
+    fn p(s: &str) -> Expr { parse(s).unwrap() }
     fn lit(c: char) -> Expr { Expr::Literal { chars: vec![c], casei: false } }
     fn liti(c: char) -> Expr { Expr::Literal { chars: vec![c], casei: true } }
     fn b<T>(v: T) -> Box<T> { Box::new(v) }
@@ -1439,6 +1471,32 @@ mod tests {
         });
     }
 
+    #[test]
+    fn group_spans() {
+        let spans = |s| {
+            Parser::parse_with_spans(s, Flags::default()).unwrap().1
+        };
+        assert_eq!(spans("a"), vec![(0, 1)]);
+        assert_eq!(spans("(a)(?:b)(?P<c>(d)|e)"),
+                   vec![(0, 20), (0, 3), (8, 20), (14, 17)]);
+        assert_eq!(spans("δ(ε)"), vec![(0, 6), (2, 6)]);
+        assert_eq!(spans(r"\((a)"), vec![(0, 5), (2, 5)]);
+    }
+
+    #[test]
+    fn builder_flags() {
+        let e = ExprBuilder::new().case_insensitive(true)
+                                  .parse("a(?-i)b").unwrap();
+        assert_eq!(e, Expr::parse("(?i)a(?-i)b").unwrap());
+        // The flags aren't added to the pattern, so positions are unchanged.
+        let err = ExprBuilder::new().case_insensitive(true)
+                                    .parse("ab)").unwrap_err();
+        assert_eq!(err.position(), 2);
+        let (_, spans) = ExprBuilder::new().multi_line(true)
+                                           .parse_with_spans("^(a)$").unwrap();
+        assert_eq!(spans, vec![(0, 5), (1, 4)]);
+    }
+
     #[test]
     fn group_no_capture() {
         assert_eq!(p("(?:a)"), Expr::Group {
@@ -2005,7 +2063,7 @@ mod tests {
 
     macro_rules! test_err {
         ($re:expr, $pos:expr, $kind:expr) => {{
-            let err = Parser::parse($re).unwrap_err();
+            let err = parse($re).unwrap_err();
             assert_eq!($pos, err.pos);
             assert_eq!($kind, err.kind);
             assert!($re.contains(&err.surround));
@@ -126,7 +126,7 @@ fn parser_never_panics() {
 
 impl Arbitrary for Expr {
     fn arbitrary<G: Gen>(g: &mut G) -> Expr {
-        fix_capture_indices(gen_expr(g, 0, ExprType::Anything)).simplify()
+        fix_capture_indices(gen_expr(g, 0, ExprType::Anything)).tidy()
     }
 
     fn shrink(&self) -> Box<Iterator<Item=Expr>> {
@@ -190,7 +190,7 @@ impl Arbitrary for Expr {
                            }))
             }
         };
-        Box::new(es.map(|e| fix_capture_indices(e).simplify()))
+        Box::new(es.map(|e| fix_capture_indices(e).tidy()))
     }
 }
 
@@ -395,7 +395,7 @@ fn display_regex_roundtrips() {
     // Given an AST, if we print it as a regex and then re-parse it, do we
     // get back the same AST?
     // A lot of this relies crucially on regex simplification. So this is
-    // testing `Expr::simplify` as much as it is testing the `Display` impl.
+    // testing `Expr::tidy` as much as it is testing the `Display` impl.
     fn prop(e: Expr) -> bool {
         e == Expr::parse(&e.to_string()).unwrap()
     }
@@ -405,3 +405,20 @@ fn display_regex_roundtrips() {
         .gen(StdGen::new(::rand::thread_rng(), 50))
         .quickcheck(prop as fn(Expr) -> bool);
 }
+
+#[test]
+fn simplify_roundtrips() {
+    // Simplifying is idempotent, and a simplified AST can be printed as a
+    // regex that parses back to the same AST (once simplified again, since
+    // the parser doesn't simplify as much).
+    fn prop(e: Expr) -> bool {
+        let e = e.simplify();
+        e.clone().simplify() == e
+        && Expr::parse(&e.to_string()).unwrap().simplify() == e
+    }
+    QuickCheck::new()
+        .tests(10_000)
+        .max_tests(20_000)
+        .gen(StdGen::new(::rand::thread_rng(), 50))
+        .quickcheck(prop as fn(Expr) -> bool);
+}
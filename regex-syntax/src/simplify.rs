@@ -0,0 +1,383 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Every rewrite here must preserve leftmost-first semantics, including
+// which capture groups participate in a match and where. This is why
+// repetitions are only merged when they repeat a single character, and why
+// only literal prefixes are factored out of alternations: those are the
+// cases where there is only one way to match each part.
+//
+// The builders below take simplified expressions, which never have a
+// non-capturing group at the top, and return simplified expressions with
+// non-capturing groups added wherever they're needed for the expression to
+// be written as a pattern.
+
+use std::collections::HashSet;
+
+use {Expr, Repeater};
+
+impl Expr {
+    /// Simplifies the expression without changing what it matches or what
+    /// its capture groups match.
+    ///
+    /// In particular:
+    ///
+    /// * Non-capturing groups are removed unless they're needed to write
+    ///   the expression as a pattern, and nested concatenations and
+    ///   alternations are flattened.
+    /// * Adjacent literals are merged, and a class of a single character
+    ///   becomes a literal.
+    /// * Alternatives that repeat an earlier alternative are removed, and
+    ///   common literal prefixes of adjacent alternatives are factored
+    ///   out, e.g., `abc|abd` becomes `ab(?:c|d)`.
+    /// * Redundant repetitions are collapsed, e.g., `x{1}` becomes `x`,
+    ///   `a*a*` becomes `a*` and `(?:a+)*` becomes `a*`.
+    ///
+    /// The result can be written as a pattern with its `Display`
+    /// implementation, and simplifying it again doesn't change it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use regex_syntax::Expr;
+    /// let e = Expr::parse(r"(?:[a][b])c|abd|x{1}y*y*").unwrap();
+    /// assert_eq!(e.simplify().to_string(), "ab(?:c|d)|xy*");
+    /// ```
+    pub fn simplify(self) -> Expr {
+        match self {
+            Expr::Group { e, i: None, name: None } => e.simplify(),
+            Expr::Group { e, i, name } => {
+                let e = match e.simplify() {
+                    // An empty group can't be written as a pattern.
+                    Expr::Empty => Expr::Repeat {
+                        e: Box::new(Expr::AnyChar),
+                        r: Repeater::Range { min: 0, max: Some(0) },
+                        greedy: true,
+                    },
+                    e => e,
+                };
+                Expr::Group { e: Box::new(e), i: i, name: name }
+            }
+            Expr::Repeat { e, r, greedy } => repeat(e.simplify(), r, greedy),
+            Expr::Concat(es) => {
+                concat(es.into_iter().map(Expr::simplify).collect())
+            }
+            Expr::Alternate(es) => {
+                alternate(es.into_iter().map(Expr::simplify).collect())
+            }
+            Expr::Class(cls) => {
+                if !cls.is_case_insensitive() && cls.len() == 1
+                   && cls[0].start == cls[0].end {
+                    Expr::Literal { chars: vec![cls[0].start], casei: false }
+                } else {
+                    Expr::Class(cls)
+                }
+            }
+            e => e,
+        }
+    }
+}
+
+fn group(e: Expr) -> Expr {
+    Expr::Group { e: Box::new(e), i: None, name: None }
+}
+
+fn ungroup(e: Expr) -> Expr {
+    match e {
+        Expr::Group { e, i: None, name: None } => *e,
+        e => e,
+    }
+}
+
+fn repeat(e: Expr, r: Repeater, greedy: bool) -> Expr {
+    let e = ungroup(e);
+    let (min, max) = bounds(r);
+    if e == Expr::Empty || (max == Some(0) && !has_captures(&e)) {
+        return Expr::Empty
+    }
+    if (min, max) == (1, Some(1)) {
+        return e
+    }
+    let r = repeater(min, max);
+    if let Expr::Repeat { e: ref inner, r: inner_r, greedy: inner_greedy } = e {
+        // Nesting `?`, `*` and `+` gives `*`, unless both are the same.
+        // Lazy repeats aren't collapsed, since that changes which match is
+        // preferred (e.g., `(?:.+?)??` can match one character per
+        // iteration of an enclosing `*`, but `.*?` matches none).
+        let nests = |r| match r {
+            Repeater::Range { .. } => false,
+            _ => true,
+        };
+        if greedy && inner_greedy && is_one_char(inner)
+           && nests(r) && nests(inner_r) {
+            let r = if r == inner_r { r } else { Repeater::ZeroOrMore };
+            return repeat((**inner).clone(), r, greedy)
+        }
+    }
+    let e = if e.can_repeat() { e } else { group(e) };
+    Expr::Repeat { e: Box::new(e), r: r, greedy: greedy }
+}
+
+fn concat(es: Vec<Expr>) -> Expr {
+    let mut new_es: Vec<Expr> = Vec::with_capacity(es.len());
+    for e in es {
+        let es = match ungroup(e) {
+            Expr::Empty => vec![],
+            Expr::Concat(es) => es,
+            e @ Expr::Alternate(_) => vec![group(e)],
+            e => vec![e],
+        };
+        for e in es {
+            let e = match (new_es.pop(), e) {
+                (None, e) => e,
+                (Some(Expr::Literal { chars: mut chars1, casei: casei1 }),
+                 Expr::Literal { chars: chars2, casei: casei2 })
+                 if casei1 == casei2 => {
+                    chars1.extend(chars2);
+                    Expr::Literal { chars: chars1, casei: casei1 }
+                }
+                (Some(Expr::Repeat { e: e1, r: r1, greedy: g1 }),
+                 Expr::Repeat { e: e2, r: r2, greedy: g2 })
+                 if e1 == e2 && g1 == g2 && is_one_char(&e1) => {
+                    let (min1, max1) = bounds(r1);
+                    let (min2, max2) = bounds(r2);
+                    let max = match (max1, max2) {
+                        (Some(a), Some(b)) => a.checked_add(b),
+                        _ => None,
+                    };
+                    match min1.checked_add(min2) {
+                        Some(min) if max.is_some() || max1.is_none()
+                                     || max2.is_none() => {
+                            repeat(*e1, repeater(min, max), g1)
+                        }
+                        _ => {
+                            new_es.push(Expr::Repeat {
+                                e: e1, r: r1, greedy: g1,
+                            });
+                            Expr::Repeat { e: e2, r: r2, greedy: g2 }
+                        }
+                    }
+                }
+                (Some(e1), e2) => {
+                    new_es.push(e1);
+                    e2
+                }
+            };
+            new_es.push(e);
+        }
+    }
+    match new_es.len() {
+        0 => Expr::Empty,
+        1 => ungroup(new_es.pop().unwrap()),
+        _ => Expr::Concat(new_es),
+    }
+}
+
+fn alternate(es: Vec<Expr>) -> Expr {
+    let mut flat: Vec<Expr> = Vec::with_capacity(es.len());
+    let mut seen = HashSet::new();
+    for e in es {
+        let es = match ungroup(e) {
+            Expr::Alternate(es) => es,
+            e => vec![e],
+        };
+        for e in es {
+            // A later alternative identical to an earlier one can never be
+            // preferred over it.
+            if seen.insert(e.clone()) {
+                flat.push(e);
+            }
+        }
+    }
+    let mut es = factor_prefixes(flat);
+
+    // An empty alternative can't be written as a pattern, so it becomes an
+    // optional group of the alternatives following it.
+    if let Some(k) = es.iter().position(|e| *e == Expr::Empty) {
+        let after = es.split_off(k + 1);
+        es.pop();
+        if after.is_empty() {
+            return repeat(alternate(es), Repeater::ZeroOrOne, true)
+        }
+        es.push(repeat(alternate(after), Repeater::ZeroOrOne, false));
+    }
+    match es.len() {
+        1 => es.pop().unwrap(),
+        _ => Expr::Alternate(es),
+    }
+}
+
+/// Factors the longest common literal prefix out of each run of adjacent
+/// alternatives that start with the same character.
+fn factor_prefixes(es: Vec<Expr>) -> Vec<Expr> {
+    let mut factored = vec![];
+    let mut run: Vec<Expr> = vec![];
+    for e in es {
+        let same = match (run.last().and_then(prefix), prefix(&e)) {
+            (Some((p1, casei1)), Some((p2, casei2))) => {
+                p1[0] == p2[0] && casei1 == casei2
+            }
+            _ => false,
+        };
+        if !same {
+            factored.extend(factor_run(run));
+            run = vec![];
+        }
+        run.push(e);
+    }
+    factored.extend(factor_run(run));
+    factored
+}
+
+fn factor_run(mut run: Vec<Expr>) -> Vec<Expr> {
+    if run.len() < 2 {
+        return run
+    }
+    let (chars, casei) = {
+        let (first, casei) = prefix(&run[0]).unwrap();
+        let len = run[1..].iter().fold(first.len(), |len, e| {
+            let (p, _) = prefix(e).unwrap();
+            first.iter().zip(p).take(len).take_while(|&(a, b)| a == b).count()
+        });
+        (first[..len].to_vec(), casei)
+    };
+    let rests = run.drain(..).map(|e| strip_prefix(e, chars.len())).collect();
+    vec![concat(vec![
+        Expr::Literal { chars: chars, casei: casei },
+        alternate(rests),
+    ])]
+}
+
+/// Returns the literal an expression starts with, if any.
+fn prefix(e: &Expr) -> Option<(&[char], bool)> {
+    match *e {
+        Expr::Literal { ref chars, casei } => Some((chars, casei)),
+        Expr::Concat(ref es) => prefix(&es[0]),
+        _ => None,
+    }
+}
+
+/// Removes the first `n` characters of the literal an expression starts
+/// with.
+fn strip_prefix(e: Expr, n: usize) -> Expr {
+    match e {
+        Expr::Literal { chars, casei } => {
+            if chars.len() == n {
+                Expr::Empty
+            } else {
+                Expr::Literal { chars: chars[n..].to_vec(), casei: casei }
+            }
+        }
+        Expr::Concat(mut es) => {
+            let first = es.remove(0);
+            es.insert(0, strip_prefix(first, n));
+            concat(es)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Returns true if the expression always matches exactly one character.
+fn is_one_char(e: &Expr) -> bool {
+    match *e {
+        Expr::Literal { ref chars, .. } => chars.len() == 1,
+        Expr::AnyChar | Expr::AnyCharNoNL | Expr::Class(_) => true,
+        _ => false,
+    }
+}
+
+fn has_captures(e: &Expr) -> bool {
+    match *e {
+        Expr::Group { i: Some(_), .. } => true,
+        Expr::Group { ref e, .. } | Expr::Repeat { ref e, .. } => {
+            has_captures(e)
+        }
+        Expr::Concat(ref es) | Expr::Alternate(ref es) => {
+            es.iter().any(has_captures)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the lower and upper bounds of a repetition.
+fn bounds(r: Repeater) -> (u32, Option<u32>) {
+    match r {
+        Repeater::ZeroOrOne => (0, Some(1)),
+        Repeater::ZeroOrMore => (0, None),
+        Repeater::OneOrMore => (1, None),
+        Repeater::Range { min, max } => (min, max),
+    }
+}
+
+fn repeater(min: u32, max: Option<u32>) -> Repeater {
+    match (min, max) {
+        (0, Some(1)) => Repeater::ZeroOrOne,
+        (0, None) => Repeater::ZeroOrMore,
+        (1, None) => Repeater::OneOrMore,
+        (min, max) => Repeater::Range { min: min, max: max },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Expr;
+
+    fn s(re: &str) -> String {
+        Expr::parse(re).unwrap().simplify().to_string()
+    }
+
+    #[test]
+    fn flatten() {
+        assert_eq!(s("(?:(?:x))"), "x");
+        assert_eq!(s("a(?:b(?:c))d"), "abcd");
+        assert_eq!(s("a|(?:b|(?:c|d))"), "a|b|c|d");
+        assert_eq!(s("(?:a|b)c"), "(?:a|b)c");
+        assert_eq!(s("((?:ab))"), "(ab)");
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(s("[a][b]"), "ab");
+        assert_eq!(s("a[b]*"), "ab*");
+        assert_eq!(s("[ab]"), "[a-b]");
+        assert_eq!(s("a(?i)b"), "a(?i:b)");
+    }
+
+    #[test]
+    fn alternates() {
+        assert_eq!(s("a|a"), "a");
+        assert_eq!(s("a|b|a|c"), "a|b|c");
+        assert_eq!(s("abc|abd"), "ab(?:c|d)");
+        assert_eq!(s("abc|abd|x|abe"), "ab(?:c|d)|x|abe");
+        assert_eq!(s("ab|abc"), "abc??");
+        assert_eq!(s("abc|ab"), "abc?");
+        assert_eq!(s("ab|abc|abd"), "ab(?:c|d)??");
+        assert_eq!(s("ac|a(b)c"), "a(?:c|(b)c)");
+    }
+
+    #[test]
+    fn repeats() {
+        assert_eq!(s("x{1}"), "x");
+        assert_eq!(s("(?:ab){1,1}"), "ab");
+        assert_eq!(s("x{0}y"), "y");
+        assert_eq!(s("(x){0}y"), "(x){0}y");
+        assert_eq!(s("(x{0})y"), "((?s:.){0})y");
+        assert_eq!(s("a*a*"), "a*");
+        assert_eq!(s("a?a{2,3}"), "a{2, 4}");
+        assert_eq!(s("a*a*?"), "a*a*?");
+        assert_eq!(s("(?:ab)*(?:ab)*"), "(?:ab)*(?:ab)*");
+        assert_eq!(s("(?:a+)*"), "a*");
+        assert_eq!(s("(?:a+)+"), "a+");
+        assert_eq!(s("(?:a+?)??"), "(?:a+?)??");
+        assert_eq!(s("(?:a+)*?"), "(?:a+)*?");
+        assert_eq!(s("(?:a{2})*"), "(?:a{2})*");
+        assert_eq!(s("(?:ab|cd)*"), "(?:ab|cd)*");
+    }
+}
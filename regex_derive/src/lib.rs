@@ -0,0 +1,251 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This crate provides `#[derive(FromCaptures)]`, which implements
+//! `regex::FromCaptures` for a struct with named fields.
+//!
+//! Each field is filled from the capture group with the same name, whose
+//! text is parsed with `FromStr`. A field of type `Option<T>` is `None` when
+//! its group didn't participate in the match.
+//!
+//! If the struct has a `#[regex(r"...")]` attribute, then the pattern is
+//! parsed at compile time and every field must correspond to a named
+//! capture group in it. The pattern is returned by `FromCaptures::pattern`.
+//!
+//! ```rust
+//! #[macro_use]
+//! extern crate regex_derive;
+//! extern crate regex;
+//!
+//! use regex::{FromCaptures, Regex};
+//!
+//! #[derive(FromCaptures)]
+//! #[regex(r"(?P<host>[a-z.]+):(?P<port>\d+)(?:/(?P<path>\S+))?")]
+//! struct Url {
+//!     host: String,
+//!     port: u16,
+//!     path: Option<String>,
+//! }
+//!
+//! fn main() {
+//!     let re = Regex::new(Url::pattern().unwrap()).unwrap();
+//!     let url: Url = re.captures_into("example.com:80").unwrap().unwrap();
+//!     assert_eq!(url.port, 80);
+//!     assert_eq!(url.path, None);
+//! }
+//! ```
+//!
+//! A field without a capture group of the same name is a compile error:
+//!
+//! ```rust,compile_fail
+//! #[macro_use]
+//! extern crate regex_derive;
+//! extern crate regex;
+//!
+//! #[derive(FromCaptures)]
+//! #[regex(r"(?P<host>[a-z.]+):(?P<port>\d+)")]
+//! struct Url {
+//!     host: String,
+//!     port: u16,
+//!     path: Option<String>,
+//! }
+//!
+//! fn main() {}
+//! ```
+//!
+//! So is an invalid pattern:
+//!
+//! ```rust,compile_fail
+//! #[macro_use]
+//! extern crate regex_derive;
+//! extern crate regex;
+//!
+//! #[derive(FromCaptures)]
+//! #[regex(r"(?P<host>[a-z.]+")]
+//! struct Url {
+//!     host: String,
+//! }
+//!
+//! fn main() {}
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate regex_syntax;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use regex_syntax::Expr;
+use syn::{Data, DeriveInput, Fields, GenericArgument, LitStr,
+          PathArguments, Type};
+
+/// Implements `regex::FromCaptures` for a struct with named fields.
+#[proc_macro_derive(FromCaptures, attributes(regex))]
+pub fn derive_from_captures(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => compile_errors(err).into(),
+    }
+}
+
+/// Converts an error to `compile_error!` invocations.
+///
+/// `syn::Error::to_compile_error` refers to `::core`, which isn't in scope
+/// in crates using the 2015 edition.
+fn compile_errors(err: syn::Error) -> proc_macro2::TokenStream {
+    let errors = err.into_iter().map(|e| {
+        let msg = e.to_string();
+        quote_spanned!(e.span()=> compile_error!(#msg);)
+    });
+    quote!(#(#errors)*)
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(syn::Error::new(
+                Span::call_site(),
+                "FromCaptures can only be derived for structs with named \
+                 fields")),
+        },
+        _ => return Err(syn::Error::new(
+            Span::call_site(),
+            "FromCaptures can only be derived for structs")),
+    };
+
+    let lit = try!(pattern(input));
+    let names = match lit {
+        None => None,
+        Some(ref lit) => Some(try!(capture_names(lit))),
+    };
+    let pattern = match lit {
+        None => quote! {},
+        Some(ref lit) => quote! {
+            fn pattern() -> ::std::option::Option<&'static str> {
+                ::std::option::Option::Some(#lit)
+            }
+        },
+    };
+
+    let mut inits = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let group = ident.to_string();
+        let group = group.trim_start_matches("r#");
+        if let Some(ref names) = names {
+            if !names.iter().any(|n| n == group) {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!("no capture group named `{}` in the pattern",
+                            group)));
+            }
+        }
+        let init = match option_inner(&field.ty) {
+            Some(ty) => quote! {
+                #ident: caps.parse_name_opt::<#ty>(#group)?
+            },
+            None => {
+                let ty = &field.ty;
+                quote! { #ident: caps.parse_name::<#ty>(#group)? }
+            }
+        };
+        inits.push(init);
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::regex::FromCaptures for #name #ty_generics
+                #where_clause {
+            fn from_captures(caps: &::regex::Captures)
+                            -> ::std::result::Result<Self,
+                                                     ::regex::CaptureError> {
+                ::std::result::Result::Ok(#name { #(#inits),* })
+            }
+
+            #pattern
+        }
+    })
+}
+
+/// Returns the pattern given in a `#[regex(...)]` attribute, if any.
+fn pattern(input: &DeriveInput) -> syn::Result<Option<LitStr>> {
+    let mut found = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("regex") {
+            continue;
+        }
+        if found.is_some() {
+            return Err(syn::Error::new_spanned(
+                attr, "only one #[regex] attribute is allowed"));
+        }
+        found = Some(try!(attr.parse_args::<LitStr>()));
+    }
+    Ok(found)
+}
+
+/// Parses the pattern and returns the names of its capture groups.
+fn capture_names(lit: &LitStr) -> syn::Result<Vec<String>> {
+    match Expr::parse(&lit.value()) {
+        Ok(expr) => {
+            let mut names = vec![];
+            collect_names(&expr, &mut names);
+            Ok(names)
+        }
+        Err(err) => Err(syn::Error::new_spanned(
+            lit, format!("invalid regex: {}", err))),
+    }
+}
+
+fn collect_names(expr: &Expr, names: &mut Vec<String>) {
+    match *expr {
+        Expr::Group { ref e, ref name, .. } => {
+            if let Some(ref name) = *name {
+                names.push(name.clone());
+            }
+            collect_names(e, names);
+        }
+        Expr::Repeat { ref e, .. } => collect_names(e, names),
+        Expr::Concat(ref es) | Expr::Alternate(ref es) => {
+            for e in es {
+                collect_names(e, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns `T` if `ty` is written as `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let path = match *ty {
+        Type::Path(ref p) if p.qself.is_none() => &p.path,
+        _ => return None,
+    };
+    let last = match path.segments.last() {
+        Some(last) if last.ident == "Option" => last,
+        _ => return None,
+    };
+    let args = match last.arguments {
+        PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+            args
+        }
+        _ => return None,
+    };
+    match args.args[0] {
+        GenericArgument::Type(ref ty) => Some(ty),
+        _ => None,
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate regex;
+#[macro_use]
+extern crate regex_derive;
+
+use regex::{CaptureError, CaptureErrorKind, FromCaptures, Regex};
+
+#[derive(Debug, PartialEq, FromCaptures)]
+#[regex(r"(?P<host>[a-z.]+):(?P<port>\d+)(?:/(?P<path>\S+))?")]
+struct Addr {
+    host: String,
+    port: u16,
+    path: Option<String>,
+}
+
+#[derive(Debug, PartialEq, FromCaptures)]
+struct Pair {
+    key: String,
+    value: i32,
+}
+
+fn addr_re() -> Regex {
+    Regex::new(Addr::pattern().unwrap()).unwrap()
+}
+
+#[test]
+fn captures_into() {
+    let addr: Addr = addr_re().captures_into("at example.com:80/index")
+                              .unwrap().unwrap();
+    assert_eq!(addr, Addr {
+        host: "example.com".to_string(),
+        port: 80,
+        path: Some("index".to_string()),
+    });
+}
+
+#[test]
+fn pattern() {
+    assert_eq!(Addr::pattern(),
+               Some(r"(?P<host>[a-z.]+):(?P<port>\d+)(?:/(?P<path>\S+))?"));
+    assert_eq!(Pair::pattern(), None);
+}
+
+#[test]
+fn optional_group() {
+    let addr: Addr = addr_re().captures_into("a.b:1").unwrap().unwrap();
+    assert_eq!(addr.path, None);
+}
+
+#[test]
+fn no_match() {
+    assert!(addr_re().captures_into::<Addr>("nothing here").is_none());
+}
+
+#[test]
+fn parse_error() {
+    let err = addr_re().captures_into::<Addr>("a.b:99999").unwrap()
+                       .unwrap_err();
+    assert_eq!(err.field(), "port");
+    match *err.kind() {
+        CaptureErrorKind::Parse { ref text, .. } => assert_eq!(text, "99999"),
+        ref kind => panic!("unexpected error kind: {:?}", kind),
+    }
+}
+
+#[test]
+fn no_group() {
+    let re = Regex::new(r"(?P<key>\w+)=(?P<val>-?\d+)").unwrap();
+    let err = re.captures_into::<Pair>("a=1").unwrap().unwrap_err();
+    assert_eq!(err, CaptureError::new("value", CaptureErrorKind::NoGroup));
+}
+
+#[test]
+fn captures_iter_into() {
+    let re = Regex::new(r"(?P<key>\w+)=(?P<value>-?\d+)").unwrap();
+    let pairs: Result<Vec<Pair>, CaptureError> =
+        re.captures_iter_into("a=1 b=-2").collect();
+    assert_eq!(pairs.unwrap(), vec![
+        Pair { key: "a".to_string(), value: 1 },
+        Pair { key: "b".to_string(), value: -2 },
+    ]);
+}
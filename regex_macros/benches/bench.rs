@@ -10,9 +10,10 @@
 #![allow(non_snake_case)]
 
 use std::iter::repeat;
+use std::str;
 use test::Bencher;
 use rand::{Rng, thread_rng};
-use regex::{Regex, NoExpand};
+use regex::{Chunks, Regex, NoExpand};
 
 fn bench_assert_match(b: &mut Bencher, re: Regex, text: &str) {
     b.iter(|| if !re.is_match(text) { panic!("no match") });
@@ -182,3 +183,38 @@ throughput!(medium_32K,medium(), 32<<10);
 throughput!(hard_32, hard(), 32);
 throughput!(hard_1K, hard(), 1<<10);
 throughput!(hard_32K,hard(), 32<<10);
+
+// The same searches through a `Haystack` made of 1K chunks, which reads the
+// text with the generic `CharReader` instead of a single string slice.
+macro_rules! throughput_chunks(
+    ($name:ident, $regex:expr, $size:expr) => (
+        #[bench]
+        fn $name(b: &mut Bencher) {
+            let text = gen_text($size);
+            let hay = Chunks::new(text.as_bytes().chunks(1 << 10).map(|c| {
+                str::from_utf8(c).unwrap()
+            }));
+            b.bytes = $size;
+            b.iter(|| if $regex.is_match_haystack(&hay) { panic!("match") });
+        }
+    );
+);
+
+throughput_chunks!(easy1_32K_chunks, easy1(), 32<<10);
+throughput_chunks!(hard_32K_chunks, hard(), 32<<10);
+
+// Text where most characters take several bytes, to measure UTF-8 decoding.
+macro_rules! throughput_unicode(
+    ($name:ident, $regex:expr, $size:expr) => (
+        #[bench]
+        fn $name(b: &mut Bencher) {
+            let text: String = "αβγ ж 中文 \u{1D11E}\n".chars().cycle()
+                                                    .take($size).collect();
+            b.bytes = text.len() as u64;
+            b.iter(|| if $regex.is_match(&text) { panic!("match") });
+        }
+    );
+);
+
+throughput_unicode!(easy1_unicode_32K, easy1(), 32<<10);
+throughput_unicode!(hard_unicode_32K, hard(), 32<<10);
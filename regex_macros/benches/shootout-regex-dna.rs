@@ -79,7 +79,8 @@ fn main() {
 fn run(mut seq: String) -> Vec<String> {
     let ilen = seq.len();
 
-    seq = regex!(">[^\n]*\n|\n").replace_all(&seq, NoExpand(""));
+    seq = regex!(">[^\n]*\n|\n").replace_all(&seq, NoExpand(""))
+                                .into_owned();
     let seq_arc = Arc::new(seq.clone()); // copy before it moves
     let clen = seq.len();
 
@@ -99,7 +100,7 @@ fn run(mut seq: String) -> Vec<String> {
         ];
         let mut seq = seq;
         for (re, replacement) in substs.into_iter() {
-            seq = re.replace_all(&seq, NoExpand(replacement));
+            seq = re.replace_all(&seq, NoExpand(replacement)).into_owned();
         }
         seq.len()
     });
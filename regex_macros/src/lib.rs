@@ -86,7 +86,7 @@ fn native(cx: &mut ExtCtxt, sp: codemap::Span, tts: &[ast::TokenTree])
         }
     };
     let prog = match re {
-        Dynamic(ExDynamic { ref prog, .. }) => prog.clone(),
+        Dynamic(ExDynamic { ref prog, .. }) => (**prog).clone(),
         Native(_) => unreachable!(),
     };
 
@@ -120,6 +120,12 @@ impl<'a> NfaGen<'a> {
                 None => cx.expr_none(self.sp),
             }
         );
+        let named_groups = self.names.iter().enumerate().filter_map(|(i, n)| {
+            n.as_ref().map(|name| (i, &**name))
+        });
+        let cap_groups = self.vec_expr(named_groups,
+            &mut |cx, (i, name)| quote_expr!(cx, ($name, $i))
+        );
         let prefix_anchor = match self.prog.insts[1] {
             Inst::StartText => true,
             _ => false,
@@ -145,20 +151,27 @@ impl<'a> NfaGen<'a> {
 static CAP_NAMES: &'static [Option<&'static str>] = &$cap_names;
 
 #[allow(dead_code)]
-fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
+static CAP_GROUPS: &'static [(&'static str, usize)] = &$cap_groups;
+
+#[allow(dead_code)]
+fn exec<'t>(which: ::regex::native::MatchKind,
+            anchor: ::regex::native::Anchor, input: &'t str,
             start: usize, end: usize) -> Vec<Option<usize>> {
     #![allow(unused_imports)]
     #![allow(unused_mut)]
 
     use regex::native::{
         MatchKind, Exists, Location, Submatches,
+        Anchor, Unanchored, AnchoredStart, AnchoredBoth,
         StepState, StepMatchEarlyReturn, StepMatch, StepContinue,
         CharReader, find_prefix, simple_case_fold,
     };
 
     return Nfa {
         which: which,
+        anchor: anchor,
         input: input,
+        end: end,
         ic: 0,
         chars: CharReader::new(input),
     }.run(start, end);
@@ -167,7 +180,9 @@ fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
 
     struct Nfa<'t> {
         which: MatchKind,
+        anchor: Anchor,
         input: &'t str,
+        end: usize,
         ic: usize,
         chars: CharReader<'t>,
     }
@@ -182,6 +197,10 @@ fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
             let (mut clist, mut nlist) = (&mut clist, &mut nlist);
 
             let mut groups = $init_groups;
+            let anchored = match self.anchor {
+                Unanchored => false,
+                AnchoredStart | AnchoredBoth => true,
+            };
 
             self.ic = start;
             let mut next_ic = self.chars.set(start);
@@ -195,9 +214,16 @@ fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
                         break
                     }
 
-                    $check_prefix
+                    if anchored && self.ic != start {
+                        break
+                    }
+
+                    if !anchored {
+                        $check_prefix
+                    }
                 }
-                if clist.size == 0 || (!$prefix_anchor && !matched) {
+                if clist.size == 0
+                    || (!$prefix_anchor && !anchored && !matched) {
                     self.add(&mut clist, 0, &mut groups)
                 }
 
@@ -325,6 +351,7 @@ fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
 ::regex::native::Native(::regex::native::ExNative {
     original: $regex,
     names: &CAP_NAMES,
+    groups: &CAP_GROUPS,
     prog: exec,
 })
         })
@@ -428,7 +455,15 @@ fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
                         self.add(nlist, $y, &mut *groups);
                     })
                 }
-                // For Match, OneChar, CharClass, Any, AnyNoNL
+                Inst::Match => {
+                    quote_expr!(self.cx, {
+                        match self.anchor {
+                            AnchoredBoth if self.ic != self.end => {}
+                            _ => nlist.add($pc, &*groups),
+                        }
+                    })
+                }
+                // For OneChar, CharClass, Any, AnyNoNL
                 _ => quote_expr!(self.cx, nlist.add($pc, &*groups)),
             };
             self.arm_inst(pc, body)
@@ -0,0 +1,130 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use regex::{Error, Regex, RegexOptions};
+use serde_json;
+
+#[test]
+fn regex_roundtrip() {
+    let re = regex!(r"(?P<y>\d{4})-\d{2}");
+    let json = serde_json::to_string(&re).unwrap();
+    assert_eq!(json, r#""(?P<y>\\d{4})-\\d{2}""#);
+    let back: Regex = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, re);
+}
+
+#[test]
+fn regex_modes_roundtrip() {
+    let re = Regex::with_full_case_folding(r"(?i)ß").unwrap();
+    let json = serde_json::to_string(&re).unwrap();
+    assert_eq!(json, concat!(r#"{"pattern":"(?i)ß","#,
+                             r#""case_insensitive":false,"#,
+                             r#""full_case_folding":true,"#,
+                             r#""canonical_equivalence":false}"#));
+    let back: Regex = serde_json::from_str(&json).unwrap();
+    assert!(back.full_case_folding() && back.is_match("SS"));
+    assert_eq!(back, re);
+    assert!(back != Regex::new(r"(?i)ß").unwrap());
+
+    let json = r#"{"pattern": "é", "canonical_equivalence": true}"#;
+    let re: Regex = serde_json::from_str(json).unwrap();
+    assert!(re.canonical_equivalence() && re.is_match("e\u{301}"));
+    assert_eq!(re, Regex::with_canonical_equivalence("é").unwrap());
+}
+
+#[test]
+fn regex_case_insensitive_roundtrip() {
+    let re = RegexOptions::new("abc", true, false, false, 1 << 16)
+                 .unwrap().into_regex();
+    assert!(re != Regex::new("abc").unwrap());
+    let json = serde_json::to_string(&re).unwrap();
+    assert_eq!(json, concat!(r#"{"pattern":"abc","case_insensitive":true,"#,
+                             r#""full_case_folding":false,"#,
+                             r#""canonical_equivalence":false}"#));
+    let back: Regex = serde_json::from_str(&json).unwrap();
+    assert!(back.is_match("ABC"));
+    assert_eq!(back, re);
+}
+
+#[test]
+fn regex_invalid() {
+    let err = serde_json::from_str::<Regex>(r#""a(b""#).unwrap_err();
+    let msg = err.to_string();
+    assert!(msg.contains("invalid regex 'a(b'"), "{}", msg);
+    assert!(msg.contains("character offset"), "{}", msg);
+}
+
+#[test]
+fn regex_not_a_string() {
+    assert!(serde_json::from_str::<Regex>("42").is_err());
+}
+
+#[test]
+fn options_defaults() {
+    let re: RegexOptions =
+        serde_json::from_str(r#"{"pattern": "^abc$"}"#).unwrap();
+    assert_eq!(re.pattern(), "^abc$");
+    assert!(!re.case_insensitive());
+    assert_eq!(re.size_limit(), 10 * (1 << 20));
+    assert!(re.is_match("abc"));
+    assert!(!re.is_match("ABC"));
+}
+
+#[test]
+fn options_case_insensitive() {
+    let json = r#"{"pattern": "^abc$", "case_insensitive": true}"#;
+    let re: RegexOptions = serde_json::from_str(json).unwrap();
+    assert!(re.is_match("ABC"));
+    assert_eq!(re.pattern(), "^abc$");
+}
+
+#[test]
+fn options_case_insensitive_error() {
+    // The flag isn't written into the pattern, so it doesn't shift the
+    // position of a syntax error or change `as_str`.
+    match RegexOptions::new("ab)", true, false, false, 1 << 16) {
+        Err(Error::Syntax(err)) => assert_eq!(err.position(), 2),
+        r => panic!("unexpected result: {:?}", r),
+    }
+    let re = RegexOptions::new("a(b)", true, false, false, 1 << 16).unwrap();
+    assert_eq!(re.as_str(), "a(b)");
+    assert_eq!(re.capture_spans(), vec![0..4, 1..4]);
+}
+
+#[test]
+fn options_roundtrip() {
+    let re = RegexOptions::new("a+", true, false, false, 1 << 16).unwrap();
+    let json = serde_json::to_string(&re).unwrap();
+    assert_eq!(json, concat!(r#"{"pattern":"a+","case_insensitive":true,"#,
+                             r#""full_case_folding":false,"#,
+                             r#""canonical_equivalence":false,"#,
+                             r#""size_limit":65536}"#));
+    let back: RegexOptions = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.pattern(), "a+");
+    assert!(back.case_insensitive());
+    assert_eq!(back.size_limit(), 1 << 16);
+}
+
+#[test]
+fn options_size_limit() {
+    let json = r#"{"pattern": "(?:a{100}){100}", "size_limit": 100}"#;
+    let err = serde_json::from_str::<RegexOptions>(json).unwrap_err();
+    assert!(err.to_string().contains("size limit"), "{}", err);
+}
+
+#[test]
+fn options_bad_fields() {
+    let missing = serde_json::from_str::<RegexOptions>("{}");
+    assert!(missing.is_err());
+    let unknown = r#"{"pattern": "a", "multi_line": true}"#;
+    assert!(serde_json::from_str::<RegexOptions>(unknown).is_err());
+    let dup = r#"{"pattern": "a", "pattern": "b"}"#;
+    assert!(serde_json::from_str::<RegexOptions>(dup).is_err());
+}
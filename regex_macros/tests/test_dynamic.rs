@@ -11,6 +11,10 @@
 #![cfg_attr(feature = "pattern", feature(core))]
 
 extern crate regex;
+extern crate regex_syntax;
+extern crate rand;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 // Due to macro scoping rules, this definition only applies for the modules
 // defined below. Effectively, it allows us to use the same tests for both
@@ -30,3 +34,6 @@ macro_rules! searcher_expr { ($e:expr) => ($e) }
 macro_rules! searcher_expr { ($e:expr) => ({}) }
 
 mod tests;
+
+#[cfg(feature = "serde")]
+mod serde_regex;
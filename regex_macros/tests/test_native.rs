@@ -12,6 +12,8 @@
 #![plugin(regex_macros)]
 
 extern crate regex;
+extern crate regex_syntax;
+extern crate rand;
 extern crate test;
 
 macro_rules! searcher_expr { ($e:expr) => ($e) }
@@ -8,7 +8,22 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use regex::{Regex, NoExpand};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::thread;
+
+use regex::{Regex, Captures, NoExpand, Replacer, ReplacementTemplate};
+use regex::{RegexCache, CacheStats};
+use regex::LexerBuilder;
+use regex::{LineSearcher, LineKind};
+use regex::{Position, PositionTracker};
+use regex::{Chunks, Haystack};
+use regex::{CancelToken, Cancelled};
+use regex::native::{Dynamic, Program};
+use regex::Error;
+use regex::LanguageChecker;
+use regex_syntax::{Expr, Generator};
+use rand::{Rng, SeedableRng, XorShiftRng};
 
 #[test]
 fn eq() {
@@ -31,48 +46,102 @@ fn split() {
     assert_eq!(subs, vec!("cauchy", "plato", "tyler", "binx"));
 }
 
+#[test]
+fn split_captures() {
+    let re = regex!(r"(-)|(\+)");
+    let got: Vec<_> = re.split_captures("1-2+3").collect();
+    assert_eq!(got, vec![Some("1"), Some("-"), None,
+                         Some("2"), None, Some("+"),
+                         Some("3")]);
+}
+
+#[test]
+fn split_captures_no_groups() {
+    let re = regex!(r"\d+");
+    let text = "cauchy123plato456tyler789binx";
+    let got: Vec<_> = re.split_captures(text).map(|s| s.unwrap()).collect();
+    assert_eq!(got, re.split(text).collect::<Vec<_>>());
+}
+
+#[test]
+fn split_inclusive() {
+    let re = regex!(r"\d+");
+    let text = "cauchy123plato456tyler789";
+    let subs: Vec<&str> = re.split_inclusive(text).collect();
+    assert_eq!(subs, vec!("cauchy123", "plato456", "tyler789"));
+    let subs: Vec<&str> = re.split_inclusive("a1b").collect();
+    assert_eq!(subs, vec!("a1", "b"));
+}
+
+#[test]
+fn split_delims() {
+    use regex::SplitItem::{Text, Delim};
+    let re = regex!(r"<(\w+)>");
+    let items: Vec<String> = re.split_delims("<a><b>x<c>yz")
+        .map(|item| match item {
+            Text(s) => format!("T:{}", s),
+            Delim(caps) => format!("D:{}", caps.at(1).unwrap()),
+        })
+        .collect();
+    assert_eq!(items, vec!["D:a", "D:b", "T:x", "D:c", "T:yz"]);
+}
+
 #[test]
 fn empty_regex_empty_match() {
     let re = regex!("");
-    let ms = re.find_iter("").collect::<Vec<_>>();
+    let ms: Vec<_> = re.find_iter("")
+                       .map(|m| (m.start(), m.end()))
+                       .collect();
     assert_eq!(ms, vec![(0, 0)]);
 }
 
 #[test]
 fn empty_regex_nonempty_match() {
     let re = regex!("");
-    let ms = re.find_iter("abc").collect::<Vec<_>>();
+    let ms: Vec<_> = re.find_iter("abc")
+                       .map(|m| (m.start(), m.end()))
+                       .collect();
     assert_eq!(ms, vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
 }
 
 #[test]
 fn quoted_bracket_set() {
     let re = regex!(r"([\x{5b}\x{5d}])");
-    let ms = re.find_iter("[]").collect::<Vec<_>>();
+    let ms: Vec<_> = re.find_iter("[]")
+                       .map(|m| (m.start(), m.end()))
+                       .collect();
     assert_eq!(ms, vec![(0, 1), (1, 2)]);
     let re = regex!(r"([\[\]])");
-    let ms = re.find_iter("[]").collect::<Vec<_>>();
+    let ms: Vec<_> = re.find_iter("[]")
+                       .map(|m| (m.start(), m.end()))
+                       .collect();
     assert_eq!(ms, vec![(0, 1), (1, 2)]);
 }
 
 #[test]
 fn first_range_starts_with_left_bracket() {
     let re = regex!(r"([[-z])");
-    let ms = re.find_iter("[]").collect::<Vec<_>>();
+    let ms: Vec<_> = re.find_iter("[]")
+                       .map(|m| (m.start(), m.end()))
+                       .collect();
     assert_eq!(ms, vec![(0, 1), (1, 2)]);
 }
 
 #[test]
 fn range_ends_with_escape() {
     let re = regex!(r"([\[-\x{5d}])");
-    let ms = re.find_iter("[]").collect::<Vec<_>>();
+    let ms: Vec<_> = re.find_iter("[]")
+                       .map(|m| (m.start(), m.end()))
+                       .collect();
     assert_eq!(ms, vec![(0, 1), (1, 2)]);
 }
 
 #[test]
 fn empty_match_find_iter() {
     let re = regex!(r".*?");
-    let ms: Vec<_> = re.find_iter("abc").collect();
+    let ms: Vec<_> = re.find_iter("abc")
+                       .map(|m| (m.start(), m.end()))
+                       .collect();
     assert_eq!(ms, vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
 }
 
@@ -88,7 +157,9 @@ fn empty_match_captures_iter() {
 #[test]
 fn empty_match_unicode_find_iter() {
     let re = regex!(r".*?");
-    let ms: Vec<_> = re.find_iter("Ⅰ1Ⅱ2").collect();
+    let ms: Vec<_> = re.find_iter("Ⅰ1Ⅱ2")
+                       .map(|m| (m.start(), m.end()))
+                       .collect();
     assert_eq!(ms, vec![(0, 0), (3, 3), (4, 4), (7, 7), (8, 8)]);
 }
 
@@ -101,6 +172,91 @@ fn empty_match_unicode_captures_iter() {
     assert_eq!(ms, vec![(0, 0), (3, 3), (4, 4), (7, 7), (8, 8)]);
 }
 
+#[test]
+fn find_match() {
+    let re = regex!(r"\d+");
+    let m = re.find("abc 123 xyz").unwrap();
+    assert_eq!((m.start(), m.end()), (4, 7));
+    assert_eq!(m.range(), 4..7);
+    assert_eq!(m.as_str(), "123");
+    assert_eq!(m.len(), 3);
+    assert!(re.find("abc").is_none());
+}
+
+#[test]
+fn find_iter_matches() {
+    let re = regex!(r"\d+");
+    let ms: Vec<&str> = re.find_iter("a1b22c333")
+                          .map(|m| m.as_str())
+                          .collect();
+    assert_eq!(ms, vec!["1", "22", "333"]);
+}
+
+#[test]
+fn captures_get() {
+    let re = regex!(r"(?P<word>[a-z]+)(\d)?");
+    let caps = re.captures("12 abc!").unwrap();
+    let word = caps.get(1).unwrap();
+    assert_eq!((word.start(), word.end(), word.as_str()), (3, 6, "abc"));
+    assert_eq!(caps.get_name("word"), Some(word));
+    assert_eq!(caps.get(2), None);
+    assert_eq!(caps.get(3), None);
+    assert_eq!(caps.get_name("nope"), None);
+}
+
+#[test]
+fn captures_index() {
+    let re = regex!(r"(?P<year>\d{4})-(?P<month>\d{2})(x)?");
+    let caps = re.captures("on 2015-06").unwrap();
+    assert_eq!(&caps[0], "2015-06");
+    assert_eq!(&caps[1], "2015");
+    assert_eq!(&caps["month"], "06");
+}
+
+#[test]
+#[should_panic(expected = "no group at index '4'")]
+fn captures_index_no_group() {
+    let re = regex!(r"(\d{4})-(\d{2})(x)?");
+    let caps = re.captures("2015-06").unwrap();
+    let _ = &caps[4];
+}
+
+#[test]
+#[should_panic(expected = "group 3 did not participate in the match")]
+fn captures_index_no_participation() {
+    let re = regex!(r"(\d{4})-(\d{2})(x)?");
+    let caps = re.captures("2015-06").unwrap();
+    let _ = &caps[3];
+}
+
+#[test]
+#[should_panic(expected = "no group named 'day'")]
+fn captures_index_no_name() {
+    let re = regex!(r"(?P<year>\d{4})");
+    let caps = re.captures("2015").unwrap();
+    let _ = &caps["day"];
+}
+
+#[test]
+fn captures_has_group() {
+    let re = regex!(r"(?P<a>a)(?P<b>b)?");
+    let caps = re.captures("a").unwrap();
+    assert!(caps.has_group(2) && caps.get(2).is_none());
+    assert!(!caps.has_group(3));
+    assert!(caps.has_name("b") && caps.name("b").is_none());
+    assert!(!caps.has_name("c"));
+}
+
+#[test]
+fn captures_iter_named_ordered() {
+    let re = regex!(r"(?P<z>z)(?P<y>y)?(?P<x>x)(?P<w>w)");
+    let caps = re.captures("zxw").unwrap();
+    let named: Vec<_> = caps.iter_named().collect();
+    assert_eq!(named, vec![
+        ("z", Some("z")), ("y", None), ("x", Some("x")), ("w", Some("w")),
+    ]);
+}
+
 #[test]
 fn invalid_regexes_no_crash() {
     // See: https://github.com/rust-lang/regex/issues/48
@@ -206,6 +362,1260 @@ replace!(rep_trim, replace_all, "^[ \t]+|[ \t]+$", " \t  trim me\t   \t",
 replace!(rep_number_hypen, replace, r"(.)(.)", "ab", "$1-$2", "a-b");
 replace!(rep_number_underscore, replace, r"(.)(.)", "ab", "$1_$2", "a_b");
 
+replace!(rep_braces_index, replace, r"(\d)", "a5", "${1}0", "a50");
+replace!(rep_braces_named, replace_all, r"(?P<w>\w+)", "a b", "${w}_x",
+         "a_x b_x");
+replace!(rep_braces_unclosed, replace, r"(\w)", "a", "${1", "${1");
+replace!(rep_dollar_at_end, replace, r"(\w)", "a", "$1$", "a$");
+replace!(rep_unknown_group, replace, r"(\w)", "a", "[$2$nope]", "[]");
+replace!(rep_template, replace_all, r"(?P<first>\S+)\s+(?P<last>\S+)",
+         "w1 w2", &ReplacementTemplate::new("${last}$$$first"), "w2$w1");
+
+#[test]
+fn rep_no_match_borrows() {
+    use std::borrow::Cow;
+    let re = regex!(r"\d");
+    match re.replace_all("no digits", "Z") {
+        Cow::Borrowed(s) => assert_eq!(s, "no digits"),
+        Cow::Owned(_) => panic!("expected a borrowed result"),
+    }
+    match re.replace_all("1 digit", "Z") {
+        Cow::Owned(s) => assert_eq!(s, "Z digit"),
+        Cow::Borrowed(_) => panic!("expected an owned result"),
+    }
+}
+
+#[test]
+fn rep_all_into() {
+    let re = regex!(r"\d");
+    let mut dst = String::from(">");
+    re.replace_all_into("a1b2", "Z", &mut dst);
+    re.replace_all_into("cd", "Z", &mut dst);
+    assert_eq!(dst, ">aZbZcd");
+}
+
+#[test]
+fn rep_all_fmt() {
+    let re = regex!(r"(\w+)=(\w+)");
+    let mut dst = String::new();
+    re.replace_all_fmt("a=1, b=2!", "$2=$1", &mut dst).unwrap();
+    assert_eq!(dst, "1=a, 2=b!");
+}
+
+#[test]
+fn rep_all_io() {
+    let re = regex!(r"(\w+)=(\w+)");
+    let mut dst: Vec<u8> = vec![];
+    re.replace_all_io("a=1, b=2!", "$2=$1", &mut dst).unwrap();
+    assert_eq!(dst, b"1=a, 2=b!");
+    dst.clear();
+    re.replace_all_io("nothing", "$2=$1", &mut dst).unwrap();
+    assert_eq!(dst, b"nothing");
+}
+
+#[test]
+fn rep_all_io_error() {
+    use std::io;
+    struct Fail;
+    impl io::Write for Fail {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Other, "nope"))
+        }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+    let re = regex!(r"\d");
+    assert!(re.replace_all_io("a1", "Z", &mut Fail).is_err());
+}
+
+#[test]
+fn rep_closure_str() {
+    let re = regex!(r"\d");
+    let digits = ["zero", "one", "two"];
+    let got = re.replace_all("1+2", |caps: &Captures| {
+        digits[caps.at(0).unwrap().parse::<usize>().unwrap()]
+    });
+    assert_eq!(got, "one+two");
+}
+
+#[test]
+fn rep_closure_cow() {
+    use std::borrow::Cow;
+    let re = regex!(r"\w+");
+    let got = re.replace_all("a bb", |caps: &Captures| -> Cow<'static, str> {
+        if caps.at(0).unwrap().len() > 1 {
+            Cow::Borrowed("long")
+        } else {
+            Cow::Owned(caps.at(0).unwrap().to_uppercase())
+        }
+    });
+    assert_eq!(got, "A long");
+}
+
+#[test]
+fn rep_by_ref() {
+    let re = regex!(r"x");
+    let mut n = 0;
+    let mut rep = |_: &Captures| { n += 1; n.to_string() };
+    assert_eq!(re.replace_all("xx", rep.by_ref()), "12");
+    assert_eq!(re.replace("axb", rep.by_ref()), "a3b");
+}
+
+#[test]
+fn rep_no_expansion() {
+    let mut lit = "literal";
+    assert_eq!(lit.no_expansion().unwrap(), "literal");
+    let mut expand = "$1";
+    assert!(expand.no_expansion().is_none());
+    assert_eq!(NoExpand("$1").no_expansion().unwrap(), "$1");
+    let tpl = ReplacementTemplate::new("a$$b");
+    assert_eq!((&tpl).no_expansion().unwrap(), "a$b");
+    assert!((&ReplacementTemplate::new("$0")).no_expansion().is_none());
+}
+
+#[test]
+fn try_rep_all() {
+    let re = regex!(r"\w+");
+    let mut seen = vec![];
+    let got = re.try_replace_all("a b c", |caps: &Captures| {
+        let word = caps.at(0).unwrap();
+        seen.push(word.to_string());
+        if word == "b" {
+            Err(word.to_string())
+        } else {
+            Ok(word.to_uppercase())
+        }
+    });
+    assert_eq!(got, Err("b".to_string()));
+    assert_eq!(seen, vec!["a", "b"]);
+
+    let got: Result<_, ()> =
+        re.try_replace_all("a b", |caps: &Captures| Ok(caps.expand("<$0>")));
+    assert_eq!(got.unwrap(), "<a> <b>");
+}
+
+#[test]
+fn template_validate() {
+    let re = regex!(r"(?P<first>\S+)\s+(\S+)");
+    assert!(ReplacementTemplate::new("$first $2 $0 $$3").validate(&re).is_ok());
+    match ReplacementTemplate::new("$1 $3").validate(&re) {
+        Err(::regex::Error::UnknownGroup(ref g)) if g == "3" => {}
+        r => panic!("unexpected result: {:?}", r),
+    }
+    match ReplacementTemplate::new("${last}").validate(&re) {
+        Err(::regex::Error::UnknownGroup(ref g)) if g == "last" => {}
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn template_expand_into() {
+    let re = regex!(r"(?P<k>\w+)=(?P<v>\w+)");
+    let tpl = ReplacementTemplate::new("$v:$k;");
+    let mut dst = String::from("> ");
+    for caps in re.captures_iter("a=1 b=2") {
+        tpl.expand(&caps, &mut dst);
+    }
+    assert_eq!(dst, "> 1:a;2:b;");
+}
+
+#[test]
+fn full_match() {
+    let re = regex!(r"a|ab");
+    assert!(re.is_full_match("a"));
+    assert!(re.is_full_match("ab"));
+    assert!(!re.is_full_match("abc"));
+    assert!(!re.is_full_match("ca"));
+    assert!(!re.is_full_match(""));
+    assert!(regex!(r"a*").is_full_match(""));
+    assert!(regex!(r"\d+$").is_full_match("123"));
+}
+
+#[test]
+fn full_match_literal_prefix() {
+    // An anchored search must not skip ahead to a later literal prefix.
+    let re = regex!(r"abc");
+    assert!(!re.is_full_match("xabc"));
+    assert!(re.match_prefix("xabc").is_none());
+}
+
+#[test]
+fn match_prefix() {
+    let re = regex!(r"\d+");
+    let m = re.match_prefix("123abc456").unwrap();
+    assert_eq!((m.start(), m.end()), (0, 3));
+    assert!(re.match_prefix("abc456").is_none());
+    assert_eq!(regex!(r"a*").match_prefix("bbb").map(|m| m.range()),
+               Some(0..0));
+}
+
+#[test]
+fn captures_full() {
+    let re = regex!(r"(a)|(ab)");
+    let caps = re.captures_full("ab").unwrap();
+    assert_eq!(caps.at(1), None);
+    assert_eq!(caps.at(2), Some("ab"));
+    assert!(re.captures_full("abab").is_none());
+}
+
+#[test]
+fn captures_prefix() {
+    let re = regex!(r"(?P<key>\w+)=");
+    let caps = re.captures_prefix("a=1 b=2").unwrap();
+    assert_eq!(caps.name("key"), Some("a"));
+    assert!(re.captures_prefix(" a=1").is_none());
+}
+
+#[test]
+fn find_overlapping() {
+    let re = regex!(r"aa");
+    let ms: Vec<(usize, usize)> = re.find_overlapping_iter("aaa")
+                                    .map(|m| (m.start(), m.end()))
+                                    .collect();
+    assert_eq!(ms, vec![(0, 2), (1, 3)]);
+
+    let re = regex!(r"a+|b");
+    let ms: Vec<&str> = re.find_overlapping_iter("xaab")
+                          .map(|m| m.as_str())
+                          .collect();
+    assert_eq!(ms, vec!["aa", "a", "b"]);
+}
+
+#[test]
+fn find_overlapping_empty() {
+    let re = regex!(r"a*");
+    let ms: Vec<(usize, usize)> = re.find_overlapping_iter("bδa")
+                                    .map(|m| (m.start(), m.end()))
+                                    .collect();
+    assert_eq!(ms, vec![(0, 0), (1, 1), (3, 4), (4, 4)]);
+}
+
+#[test]
+fn find_overlapping_all_ends() {
+    let re = regex!(r"a|ab|abc");
+    let ms: Vec<(usize, usize)> = re.find_overlapping_iter("abcab")
+                                    .map(|m| (m.start(), m.end()))
+                                    .collect();
+    assert_eq!(ms, vec![(0, 1), (3, 4)]);
+    let ms: Vec<(usize, usize)> = re.find_overlapping_iter("abcab")
+                                    .all_ends()
+                                    .map(|m| (m.start(), m.end()))
+                                    .collect();
+    assert_eq!(ms, vec![(0, 1), (0, 2), (0, 3), (3, 4), (3, 5)]);
+
+    let re = regex!(r"x*");
+    let ms: Vec<(usize, usize)> = re.find_overlapping_iter("xx")
+                                    .all_ends()
+                                    .map(|m| (m.start(), m.end()))
+                                    .collect();
+    assert_eq!(ms, vec![(0, 0), (0, 1), (0, 2), (1, 1), (1, 2), (2, 2)]);
+}
+
+#[test]
+fn rfind() {
+    let re = regex!(r"\d+");
+    assert_eq!(re.rfind("a1b22c333d").map(|m| m.range()), Some(6..9));
+    assert!(re.rfind("abc").is_none());
+
+    // Reverse matches end as far right as possible.
+    let re = regex!(r"b|ab");
+    assert_eq!(re.rfind("ab").map(|m| m.range()), Some(1..2));
+    let re = regex!(r"a+b");
+    assert_eq!(re.rfind("xaab").map(|m| m.range()), Some(1..4));
+}
+
+#[test]
+fn rfind_anchors() {
+    assert_eq!(regex!(r"^a").rfind("aa").map(|m| m.range()), Some(0..1));
+    assert_eq!(regex!(r"a$").rfind("aab").map(|m| m.range()), None);
+    assert_eq!(regex!(r"(?m)^\w+").rfind("ab\ncd").map(|m| m.range()),
+               Some(3..5));
+    assert_eq!(regex!(r"\bcat").rfind("cat bobcat").map(|m| m.range()),
+               Some(0..3));
+}
+
+#[test]
+fn rfind_iter() {
+    let re = regex!(r"δ\w");
+    let ms: Vec<(usize, usize)> = re.rfind_iter("δaxδbδc")
+                                    .map(|m| (m.start(), m.end()))
+                                    .collect();
+    assert_eq!(ms, vec![(7, 10), (4, 7), (0, 3)]);
+
+    let re = regex!(r"a*");
+    let ms: Vec<(usize, usize)> = re.rfind_iter("baa")
+                                    .map(|m| (m.start(), m.end()))
+                                    .collect();
+    assert_eq!(ms, vec![(1, 3), (0, 0)]);
+}
+
+#[test]
+fn rsplitn() {
+    let re = regex!(r"\s*,\s*");
+    let text = "a , b,c ,d";
+    assert_eq!(re.rsplitn(text, 0).collect::<Vec<_>>(), Vec::<&str>::new());
+    assert_eq!(re.rsplitn(text, 1).collect::<Vec<_>>(), vec![text]);
+    assert_eq!(re.rsplitn(text, 2).collect::<Vec<_>>(), vec!["d", "a , b,c"]);
+    assert_eq!(re.rsplitn(text, 10).collect::<Vec<_>>(),
+               vec!["d", "c", "b", "a"]);
+}
+
+#[test]
+fn rfind_literal_suffix() {
+    // The reversed program starts with a literal, which is searched for
+    // backwards.
+    let re = regex!(r"\w+foo");
+    let ms: Vec<&str> = re.rfind_iter("afoo bfoo c").map(|m| m.as_str())
+                          .collect();
+    assert_eq!(ms, vec!["bfoo", "afoo"]);
+    let re = regex!(r"aδ");
+    assert_eq!(re.rfind("aδ aδδ").map(|m| m.range()), Some(4..7));
+}
+
+#[test]
+fn rfind_rewritten() {
+    // A reverse search uses the expression the regex was compiled from,
+    // not its pattern.
+    let re = Regex::with_full_case_folding(r"(?i)straße").unwrap();
+    assert_eq!(re.rfind("xSTRASSE").map(|m| m.range()), Some(1..8));
+    let re = Regex::with_canonical_equivalence(r"café").unwrap();
+    assert_eq!(re.rfind("café cafe\u{301}").map(|m| m.range()),
+               Some(6..12));
+    let ms: Vec<&str> = re.rsplitn("acafébcafe\u{301}c", 3).collect();
+    assert_eq!(ms, vec!["c", "b", "a"]);
+}
+
+#[test]
+fn capture_introspection() {
+    let re = regex!(r"(?P<year>\d{4})-(\d{2})(?:-(?P<day>\d{2}))?");
+    assert_eq!(re.captures_len(), 4);
+    let names: Vec<_> = re.capture_names().collect();
+    assert_eq!(names, vec![None, Some("year"), None, Some("day")]);
+    assert_eq!(re.capture_index("year"), Some(1));
+    assert_eq!(re.capture_index("day"), Some(3));
+    assert_eq!(re.capture_index("month"), None);
+}
+
+#[test]
+fn capture_spans() {
+    let re = regex!(r"(?P<year>\d{4})-(\d{2})(?:-(?P<day>\d{2}))?");
+    let groups: Vec<&str> = re.capture_spans()
+                              .into_iter()
+                              .map(|span| &re.as_str()[span])
+                              .collect();
+    assert_eq!(groups, vec![
+        re.as_str(), r"(?P<year>\d{4})", r"(\d{2})", r"(?P<day>\d{2})",
+    ]);
+}
+
+#[test]
+fn cache_hits_and_misses() {
+    let cache = RegexCache::new(10);
+    let a = cache.get_or_compile(r"\d+").unwrap();
+    let b = cache.get_or_compile(r"\d+").unwrap();
+    assert!(Arc::ptr_eq(&a, &b));
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1, evictions: 0 });
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn cache_options() {
+    let cache = RegexCache::new(10);
+    let plain = cache.get_or_compile("abc").unwrap();
+    let casei = cache.get_or_compile_with("abc", true, false, false,
+                                          1 << 20).unwrap();
+    assert!(!plain.is_match("ABC"));
+    assert!(casei.is_match("ABC"));
+    assert!(*casei != *plain);
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get_or_compile_with("a{1000}", false, false, false,
+                                      100).is_err());
+
+    let folded = cache.get_or_compile_with("(?i)ß", false, true, false,
+                                           1 << 20).unwrap();
+    assert!(folded.full_case_folding() && folded.is_match("SS"));
+    assert!(!cache.get_or_compile("(?i)ß").unwrap().is_match("SS"));
+    let equiv = cache.get_or_compile_with("é", false, false, true, 1 << 20)
+                     .unwrap();
+    assert!(equiv.canonical_equivalence() && equiv.is_match("e\u{301}"));
+    assert_eq!(cache.len(), 6);
+}
+
+#[test]
+fn cache_errors() {
+    let cache = RegexCache::new(10);
+    assert!(cache.get_or_compile("a(").is_err());
+    assert!(cache.get_or_compile("a(").is_err());
+    assert_eq!(cache.stats().misses, 1);
+    assert_eq!(cache.stats().hits, 1);
+}
+
+#[test]
+fn cache_lru_eviction() {
+    let cache = RegexCache::new(2);
+    cache.get_or_compile("a").unwrap();
+    cache.get_or_compile("b").unwrap();
+    // Using "a" makes "b" the least recently used entry.
+    cache.get_or_compile("a").unwrap();
+    cache.get_or_compile("c").unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.stats().evictions, 1);
+
+    cache.get_or_compile("a").unwrap();
+    assert_eq!(cache.stats().hits, 2);
+    cache.get_or_compile("b").unwrap();
+    assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 4, evictions: 2 });
+}
+
+#[test]
+fn cache_zero_capacity() {
+    let cache = RegexCache::new(0);
+    cache.get_or_compile("a").unwrap();
+    cache.get_or_compile("a").unwrap();
+    assert!(cache.is_empty());
+    assert_eq!(cache.stats().misses, 2);
+}
+
+#[test]
+fn cache_threads() {
+    let cache = Arc::new(RegexCache::new(4));
+    let handles: Vec<_> = (0..8).map(|i| {
+        let cache = cache.clone();
+        thread::spawn(move || {
+            let pat = format!("x{}", i % 4);
+            for _ in 0..100 {
+                assert!(cache.get_or_compile(&pat).unwrap().is_match(&pat));
+            }
+        })
+    }).collect();
+    for h in handles {
+        h.join().unwrap();
+    }
+    let stats = cache.stats();
+    assert_eq!(stats.hits + stats.misses, 800);
+    assert_eq!(stats.evictions, 0);
+    assert_eq!(cache.len(), 4);
+}
+
+fn lex(text: &str) -> Vec<(&'static str, &str)> {
+    let lexer = LexerBuilder::new("error")
+        .token("kw", "if|else")
+        .token("ident", "[a-z_]+")
+        .token("op", "=|==|!=")
+        .token("num", "[0-9]+")
+        .skip(r"\s+")
+        .skip(r"#[^\n]*")
+        .build().unwrap();
+    lexer.tokens(text).map(|t| (*t.kind(), t.as_str())).collect()
+}
+
+#[test]
+fn lexer_longest_match() {
+    assert_eq!(lex("if iffy == 1"), vec![
+        ("kw", "if"), ("ident", "iffy"), ("op", "=="), ("num", "1"),
+    ]);
+}
+
+#[test]
+fn lexer_ties_prefer_earlier_rule() {
+    assert_eq!(lex("else elsewhere"), vec![
+        ("kw", "else"), ("ident", "elsewhere"),
+    ]);
+}
+
+#[test]
+fn lexer_skip() {
+    assert_eq!(lex("  a # comment\n b"), vec![("ident", "a"), ("ident", "b")]);
+    assert_eq!(lex(" # only a comment"), vec![]);
+}
+
+#[test]
+fn lexer_error_token() {
+    assert_eq!(lex("a ?!=? b"), vec![
+        ("ident", "a"), ("error", "?"), ("op", "!="), ("error", "?"),
+        ("ident", "b"),
+    ]);
+    assert_eq!(lex("é€x"), vec![("error", "é€"), ("ident", "x")]);
+}
+
+#[test]
+fn lexer_error_recovery_single_pass() {
+    // Every position starts a match of the rule that fails at the very
+    // end, so trying each position in turn would take quadratic time.
+    let lexer = LexerBuilder::new(0).token(1, "x*y").token(2, "[0-9]")
+                                     .build().unwrap();
+    let text = format!("{}0", ::std::iter::repeat("x").take(50000)
+                                           .collect::<String>());
+    let toks: Vec<_> = lexer.tokens(&text)
+                            .map(|t| (*t.kind(), t.start(), t.end()))
+                            .collect();
+    assert_eq!(toks, vec![(0, 0, 50000), (2, 50000, 50001)]);
+}
+
+#[test]
+fn lexer_spans() {
+    let lexer = LexerBuilder::new(0).token(1, "[0-9]+").skip(" ")
+                                     .build().unwrap();
+    let spans: Vec<_> = lexer.tokens("12 345").map(|t| t.range()).collect();
+    assert_eq!(spans, vec![0..2, 3..6]);
+}
+
+#[test]
+fn lexer_empty_matches_ignored() {
+    let lexer = LexerBuilder::new(0).token(1, "a*").build().unwrap();
+    let toks: Vec<_> = lexer.tokens("baa")
+                            .map(|t| (*t.kind(), t.start(), t.end()))
+                            .collect();
+    assert_eq!(toks, vec![(0, 0, 1), (1, 1, 3)]);
+}
+
+#[test]
+fn lexer_no_rules() {
+    let lexer = LexerBuilder::new(0).build().unwrap();
+    let toks: Vec<_> = lexer.tokens("ab").map(|t| t.range()).collect();
+    assert_eq!(toks, vec![0..2]);
+}
+
+#[test]
+fn lexer_invalid_rule() {
+    assert!(LexerBuilder::new(0).token(1, "(").build().is_err());
+}
+
+const GREP_TEXT: &'static str =
+    "alpha\nbeta 1\ngamma\ndelta\nepsilon 2\nzeta\n";
+
+fn grep_lines(s: &LineSearcher, text: &str) -> Vec<(usize, bool, String)> {
+    s.search(text)
+     .map(|l| (l.number(), l.is_selected(), l.as_str().to_string()))
+     .collect()
+}
+
+#[test]
+fn grep_lines_and_matches() {
+    let re = Regex::new(r"\d|a\b").unwrap();
+    let lines: Vec<_> = LineSearcher::new(&re).search(GREP_TEXT).collect();
+    let found: Vec<_> = lines.iter()
+        .map(|l| (l.number(), l.offset(), l.as_str(), l.matches().to_vec()))
+        .collect();
+    assert_eq!(found, vec![
+        (1, 0, "alpha", vec![4..5]),
+        (2, 6, "beta 1", vec![3..4, 5..6]),
+        (3, 13, "gamma", vec![4..5]),
+        (4, 19, "delta", vec![4..5]),
+        (5, 25, "epsilon 2", vec![8..9]),
+        (6, 35, "zeta", vec![3..4]),
+    ]);
+}
+
+#[test]
+fn grep_anchors_per_line() {
+    let re = Regex::new(r"^[a-z]+$").unwrap();
+    let s = LineSearcher::new(&re);
+    assert_eq!(s.count(GREP_TEXT), 4);
+    assert_eq!(s.count("ab\r\ncd"), 1);
+}
+
+#[test]
+fn grep_invert() {
+    let re = Regex::new(r"\d").unwrap();
+    let s = LineSearcher::new(&re).invert(true);
+    let lines: Vec<_> = s.search(GREP_TEXT).map(|l| l.number()).collect();
+    assert_eq!(lines, vec![1, 3, 4, 6]);
+    assert_eq!(s.count(GREP_TEXT), 4);
+}
+
+#[test]
+fn grep_context() {
+    let re = Regex::new(r"\d").unwrap();
+    let s = LineSearcher::new(&re).before_context(1).after_context(2);
+    assert_eq!(grep_lines(&s, GREP_TEXT), vec![
+        (1, false, "alpha".to_string()),
+        (2, true, "beta 1".to_string()),
+        (3, false, "gamma".to_string()),
+        (4, false, "delta".to_string()),
+        (5, true, "epsilon 2".to_string()),
+        (6, false, "zeta".to_string()),
+    ]);
+
+    let s = LineSearcher::new(&re).after_context(1);
+    let kinds: Vec<_> = s.search(GREP_TEXT).map(|l| (l.number(), l.kind()))
+                         .collect();
+    assert_eq!(kinds, vec![
+        (2, LineKind::Selected), (3, LineKind::Context),
+        (5, LineKind::Selected), (6, LineKind::Context),
+    ]);
+    assert_eq!(s.count(GREP_TEXT), 2);
+}
+
+#[test]
+fn grep_multi_line_pattern() {
+    let re = Regex::new(r"a\nb").unwrap();
+    let lines: Vec<_> = LineSearcher::new(&re).search(GREP_TEXT)
+        .map(|l| (l.number(), l.matches().to_vec()))
+        .collect();
+    assert_eq!(lines, vec![(1, vec![4..5]), (2, vec![0..1])]);
+
+    let re = Regex::new(r"(?s)gamma.delta").unwrap();
+    assert_eq!(LineSearcher::new(&re).count(GREP_TEXT), 2);
+
+    let re = Regex::new(r"[^a-z]").unwrap();
+    assert_eq!(LineSearcher::new(&re).count(GREP_TEXT), 6);
+    assert_eq!(LineSearcher::new(&re).count("abc"), 0);
+
+    // A match spanning several lines is reported with each of them.
+    let re = Regex::new(r"(?s)b.*?d|x\n").unwrap();
+    let lines: Vec<_> = LineSearcher::new(&re).search("ab\nc\ndx\nyx\n")
+        .map(|l| (l.number(), l.matches().to_vec()))
+        .collect();
+    assert_eq!(lines, vec![
+        (1, vec![1..2]), (2, vec![0..1]), (3, vec![0..1, 1..2]),
+        (4, vec![1..2]),
+    ]);
+}
+
+#[test]
+fn grep_reader() {
+    let re = Regex::new(r"\d").unwrap();
+    let s = LineSearcher::new(&re).context(1);
+    let from_reader: Vec<_> = s.search_reader(GREP_TEXT.as_bytes())
+                               .map(|l| l.unwrap()).collect();
+    let from_str: Vec<_> = s.search(GREP_TEXT).map(|l| l.into_owned())
+                            .collect();
+    assert_eq!(from_reader, from_str);
+    assert_eq!(s.count_reader("1\n2\nx".as_bytes()).unwrap(), 2);
+
+    let re = Regex::new(r"a\nb").unwrap();
+    let s = LineSearcher::new(&re);
+    let lines: Vec<_> = s.search_reader(GREP_TEXT.as_bytes())
+                         .map(|l| l.unwrap().number()).collect();
+    assert_eq!(lines, vec![1, 2]);
+}
+
+#[test]
+fn grep_reader_invalid_utf8() {
+    let re = Regex::new(r"a").unwrap();
+    let s = LineSearcher::new(&re);
+    assert!(s.count_reader(&b"a\n\xff\n"[..]).is_err());
+}
+
+#[test]
+fn position_tracker() {
+    let text = "aé\n€𝄞\nz";
+    let mut t = PositionTracker::new(text);
+    assert_eq!(t.position(0), Position::default());
+    assert_eq!(t.position(3), Position {
+        byte: 3, char: 2, utf16: 2, line: 0, column: 2, utf16_column: 2,
+    });
+    assert_eq!(t.position(11), Position {
+        byte: 11, char: 5, utf16: 6, line: 1, column: 2, utf16_column: 3,
+    });
+    // Going backwards starts over.
+    assert_eq!(t.position(4).line, 1);
+    assert_eq!(t.position(12), Position {
+        byte: 12, char: 6, utf16: 7, line: 2, column: 0, utf16_column: 0,
+    });
+}
+
+#[test]
+#[should_panic]
+fn position_tracker_not_boundary() {
+    PositionTracker::new("é").position(1);
+}
+
+#[test]
+fn find_iter_positions() {
+    let re = Regex::new(r"[^\s]+").unwrap();
+    let text = "𝄞 ab\n  é";
+    let got: Vec<_> = re.find_iter_positions(text).map(|(m, s, e)| {
+        (m.as_str(), s.char, s.utf16, e.utf16, e.line, e.utf16_column)
+    }).collect();
+    assert_eq!(got, vec![
+        ("𝄞", 0, 0, 2, 0, 2),
+        ("ab", 2, 3, 5, 0, 5),
+        ("é", 7, 8, 9, 1, 3),
+    ]);
+}
+
+#[test]
+fn captures_iter_positions() {
+    let re = Regex::new(r"(?P<outer>(é)(x)?)(€)").unwrap();
+    let text = "aé€ é€";
+    let got: Vec<Vec<Option<(usize, usize)>>> =
+        re.captures_iter_positions(text).map(|(caps, spans)| {
+            assert_eq!(caps.len(), spans.len());
+            spans.iter().map(|s| s.map(|(s, e)| (s.char, e.char))).collect()
+        }).collect();
+    assert_eq!(got, vec![
+        vec![Some((1, 3)), Some((1, 2)), Some((1, 2)), None, Some((2, 3))],
+        vec![Some((4, 6)), Some((4, 5)), Some((4, 5)), None, Some((5, 6))],
+    ]);
+}
+
+// A haystack of byte chunks, which may split characters.
+struct ByteChunks(Vec<Vec<u8>>);
+
+impl ByteChunks {
+    fn new(text: &str, size: usize) -> ByteChunks {
+        ByteChunks(text.as_bytes().chunks(size).map(|c| c.to_vec()).collect())
+    }
+}
+
+impl Haystack for ByteChunks {
+    fn len(&self) -> usize {
+        self.0.iter().map(|c| c.len()).sum()
+    }
+
+    fn chunk_at(&self, at: usize) -> (&[u8], usize) {
+        let mut start = 0;
+        for chunk in &self.0 {
+            if at < start + chunk.len() {
+                return (chunk, start)
+            }
+            start += chunk.len();
+        }
+        panic!("offset {} out of bounds", at)
+    }
+}
+
+#[test]
+fn haystack_matches_str() {
+    let text = "foo été bar\nfoobar 𝄞 baz foo";
+    let pats = [r"foo", r"\bbar\b", r"^foo", r"foo$", r"(?m)^\w+", r"\w*",
+                r"é+", r"𝄞", r"(o+)(b)?"];
+    for pat in pats.iter() {
+        let re = Regex::new(pat).unwrap();
+        let expected: Vec<_> = re.find_iter(text)
+                                 .map(|m| (m.start(), m.end())).collect();
+        let caps = re.captures(text).map(|c| {
+            (0..c.len()).map(|i| c.pos(i)).collect::<Vec<_>>()
+        });
+        for size in 1..6 {
+            let hay = ByteChunks::new(text, size);
+            let got: Vec<_> = re.find_iter_haystack(&hay)
+                                .map(|m| (m.start(), m.end())).collect();
+            assert_eq!(got, expected, "pattern {:?}, size {}", pat, size);
+            let got = re.captures_haystack(&hay).map(|c| {
+                (0..c.len()).map(|i| c.pos(i)).collect::<Vec<_>>()
+            });
+            assert_eq!(got, caps);
+        }
+        let strs: Vec<&str> = text.split(' ').collect();
+        let mut pieces = vec![];
+        for (i, s) in strs.iter().enumerate() {
+            if i > 0 {
+                pieces.push(" ");
+            }
+            pieces.push(s);
+        }
+        let rope = Chunks::new(pieces);
+        let got: Vec<_> = re.find_iter_haystack(&rope)
+                            .map(|m| (m.start(), m.end())).collect();
+        assert_eq!(got, expected, "pattern {:?}", pat);
+        assert_eq!(re.is_match_haystack(&rope), re.is_match(text));
+        assert_eq!(re.find_haystack(text).map(|m| m.range()),
+                   re.find(text).map(|m| m.range()));
+    }
+}
+
+#[test]
+fn haystack_deque_wrapped() {
+    let mut deque: VecDeque<u8> = VecDeque::with_capacity(8);
+    deque.extend(b"xxxxxx".iter().cloned());
+    for _ in 0..6 {
+        deque.pop_front();
+    }
+    deque.extend("ab€cd".as_bytes().iter().cloned());
+    assert!(!deque.as_slices().1.is_empty());
+    let re = Regex::new(r"b€c").unwrap();
+    assert_eq!(re.find_haystack(&deque).map(|m| m.range()), Some(1..6));
+}
+
+#[test]
+fn haystack_invalid_utf8() {
+    let re = Regex::new(r"a.b").unwrap();
+    let bytes: &[u8] = b"xa\xffb";
+    assert_eq!(re.find_haystack(bytes).map(|m| m.range()), Some(1..4));
+    let re = Regex::new(r"a\x{FFFD}{2}b").unwrap();
+    assert_eq!(re.find_haystack(&b"a\xe2\x82b"[..]).map(|m| m.range()),
+               Some(0..4));
+}
+
+#[test]
+fn haystack_empty_chunks() {
+    let rope = Chunks::new(vec!["", "ab", "", "c", ""]);
+    let re = Regex::new(r"bc$").unwrap();
+    assert_eq!(re.find_haystack(&rope).map(|m| m.range()), Some(1..3));
+    let re = Regex::new(r"").unwrap();
+    let empty = Chunks::new(vec![""]);
+    assert_eq!(re.find_iter_haystack(&empty).map(|m| m.range())
+                 .collect::<Vec<_>>(), vec![0..0]);
+}
+
+#[test]
+fn cancel_not_cancelled() {
+    let re = Regex::new(r"(?P<w>\w+)").unwrap();
+    let token = CancelToken::new();
+    let text = "foo bar  baz";
+    let found: Vec<_> = re.find_iter_cancellable(text, &token)
+                          .map(|m| m.unwrap().range()).collect();
+    let expected: Vec<_> = re.find_iter(text).map(|m| m.range()).collect();
+    assert_eq!(found, expected);
+    assert_eq!(re.replace_all_cancellable(text, "<$w>", &token).unwrap(),
+               re.replace_all(text, "<$w>"));
+    assert!(re.is_match_cancellable(text, &token).unwrap());
+    assert_eq!(re.find_cancellable("  ", &token), Ok(None));
+
+    // Empty matches are skipped the same way as by the other iterators.
+    let re = Regex::new(r"a*").unwrap();
+    let text = "baaéb";
+    let found: Vec<_> = re.find_iter_cancellable(text, &token)
+                          .map(|m| m.unwrap().range()).collect();
+    let expected: Vec<_> = re.find_iter(text).map(|m| m.range()).collect();
+    assert_eq!(found, expected);
+    assert_eq!(re.replace_all_cancellable(text, "-", &token).unwrap(),
+               re.replace_all(text, "-"));
+}
+
+#[test]
+fn cancel_before_search() {
+    let re = Regex::new(r"\w+").unwrap();
+    let token = CancelToken::new();
+    token.clone().cancel();
+    assert!(token.is_cancelled());
+    assert_eq!(re.is_match_cancellable("foo", &token), Err(Cancelled::new(0)));
+    let mut it = re.captures_iter_cancellable("foo", &token);
+    assert_eq!(it.next().unwrap().err().unwrap().position(), 0);
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn cancel_during_iteration() {
+    let re = Regex::new(r"\d+").unwrap();
+    let token = CancelToken::new();
+    let mut results = vec![];
+    for m in re.find_iter_cancellable("1 22 333 4444", &token) {
+        results.push(m.map(|m| m.as_str()));
+        if results.len() == 2 {
+            token.cancel();
+        }
+    }
+    assert_eq!(results, vec![Ok("1"), Ok("22"), Err(Cancelled::new(4))]);
+}
+
+#[test]
+fn cancel_replace_resume() {
+    let re = Regex::new(r"\d+").unwrap();
+    let text = "a1 b22 c333";
+    let token = CancelToken::new();
+    let mut dst = String::new();
+    let err = re.replace_all_cancellable_into(text, |caps: &Captures| {
+        token.cancel();
+        format!("[{}]", caps.at(0).unwrap())
+    }, &mut dst, &token).unwrap_err();
+    assert_eq!(dst, "a[1]");
+    assert_eq!(err.position(), 2);
+
+    let token = CancelToken::new();
+    re.replace_all_cancellable_into(&text[err.position()..], "#", &mut dst,
+                                    &token).unwrap();
+    assert_eq!(dst, "a[1] b# c#");
+
+    let mut dst = String::from("> ");
+    re.replace_all_cancellable_into("none", "#", &mut dst, &token).unwrap();
+    assert_eq!(dst, "> none");
+}
+
+#[test]
+fn cancel_from_another_thread() {
+    // A search that takes a long time: nested repetition over a long text
+    // that never matches.
+    let re = Regex::new(r"(?:a*)*(?:b|c)*x").unwrap();
+    let text: String = ::std::iter::repeat("ab").take(2_000_000).collect();
+    let token = CancelToken::new();
+    let remote = token.clone();
+    let handle = thread::spawn(move || {
+        thread::sleep(::std::time::Duration::from_millis(1));
+        remote.cancel();
+    });
+    assert_eq!(re.find_cancellable(&text, &token), Err(Cancelled::new(0)));
+    handle.join().unwrap();
+}
+
+#[test]
+fn trace_alternation() {
+    let re = Regex::new(r"a|ab").unwrap();
+    let trace = re.trace("ab");
+    assert_eq!(&trace.captures()[..2], &[Some(0), Some(1)]);
+    assert_eq!(trace.captures(), &re.captures("ab").map(|c| {
+        (0..c.len()).flat_map(|i| {
+            let p = c.pos(i);
+            vec![p.map(|p| p.0), p.map(|p| p.1)]
+        }).collect::<Vec<_>>()
+    }).unwrap()[..]);
+
+    let steps = trace.steps();
+    assert_eq!(steps[0].position, 0);
+    assert_eq!(steps[0].saves[0].slot, 0);
+    // The program is simplified to `ab??` before it's traced, so a single
+    // thread consumes the 'a' and the lazy split then prefers the match.
+    assert_eq!(steps[0].stepped.len(), 1);
+    assert!(trace.to_string().contains(": split to 4, then 3\n"));
+    let won = steps.iter().position(|s| s.matched.is_some()).unwrap();
+    assert_eq!(steps[won].position, 1);
+    assert_eq!(trace.describe(steps[won].matched.unwrap()), "match");
+}
+
+#[test]
+fn trace_dump() {
+    let re = Regex::new(r"(?P<x>[0-9])\b").unwrap();
+    let trace = re.trace("a1");
+    let dump = trace.to_string();
+    assert!(dump.starts_with("pattern: (?P<x>[0-9])\\b\n"));
+    assert!(dump.contains("save start of group 1 `(?P<x>[0-9])`"));
+    assert!(dump.contains("class [0-9] in `(?P<x>[0-9])`"));
+    assert!(dump.contains(": word boundary\n"));
+    // The group's two saves and its class.
+    let in_group = (0..trace.num_instructions())
+        .filter(|&pc| trace.group(pc) == Some(1)).count();
+    assert_eq!(in_group, 3);
+    assert!(dump.contains("word boundary"));
+    assert!(dump.contains("at 2 (end):"));
+    assert!(dump.ends_with("result: match at 1..2 \"1\""));
+    assert!(re.trace("a").to_string().ends_with("result: no match"));
+}
+
+#[test]
+fn union_and_concat() {
+    let a = Regex::new(r"(?i)(?P<a>ab)").unwrap();
+    let b = Regex::new(r"(x)|y").unwrap();
+    let c = Regex::new(r"(?s)(.)$").unwrap();
+
+    let re = Regex::union(&[&a, &b]).unwrap();
+    assert_eq!(re.captures_len(), 3);
+    assert_eq!(re.capture_index("a"), Some(1));
+    assert!(re.is_match("AB") && re.is_match("y"));
+    assert_eq!(re.captures("zx").unwrap().pos(2), Some((1, 2)));
+
+    let re = Regex::concat(&[&b, &a, &c]).unwrap();
+    // The alternation of `b` stays grouped and `(?i)` stays with `a`.
+    assert!(re.is_match("xAb\n"));
+    assert!(re.is_match("yab!"));
+    assert!(!re.is_match("zab!"));
+    let caps = re.captures("yAB\n").unwrap();
+    assert_eq!(caps.at(1), None);
+    assert_eq!(caps.name("a"), Some("AB"));
+    assert_eq!(caps.at(3), Some("\n"));
+    // Case insensitivity doesn't leak into the other operands.
+    assert!(!Regex::concat(&[&a, &b]).unwrap().is_match("abX"));
+}
+
+#[test]
+fn union_and_concat_empty() {
+    let re = Regex::union(&[]).unwrap();
+    assert!(!re.is_match("") && !re.is_match("a b"));
+    let re = Regex::new(re.as_str()).unwrap();
+    assert!(!re.is_match("") && !re.is_match("a b"));
+    let re = Regex::concat(&[]).unwrap();
+    assert_eq!(re.find("abc").map(|m| m.range()), Some(0..0));
+}
+
+#[test]
+fn union_keeps_modes() {
+    // The operands are combined as compiled, not as their patterns read.
+    let folded = Regex::with_full_case_folding(r"(?i)(straße)").unwrap();
+    let num = Regex::new(r"(\d+)").unwrap();
+    let re = Regex::union(&[&num, &folded]).unwrap();
+    assert_eq!(re.captures("STRASSE").unwrap().pos(2), Some((0, 7)));
+    assert!(Regex::new(re.as_str()).unwrap().is_match("STRASSE"));
+}
+
+#[test]
+fn union_duplicate_names() {
+    let a = Regex::new(r"(?P<n>a)").unwrap();
+    let b = Regex::new(r"(?P<n>b)").unwrap();
+    match Regex::union(&[&a, &b]) {
+        Err(Error::DuplicateGroupName(ref name)) => assert_eq!(name, "n"),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(re) => panic!("unexpected regex: {}", re),
+    }
+    assert!(Regex::concat(&[&a, &a]).is_err());
+}
+
+#[test]
+fn from_expr() {
+    // `ab|c` followed by `d*`, built without a pattern string.
+    let expr = Expr::Concat(vec![
+        Expr::Alternate(vec![
+            Expr::Literal { chars: vec!['a', 'b'], casei: false },
+            Expr::Group {
+                e: Box::new(Expr::Literal { chars: vec!['c'], casei: true }),
+                i: Some(7),
+                name: Some("c".to_string()),
+            },
+        ]),
+        Expr::Repeat {
+            e: Box::new(Expr::Concat(vec![
+                Expr::Literal { chars: vec!['d'], casei: false },
+                Expr::WordBoundary,
+            ])),
+            r: regex_syntax::Repeater::ZeroOrMore,
+            greedy: true,
+        },
+    ]);
+    let re = Regex::from_expr(expr).unwrap();
+    assert_eq!(re.capture_index("c"), Some(1));
+    assert_eq!(re.find("xCd").map(|m| m.as_str()), Some("Cd"));
+    assert_eq!(re.find("abdd").map(|m| m.as_str()), Some("ab"));
+    assert_eq!(Regex::new(re.as_str()).unwrap().as_str(), re.as_str());
+    let spans = re.capture_spans();
+    assert_eq!(spans.len(), 2);
+    assert!(re.as_str()[spans[1].clone()].starts_with("(?P<c>"));
+
+    let expr = Expr::parse(r"(?:x(?:y|z)){2,3}?").unwrap();
+    let re = Regex::from_expr(expr).unwrap();
+    assert_eq!(re.find("xyxzxy").map(|m| m.as_str()), Some("xyxz"));
+}
+
+#[test]
+fn language_subset() {
+    let checker = LanguageChecker::new();
+    let num = Regex::new(r"[0-9]+").unwrap();
+    let small = Regex::new(r"[1-9][0-9]?").unwrap();
+    assert!(checker.is_subset(&small, &num).unwrap().holds());
+    let v = checker.is_subset(&num, &small).unwrap();
+    assert!(!v.holds());
+    assert_eq!(v.witness(), Some("0"));
+    assert!(!num.is_full_match("") && !small.is_full_match("0"));
+}
+
+#[test]
+fn language_equivalent() {
+    let checker = LanguageChecker::new();
+    let a = Regex::new(r"a*").unwrap();
+    let b = Regex::new(r"(?:a|aa)*").unwrap();
+    let c = Regex::new(r"(?:aa)*").unwrap();
+    let v = checker.is_equivalent(&a, &b).unwrap();
+    assert_eq!((v.holds(), v.witness()), (true, None));
+    assert_eq!(checker.is_equivalent(&a, &c).unwrap().witness(), Some("a"));
+    // Anchors and groups don't change the full match language.
+    let d = Regex::new(r"^(a)*$").unwrap();
+    assert!(checker.is_equivalent(&a, &d).unwrap().holds());
+}
+
+#[test]
+fn language_intersects_and_empty() {
+    let checker = LanguageChecker::new();
+    let a = Regex::new(r"[a-c]+x").unwrap();
+    let b = Regex::new(r"c.*").unwrap();
+    let c = Regex::new(r"\d+").unwrap();
+    let v = checker.intersects(&a, &b).unwrap();
+    assert_eq!((v.holds(), v.witness()), (true, Some("cx")));
+    assert!(!checker.intersects(&a, &c).unwrap().holds());
+
+    let empty = Regex::new(r"\b\B").unwrap();
+    assert!(checker.is_empty(&empty).unwrap().holds());
+    let v = checker.is_empty(&Regex::new(r"a$b|\d{3}").unwrap()).unwrap();
+    assert_eq!((v.holds(), v.witness()), (false, Some("000")));
+}
+
+#[test]
+fn language_assertions_and_case() {
+    let checker = LanguageChecker::new();
+    let a = Regex::new(r"(?i)straße").unwrap();
+    let b = Regex::new(r"[sS][tT][rR][aA][ßẞ][eE]").unwrap();
+    // `(?i)s` also matches the long s, `ſ`.
+    let v = checker.is_equivalent(&a, &b).unwrap();
+    assert_eq!(v.witness(), Some("ſtraße"));
+    let b = Regex::new(r"[sSſ][tT][rR][aA][ßẞ][eE]").unwrap();
+    assert!(checker.is_equivalent(&a, &b).unwrap().holds());
+    let c = Regex::new(r"(?i)[a-z]+").unwrap();
+    let v = checker.is_subset(&b, &c).unwrap();
+    assert_eq!(v.witness(), Some("straße"));
+
+    let words = Regex::new(r"(?s)\w+\b.*").unwrap();
+    let spaced = Regex::new(r"(?s)\w+(?:\W.*)?").unwrap();
+    assert!(checker.is_equivalent(&words, &spaced).unwrap().holds());
+    let lines = Regex::new(r"(?ms)a$.*").unwrap();
+    let v = checker.is_subset(&lines, &Regex::new(r"a").unwrap()).unwrap();
+    assert_eq!(v.witness(), Some("a\n"));
+}
+
+#[test]
+fn language_state_limit() {
+    let checker = LanguageChecker::new().state_limit(50);
+    // The DFA has to remember the last 11 characters.
+    let a = Regex::new(r"[ab]*a[ab]{10}").unwrap();
+    let b = Regex::new(r"[ab]*[ab]{11}").unwrap();
+    match checker.is_subset(&a, &b) {
+        Err(Error::TooManyStates(50)) => {}
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert!(LanguageChecker::new().is_subset(&a, &b).unwrap().holds());
+}
+
+#[test]
+fn generated_strings_match() {
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let pats = [
+        r"[a-z]+@[a-z]+\.(?:com|org)",
+        r"(?i)straße\d{2,4}",
+        r"\b\w+\b[^\w\n]*",
+        r"(?m)^a$\n?b*(?s:.)",
+    ];
+    for pat in &pats {
+        let re = Regex::new(pat).unwrap();
+        let gen = Generator::new(Expr::parse(pat).unwrap());
+        for _ in 0..100 {
+            let s = gen.generate(&mut rng).unwrap();
+            assert!(re.is_full_match(&s), "{} doesn't match {:?}", pat, s);
+        }
+    }
+}
+
+/// Returns a random pattern over `a` and `b` with groups, alternations and
+/// every kind of repetition.
+fn random_pattern<R: Rng>(rng: &mut R, depth: usize) -> String {
+    const ATOMS: &'static [&'static str] = &["a", "b", ".", "[ab]", "ab"];
+    const REPEATS: &'static [&'static str] = &[
+        "*", "+", "?", "*?", "+?", "??", "{0,2}", "{1,2}?",
+    ];
+    if depth == 0 {
+        return rng.choose(ATOMS).unwrap().to_string()
+    }
+    let e = random_pattern(rng, depth - 1);
+    match rng.gen_range(0, 5) {
+        0 => format!("{}{}", e, random_pattern(rng, depth - 1)),
+        1 => format!("(?:{}|{})", e, random_pattern(rng, depth - 1)),
+        2 => format!("({})", e),
+        _ => format!("(?:{}){}", e, rng.choose(REPEATS).unwrap()),
+    }
+}
+
+#[test]
+fn simplify_keeps_matches() {
+    // Every compiled regex is simplified first, so simplifying must not
+    // change any match or capture, not even for lazy repetitions.
+    fn all_captures(re: &Regex, text: &str)
+                   -> Vec<Vec<Option<(usize, usize)>>> {
+        re.captures_iter(text).map(|caps| {
+            (0..caps.len()).map(|i| caps.pos(i)).collect()
+        }).collect()
+    }
+
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    for _ in 0..2000 {
+        let pat = random_pattern(&mut rng, 4);
+        let simplified = Regex::new(&pat).unwrap();
+        let mut plain = simplified.clone();
+        if let Dynamic(ref mut d) = plain {
+            let ast = Expr::parse(&pat).unwrap();
+            let (prog, _) = Program::new_unsimplified(ast, 1 << 20).unwrap();
+            d.prog = Arc::new(prog);
+        }
+        for _ in 0..10 {
+            let len = rng.gen_range(0, 6);
+            let text: String = (0..len).map(|_| {
+                *rng.choose(&['a', 'b']).unwrap()
+            }).collect();
+            assert_eq!(all_captures(&simplified, &text),
+                       all_captures(&plain, &text),
+                       "{} on {:?}", pat, text);
+        }
+    }
+    let re = Regex::new(r"(?:(?:.+?)??)*").unwrap();
+    let found: Vec<_> = re.find_iter("aab").map(|m| m.range()).collect();
+    assert_eq!(found, vec![0..3]);
+}
+
+#[test]
+fn near_misses_dont_match() {
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let pat = r"[0-9]{3}-[0-9]{4}";
+    let re = Regex::new(pat).unwrap();
+    let gen = Generator::new(Expr::parse(pat).unwrap()).ascii_bias(false);
+    for _ in 0..100 {
+        let s = gen.near_miss(&mut rng).unwrap();
+        assert!(!re.is_full_match(&s), "{} matches {:?}", pat, s);
+    }
+}
+
+#[test]
+fn full_case_folding() {
+    let re = Regex::with_full_case_folding(r"(?i)stra(ß)e").unwrap();
+    for text in &["straße", "STRASSE", "Strasse", "STRAẞE", "ſtraſſe"] {
+        assert!(re.is_full_match(text), "no match for {:?}", text);
+    }
+    assert!(!re.is_match("strase") && !re.is_match("strasße"));
+    assert_eq!(re.captures("STRASSE").unwrap().at(1), Some("SS"));
+
+    let re = Regex::with_full_case_folding(r"(?i)file|ﬀ").unwrap();
+    let found: Vec<_> = re.find_iter("ﬁle FILE ff FF ﬀ").map(|m| m.as_str())
+                          .collect();
+    assert_eq!(found, vec!["ﬁle", "FILE", "ff", "FF", "ﬀ"]);
+
+    // Negated classes only match single characters.
+    let re = Regex::with_full_case_folding(r"(?i)^[^s]$").unwrap();
+    assert!(re.is_match("ß") && !re.is_match("ss"));
+    let re = Regex::with_full_case_folding(r"(?i)^[ß]$").unwrap();
+    assert!(re.is_match("SS") && !re.is_match("sss"));
+
+    // Runs of overlapping foldings don't blow up.
+    let long: String = ::std::iter::repeat('s').take(24).collect();
+    let re = Regex::with_full_case_folding(&format!("(?i){}", long)).unwrap();
+    assert!(re.is_full_match(&long.replace("ss", "ß")));
+    assert!(!re.is_match(&long[1..].replace("ss", "ß")));
+
+    // Case sensitive parts are unaffected.
+    let re = Regex::with_full_case_folding(r"ß(?i:ß)").unwrap();
+    assert!(re.is_match("ßSS") && !re.is_match("ssß"));
+}
+
+#[test]
+fn canonical_equivalence() {
+    let re = Regex::with_canonical_equivalence(r"^caf(é)$").unwrap();
+    for text in &["café", "cafe\u{301}", "cafe\u{341}"] {
+        assert!(re.is_match(text), "no match for {:?}", text);
+    }
+    assert!(!re.is_match("cafe") && !re.is_match("cafe\u{300}"));
+    assert_eq!(re.captures("cafe\u{301}").unwrap().pos(1), Some((3, 6)));
+
+    // Marks of different classes can come in any order.
+    let re = Regex::with_canonical_equivalence(r"(?i)^Ệ$").unwrap();
+    for text in &["ệ", "e\u{323}\u{302}", "E\u{302}\u{323}", "ê\u{323}",
+                  "Ẹ\u{302}"] {
+        assert!(re.is_match(text), "no match for {:?}", text);
+    }
+    assert!(!re.is_match("ê") && !re.is_match("e\u{302}\u{302}\u{323}"));
+
+    let re = Regex::with_canonical_equivalence(r"[à-å]+|한").unwrap();
+    let text = "a\u{300}a\u{30a} \u{1112}\u{1161}\u{11ab} 하\u{11ab}";
+    let found: Vec<_> = re.find_iter(text).map(|m| m.as_str()).collect();
+    assert_eq!(found, vec!["a\u{300}a\u{30a}", "\u{1112}\u{1161}\u{11ab}",
+                           "하\u{11ab}"]);
+
+    // A match doesn't end between a character and its marks.
+    for re in &["e", "[a-z]", "e|e\u{301}x"] {
+        let re = Regex::with_canonical_equivalence(re).unwrap();
+        assert_eq!(re.find("e\u{301} e").map(|m| m.range()), Some(4..5));
+        assert_eq!(re.rfind("e e\u{301}").map(|m| m.range()), Some(0..1));
+        assert_eq!(re.find_overlapping_iter("e\u{301}").count(), 0);
+    }
+    let re = Regex::with_canonical_equivalence(r"\w+?").unwrap();
+    assert_eq!(re.find("e\u{301}\u{323}x").map(|m| m.as_str()),
+               Some("e\u{301}\u{323}"));
+
+    // Six marks of different classes can be written in 720 orders, but
+    // seven are too many.
+    let marks = "\u{334}\u{31b}\u{327}\u{323}\u{301}\u{315}";
+    let re = Regex::with_canonical_equivalence(&format!("^x{}$", marks))
+                   .unwrap();
+    let reordered: String = marks.chars().rev().collect();
+    assert!(re.is_match(&format!("x{}", reordered)));
+    match Regex::with_canonical_equivalence(&format!("x{}\u{35d}", marks)) {
+        Err(Error::TooManyMarkOrders(720)) => {}
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
 macro_rules! noparse(
     ($name:ident, $re:expr) => (
         #[test]
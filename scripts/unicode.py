@@ -67,6 +67,8 @@ def is_surrogate(n):
 def load_unicode_data(f):
     fetch(f)
     gencats = {}
+    decomps = {}
+    combines = {}
 
     udict = {};
     range_start = -1;
@@ -97,6 +99,13 @@ def load_unicode_data(f):
                 gencats[cat] = []
             gencats[cat].append(code)
 
+        # record canonical decompositions (the ones without a <tag>) and
+        # non-zero combining classes
+        if decomp != "" and not decomp.startswith("<"):
+            decomps[code] = [int(c, 16) for c in decomp.split()]
+        if int(combine) != 0:
+            combines[code] = int(combine)
+
     # generate Not_Assigned from Assigned
     gencats["Cn"] = gen_unassigned(gencats["Assigned"])
     # Assigned is not a real category
@@ -105,7 +114,7 @@ def load_unicode_data(f):
     gencats["C"].extend(gencats["Cn"])
     gencats = group_cats(gencats)
 
-    return gencats
+    return (gencats, decomps, combines)
 
 def group_cats(cats):
     cats_out = {}
@@ -197,15 +206,55 @@ def load_properties(f, interestingprops):
 def load_case_folding(f):
     fetch(f)
     re1 = re.compile("^ *([0-9A-F]+) *; *[CS] *; *([0-9A-F]+) *;")
+    re2 = re.compile("^ *([0-9A-F]+) *; *F *; *([0-9A-F ]+) *;")
     c_plus_s = []
+    full = []
     for line in fileinput.input(f):
         m = re1.match(line)
         if m:
             a = int(m.group(1), 16)
             b = int(m.group(2), 16)
             c_plus_s.append((a, b))
-
-    return {"C_plus_S": c_plus_s}
+            continue
+        m = re2.match(line)
+        if m:
+            a = int(m.group(1), 16)
+            bs = [int(b, 16) for b in m.group(2).split()]
+            full.append((a, bs))
+
+    return ({"C_plus_S": c_plus_s}, full)
+
+def full_decompositions(decomps, combines):
+    # Applies canonical decompositions recursively and puts the combining
+    # marks in canonical order. Hangul syllables are decomposed
+    # algorithmically, so they have no entries in UnicodeData.txt.
+    def decompose(c):
+        if c not in decomps:
+            return [c]
+        return [d for x in decomps[c] for d in decompose(x)]
+    full = []
+    for code in sorted(decomps):
+        ds = decompose(code)
+        # a stable sort of each run of combining marks by combining class
+        i = 0
+        while i < len(ds):
+            j = i
+            while j < len(ds) and ds[j] in combines:
+                j += 1
+            ds[i:j] = sorted(ds[i:j], key=lambda d: combines[d])
+            i = j + 1
+        full.append((code, ds))
+    return full
+
+def group_combining_classes(combines):
+    groups = []
+    for code in sorted(combines):
+        ccc = combines[code]
+        if groups and groups[-1][1] + 1 == code and groups[-1][2] == ccc:
+            groups[-1] = (groups[-1][0], code, ccc)
+        else:
+            groups.append((code, code, ccc))
+    return groups
 
 def escape_char(c):
     return "'\\u{%x}'" % c
@@ -234,6 +283,30 @@ def emit_property_module(f, mod, tbl):
         emit_table(f, "%s_table" % cat, tbl[cat])
     f.write("}\n\n")
 
+def emit_case_folding_module(f, tbl, full):
+    f.write("pub mod case_folding {\n")
+    keys = tbl.keys()
+    keys.sort()
+    for cat in keys:
+        emit_table(f, "%s_table" % cat, tbl[cat])
+    # status F: characters whose full case folding is several characters
+    emit_table(f, "F_table", full, "&'static [(char, &'static [char])]",
+        pfun=lambda x: "(%s,&[%s])" % (escape_char(x[0]),
+                                       ",".join(map(escape_char, x[1]))))
+    f.write("}\n\n")
+
+def emit_normalization_module(f, decomps, combines):
+    f.write("pub mod normalization {\n")
+    emit_table(f, "Decomposition_table", full_decompositions(decomps, combines),
+        "&'static [(char, &'static [char])]",
+        pfun=lambda x: "(%s,&[%s])" % (escape_char(x[0]),
+                                       ",".join(map(escape_char, x[1]))))
+    emit_table(f, "Combining_Class_table", group_combining_classes(combines),
+        "&'static [(char, char, u8)]",
+        pfun=lambda x: "(%s,%s,%d)" % (escape_char(x[0]), escape_char(x[1]),
+                                       x[2]))
+    f.write("}\n\n")
+
 def emit_regex_module(f, cats, w_data):
     f.write("pub mod regex {\n")
     regex_class = "&'static [(char, char)]"
@@ -264,14 +337,14 @@ if __name__ == "__main__":
         with open("ReadMe.txt") as readme:
             pattern = "for Version (\d+)\.(\d+)\.(\d+) of the Unicode"
             unicode_version = re.search(pattern, readme.read()).groups()
-        gencats = load_unicode_data("UnicodeData.txt")
+        (gencats, decomps, combines) = load_unicode_data("UnicodeData.txt")
         want_derived = ["XID_Start", "XID_Continue", "Alphabetic", "Lowercase", "Uppercase"]
         other_derived = ["Default_Ignorable_Code_Point", "Grapheme_Extend"]
         derived = load_properties("DerivedCoreProperties.txt", want_derived + other_derived)
         scripts = load_properties("Scripts.txt", [])
         props = load_properties("PropList.txt",
                 ["White_Space", "Join_Control", "Noncharacter_Code_Point"])
-        case_folding = load_case_folding("CaseFolding.txt")
+        (case_folding, full_case_folding) = load_case_folding("CaseFolding.txt")
 
         # all of these categories will also be available as \p{} in libregex
         allcats = []
@@ -294,4 +367,5 @@ if __name__ == "__main__":
 
         # emit lookup tables for \p{}, along with \d, \w, and \s for libregex
         emit_regex_module(rf, allcats, perl_words)
-        emit_property_module(rf, "case_folding", case_folding)
+        emit_case_folding_module(rf, case_folding, full_case_folding)
+        emit_normalization_module(rf, decomps, combines)
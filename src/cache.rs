@@ -0,0 +1,224 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use re::{DEFAULT_SIZE_LIMIT, Error, Modes, Regex, compile_with_options};
+
+/// A thread-safe cache of compiled regular expressions.
+///
+/// Patterns are compiled on first use and kept until the cache is full, at
+/// which point the least recently used entry is evicted. An entry is keyed
+/// by its pattern string together with the options it was compiled with.
+///
+/// Compile errors are cached too, so a bad pattern that's requested
+/// repeatedly is only compiled once (until it's evicted).
+///
+/// A cache is usually shared between threads by wrapping it in an `Arc` or
+/// by storing it in a lazily initialized `static`.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::RegexCache;
+/// # fn main() {
+/// let cache = RegexCache::new(100);
+/// let re = cache.get_or_compile(r"\d+").unwrap();
+/// assert!(re.is_match("abc123"));
+///
+/// // The second lookup doesn't compile the pattern again.
+/// let again = cache.get_or_compile(r"\d+").unwrap();
+/// assert_eq!(cache.stats().hits, 1);
+/// assert_eq!(cache.stats().misses, 1);
+/// # let _ = again;
+/// # }
+/// ```
+pub struct RegexCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+/// Statistics about the lookups in a `RegexCache`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of lookups that found an entry in the cache.
+    pub hits: u64,
+    /// The number of lookups that had to compile the pattern.
+    pub misses: u64,
+    /// The number of entries removed to make room for new ones.
+    pub evictions: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    pattern: String,
+    modes: Modes,
+    size_limit: usize,
+}
+
+struct Entry {
+    compiled: Result<Arc<Regex>, Error>,
+    last_used: u64,
+}
+
+struct Inner {
+    entries: HashMap<Key, Entry>,
+    // Maps the time each entry was last used to its key, so the least
+    // recently used entry is always first.
+    recency: BTreeMap<u64, Key>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl Inner {
+    /// Looks up `key` and marks it as the most recently used entry.
+    fn get(&mut self, key: &Key) -> Option<Result<Arc<Regex>, Error>> {
+        let now = self.clock;
+        let compiled = match self.entries.get_mut(key) {
+            None => return None,
+            Some(entry) => {
+                self.recency.remove(&entry.last_used);
+                entry.last_used = now;
+                entry.compiled.clone()
+            }
+        };
+        self.recency.insert(now, key.clone());
+        self.clock += 1;
+        Some(compiled)
+    }
+
+    /// Inserts an entry, evicting the least recently used entries if the
+    /// cache would exceed `capacity`.
+    fn insert(&mut self, key: Key, compiled: Result<Arc<Regex>, Error>,
+              capacity: usize) {
+        while self.entries.len() >= capacity {
+            let oldest = match self.recency.keys().next() {
+                None => break,
+                Some(&t) => t,
+            };
+            let key = self.recency.remove(&oldest).unwrap();
+            self.entries.remove(&key);
+            self.stats.evictions += 1;
+        }
+        if capacity == 0 {
+            return
+        }
+        let now = self.clock;
+        self.clock += 1;
+        self.recency.insert(now, key.clone());
+        self.entries.insert(key, Entry {
+            compiled: compiled,
+            last_used: now,
+        });
+    }
+}
+
+impl RegexCache {
+    /// Creates an empty cache that holds at most `capacity` entries.
+    ///
+    /// A cache with a capacity of `0` never keeps anything, so every lookup
+    /// compiles its pattern.
+    pub fn new(capacity: usize) -> RegexCache {
+        RegexCache {
+            capacity: capacity,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                clock: 0,
+                stats: CacheStats::default(),
+            }),
+        }
+    }
+
+    /// Returns the regex compiled from `pattern` with `Regex::new`,
+    /// compiling it only if it isn't in the cache.
+    ///
+    /// If the pattern is invalid, then the error is returned (and cached).
+    pub fn get_or_compile(&self, pattern: &str) -> Result<Arc<Regex>, Error> {
+        self.get_or_compile_with(pattern, false, false, false,
+                                 DEFAULT_SIZE_LIMIT)
+    }
+
+    /// Returns the regex compiled from `pattern` with the options given,
+    /// compiling it only if it isn't in the cache.
+    ///
+    /// If `case_insensitive` is set, then the regex matches as if the
+    /// pattern started with `(?i)`. If `full_case_folding` or
+    /// `canonical_equivalence` is set, then it's compiled as with
+    /// `Regex::with_full_case_folding` or `Regex::with_canonical_equivalence`
+    /// respectively. The size limit is the same as in
+    /// `Regex::with_size_limit`. The same pattern with different options is
+    /// cached separately.
+    pub fn get_or_compile_with(&self, pattern: &str, case_insensitive: bool,
+                               full_case_folding: bool,
+                               canonical_equivalence: bool, size_limit: usize)
+                              -> Result<Arc<Regex>, Error> {
+        let modes = Modes {
+            case_insensitive: case_insensitive,
+            full_case_folding: full_case_folding,
+            canonical_equivalence: canonical_equivalence,
+        };
+        let key = Key {
+            pattern: pattern.to_string(),
+            modes: modes,
+            size_limit: size_limit,
+        };
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(compiled) = inner.get(&key) {
+                inner.stats.hits += 1;
+                return compiled;
+            }
+            inner.stats.misses += 1;
+        }
+
+        // The lock isn't held while compiling, so other lookups aren't
+        // blocked by a slow compilation. If another thread compiled the same
+        // pattern in the meantime, then its entry is kept.
+        let compiled = compile_with_options(pattern, key.modes, size_limit)
+                           .map(Arc::new);
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(existing) = inner.get(&key) {
+            return existing;
+        }
+        inner.insert(key, compiled.clone(), self.capacity);
+        compiled
+    }
+
+    /// Returns the statistics of all lookups since the cache was created or
+    /// last cleared.
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().unwrap().stats
+    }
+
+    /// Returns the number of entries in the cache, including cached errors.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Returns true if the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of entries in the cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes every entry from the cache and resets its statistics.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.recency.clear();
+        inner.stats = CacheStats::default();
+    }
+}
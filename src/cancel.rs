@@ -0,0 +1,101 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A handle used to cancel searches that are in progress.
+///
+/// Clones of a token share the same state, so a token can be cloned into
+/// another thread and cancelled from there. A search checks its token
+/// periodically, so it stops shortly after the token is cancelled, and
+/// returns a `Cancelled` error.
+///
+/// Once cancelled, a token stays cancelled.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::{CancelToken, Regex};
+/// # fn main() {
+/// let re = Regex::new(r"\d+").unwrap();
+/// let token = CancelToken::new();
+/// let found: Vec<_> = re.find_iter_cancellable("1 2 3", &token)
+///                       .map(|m| m.unwrap().as_str())
+///                       .collect();
+/// assert_eq!(found, vec!["1", "2", "3"]);
+///
+/// token.cancel();
+/// let err = re.find_cancellable("1 2 3", &token).unwrap_err();
+/// assert_eq!(err.position(), 0);
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Creates a token that isn't cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Cancels every search using this token or one of its clones.
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    #[doc(hidden)]
+    pub fn flag(&self) -> &AtomicBool {
+        &self.flag
+    }
+}
+
+/// The error returned by a search that was cancelled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled {
+    position: usize,
+}
+
+impl Cancelled {
+    #[doc(hidden)]
+    pub fn new(position: usize) -> Cancelled {
+        Cancelled { position: position }
+    }
+
+    /// Returns the byte offset in the text at which the search that was
+    /// cancelled began.
+    ///
+    /// Every match starting before this position was already reported, so
+    /// the work can be resumed by searching the text from here.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Search cancelled at byte offset {}.", self.position)
+    }
+}
+
+impl error::Error for Cancelled {
+    fn description(&self) -> &str {
+        "search cancelled"
+    }
+}
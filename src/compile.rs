@@ -38,6 +38,10 @@ pub enum Inst {
     Save(usize),
     Jump(InstIdx),
     Split(InstIdx, InstIdx),
+    /// When a MatchRule instruction is executed, the current thread has
+    /// matched the rule with the given index. It only appears in programs
+    /// built with `Program::new_rules`.
+    MatchRule(usize),
 }
 
 /// Program represents a compiled regular expression. Once an expression is
@@ -54,11 +58,22 @@ pub struct Program {
     /// match, that prefix is stored here. (It's used in the VM to implement
     /// an optimization.)
     pub prefix: String,
+    /// If set, a match can't end right before a combining mark, so it never
+    /// splits a character from the marks after it.
+    pub whole_clusters: bool,
 }
 
 impl Program {
     /// Compiles a Regex given its AST.
     pub fn new(ast: Expr, size: usize) -> Result<(Program, Vec<Option<String>>), Error> {
+        Program::new_unsimplified(ast.simplify(), size)
+    }
+
+    /// Does the same as `new`, except the AST is compiled as it is instead
+    /// of being simplified first. This is useful for checking that
+    /// simplifying doesn't change what a regex matches.
+    pub fn new_unsimplified(ast: Expr, size: usize)
+                           -> Result<(Program, Vec<Option<String>>), Error> {
         let mut c = Compiler {
             insts: Vec::with_capacity(100),
             names: vec![None],
@@ -85,10 +100,56 @@ impl Program {
         let prog = Program {
             insts: insts,
             prefix: pre,
+            whole_clusters: false,
         };
         Ok((prog, names))
     }
 
+    /// Compiles a program that matches the reverse of every string matched
+    /// by the given AST. The program is meant to be run on text whose
+    /// characters are in reverse order.
+    ///
+    /// Capture groups are kept, but the positions they record are only
+    /// meaningful with respect to the reversed text.
+    pub fn new_reversed(ast: Expr, size: usize)
+                       -> Result<(Program, Vec<Option<String>>), Error> {
+        Program::new(reverse(ast), size)
+    }
+
+    /// Compiles a single program that matches any of the given ASTs, where
+    /// each alternative ends with a `MatchRule` instruction carrying the
+    /// index of its AST.
+    ///
+    /// The program doesn't record the location of the overall match, so it
+    /// is only useful with `vm::run_longest`.
+    pub fn new_rules(asts: Vec<Expr>, size: usize) -> Result<Program, Error> {
+        let mut c = Compiler {
+            insts: Vec::with_capacity(100),
+            names: vec![None],
+            size_limit: size,
+        };
+
+        let n = asts.len();
+        for (i, ast) in asts.into_iter().enumerate() {
+            if i + 1 < n {
+                let split = c.empty_split();
+                try!(c.compile(ast.simplify()));
+                c.push(MatchRule(i));
+                let next = c.insts.len();
+                c.set_split(split, split + 1, next);
+            } else {
+                try!(c.compile(ast.simplify()));
+                c.push(MatchRule(i));
+            }
+            try!(c.check_size());
+        }
+        Ok(Program {
+            insts: c.insts,
+            prefix: String::new(),
+            whole_clusters: false,
+        })
+    }
+
     /// Returns the total number of capture groups in the regular expression.
     /// This includes the zeroth capture.
     pub fn num_captures(&self) -> usize {
@@ -291,3 +352,36 @@ impl Compiler {
         }
     }
 }
+
+/// Reverses an expression, so that it matches exactly the reverse of each
+/// string the original matches.
+///
+/// Concatenations and literals are reversed and assertions about the
+/// beginning of text (or a line) become assertions about its end, and vice
+/// versa. The order of alternations is kept, so earlier alternatives are
+/// still preferred.
+fn reverse(ast: Expr) -> Expr {
+    match ast {
+        Expr::Literal { mut chars, casei } => {
+            chars.reverse();
+            Expr::Literal { chars: chars, casei: casei }
+        }
+        Expr::StartLine => Expr::EndLine,
+        Expr::EndLine => Expr::StartLine,
+        Expr::StartText => Expr::EndText,
+        Expr::EndText => Expr::StartText,
+        Expr::Group { e, i, name } => {
+            Expr::Group { e: Box::new(reverse(*e)), i: i, name: name }
+        }
+        Expr::Repeat { e, r, greedy } => {
+            Expr::Repeat { e: Box::new(reverse(*e)), r: r, greedy: greedy }
+        }
+        Expr::Concat(es) => {
+            Expr::Concat(es.into_iter().rev().map(reverse).collect())
+        }
+        Expr::Alternate(es) => {
+            Expr::Alternate(es.into_iter().map(reverse).collect())
+        }
+        e => e,
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashSet;
+
+use re::{DEFAULT_SIZE_LIMIT, Error, Modes, Regex, expr, from_ast};
+use syntax::{CharClass, Expr};
+
+impl Regex {
+    /// Compiles a regex from a syntax tree built with the `regex-syntax`
+    /// crate.
+    ///
+    /// Capture groups are numbered in the order their opening parentheses
+    /// appear, regardless of the indices in the tree. The tree is compiled
+    /// as it is, and `as_str` returns a pattern written from it, which
+    /// compiles to the same regex.
+    ///
+    /// An error is returned if two capture groups have the same name, or if
+    /// the result exceeds the default size limit.
+    pub fn from_expr(expr: Expr) -> Result<Regex, Error> {
+        let mut names = HashSet::new();
+        try!(check_names(&expr, &mut names));
+        let expr = number_groups(group_operands(expr), &mut 1);
+        let pattern = expr.to_string();
+        // The spans of the groups are only known by parsing the pattern.
+        // If a tree built by hand can't be written as a pattern that parses,
+        // then the regex still works, but has no spans.
+        let spans = Expr::parse_with_spans(&pattern)
+                         .map(|(_, spans)| spans).unwrap_or(vec![]);
+        from_ast(&pattern, expr, spans, Modes::default(),
+                 DEFAULT_SIZE_LIMIT)
+    }
+
+    /// Returns a regex that matches if any of the given regexes match.
+    ///
+    /// Alternatives are tried in order, so earlier regexes are preferred
+    /// (as with `|`). The flags of each regex only apply to it, and its
+    /// capture groups are renumbered to follow the groups of the regexes
+    /// before it. The union of no regexes never matches.
+    ///
+    /// An error is returned if two capture groups have the same name, or if
+    /// the result exceeds the default size limit.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let word = Regex::new(r"(?i)(?P<word>hello)").unwrap();
+    /// let num = Regex::new(r"(?P<num>\d+)").unwrap();
+    /// let re = Regex::union(&[&word, &num]).unwrap();
+    /// assert_eq!(re.captures_len(), 3);
+    /// assert_eq!(re.captures("HELLO").unwrap().name("word"), Some("HELLO"));
+    /// assert_eq!(re.captures("x42").unwrap().name("num"), Some("42"));
+    /// # }
+    /// ```
+    pub fn union(res: &[&Regex]) -> Result<Regex, Error> {
+        if res.is_empty() {
+            // A class without any characters can't match.
+            return Regex::from_expr(Expr::Class(CharClass::empty()))
+        }
+        Regex::from_expr(Expr::Alternate(operands(res)))
+    }
+
+    /// Returns a regex that matches the concatenation of the given regexes.
+    ///
+    /// The flags of each regex only apply to it, and its capture groups are
+    /// renumbered to follow the groups of the regexes before it. The
+    /// concatenation of no regexes matches the empty string.
+    ///
+    /// An error is returned if two capture groups have the same name, or if
+    /// the result exceeds the default size limit.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let key = Regex::new(r"(?i)(\w+)").unwrap();
+    /// let sep = Regex::new(r"\s*=\s*|:").unwrap();
+    /// let val = Regex::new(r"(\d+)").unwrap();
+    /// let re = Regex::concat(&[&key, &sep, &val]).unwrap();
+    /// let caps = re.captures("Width = 80").unwrap();
+    /// assert_eq!((caps.at(1), caps.at(2)), (Some("Width"), Some("80")));
+    /// # }
+    /// ```
+    pub fn concat(res: &[&Regex]) -> Result<Regex, Error> {
+        Regex::from_expr(Expr::Concat(operands(res)))
+    }
+}
+
+/// Wraps the expression of each regex in a non-capturing group, so that it
+/// can be combined with others without changing its meaning.
+fn operands(res: &[&Regex]) -> Vec<Expr> {
+    res.iter().map(|re| {
+        Expr::Group { e: Box::new(expr(re)), i: None, name: None }
+    }).collect()
+}
+
+/// Numbers the capture groups from `next` on, in the order their opening
+/// parentheses appear when the tree is written as a pattern.
+fn number_groups(expr: Expr, next: &mut usize) -> Expr {
+    match expr {
+        Expr::Group { e, i: Some(_), name } => {
+            let i = *next;
+            *next += 1;
+            let e = number_groups(*e, next);
+            Expr::Group { e: Box::new(e), i: Some(i), name: name }
+        }
+        Expr::Group { e, i: None, name } => {
+            let e = number_groups(*e, next);
+            Expr::Group { e: Box::new(e), i: None, name: name }
+        }
+        Expr::Repeat { e, r, greedy } => {
+            let e = number_groups(*e, next);
+            Expr::Repeat { e: Box::new(e), r: r, greedy: greedy }
+        }
+        Expr::Concat(es) => {
+            Expr::Concat(es.into_iter().map(|e| number_groups(e, next))
+                                       .collect())
+        }
+        Expr::Alternate(es) => {
+            Expr::Alternate(es.into_iter().map(|e| number_groups(e, next))
+                                          .collect())
+        }
+        e => e,
+    }
+}
+
+/// Returns an error for the first capture group name that was already seen.
+fn check_names(expr: &Expr, names: &mut HashSet<String>) -> Result<(), Error> {
+    match *expr {
+        Expr::Group { ref e, ref name, .. } => {
+            if let Some(ref name) = *name {
+                if !names.insert(name.clone()) {
+                    return Err(Error::DuplicateGroupName(name.clone()));
+                }
+            }
+            check_names(e, names)
+        }
+        Expr::Repeat { ref e, .. } => check_names(e, names),
+        Expr::Concat(ref es) | Expr::Alternate(ref es) => {
+            for e in es {
+                try!(check_names(e, names));
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Adds non-capturing groups wherever they're needed for the tree to be
+/// written as a pattern with the same meaning.
+///
+/// The parser never produces a tree that needs them, but a tree built by
+/// hand might, e.g., an alternation inside a concatenation.
+fn group_operands(expr: Expr) -> Expr {
+    fn group(e: Expr) -> Expr {
+        Expr::Group { e: Box::new(e), i: None, name: None }
+    }
+    match expr {
+        Expr::Group { e, i, name } => {
+            Expr::Group { e: Box::new(group_operands(*e)), i: i, name: name }
+        }
+        Expr::Repeat { e, r, greedy } => {
+            let e = match group_operands(*e) {
+                e @ Expr::Literal { .. } | e @ Expr::AnyChar
+                | e @ Expr::AnyCharNoNL | e @ Expr::Class(_)
+                | e @ Expr::Group { .. } => e,
+                e => group(e),
+            };
+            Expr::Repeat { e: Box::new(e), r: r, greedy: greedy }
+        }
+        Expr::Concat(es) => {
+            Expr::Concat(es.into_iter().map(|e| {
+                match group_operands(e) {
+                    e @ Expr::Alternate(_) => group(e),
+                    e => e,
+                }
+            }).collect())
+        }
+        Expr::Alternate(es) => {
+            Expr::Alternate(es.into_iter().map(group_operands).collect())
+        }
+        e => e,
+    }
+}
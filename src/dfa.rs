@@ -0,0 +1,479 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// The checks in this module determinize programs lazily with the subset
+// construction, and explore the product of the resulting DFAs breadth first.
+// Since the search is breadth first, every witness is as short as possible.
+//
+// A DFA state is the set of threads waiting to read the next character along
+// with a summary of the previous character. Zero-width assertions can only
+// be evaluated once the next character is known, so threads are followed
+// through empty instructions at the start of each transition rather than at
+// its end.
+//
+// The alphabet is partitioned into blocks of characters that no instruction
+// of any program can tell apart, and each block is represented by a single
+// character.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::char;
+
+use compile::{Inst, Program};
+use re::{Error, Regex, program};
+use syntax::{self, Expr};
+
+/// The default maximum number of states explored by a `LanguageChecker`.
+pub const DEFAULT_STATE_LIMIT: usize = 10_000;
+
+/// Compares the languages of regexes.
+///
+/// The language of a regex is the set of strings it matches in full, i.e.,
+/// the strings for which `is_full_match` returns true. (To compare what
+/// regexes find when searching, surround their patterns with `(?s:.*)`.)
+///
+/// Every check explores the product of the DFAs of the regexes, which can
+/// be exponentially large. The number of states explored is therefore
+/// limited, and `Error::TooManyStates` is returned if the limit is reached.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::{LanguageChecker, Regex};
+/// # fn main() {
+/// let checker = LanguageChecker::new();
+/// let port = Regex::new(r"[0-9]{1,5}").unwrap();
+/// let https = Regex::new(r"443|8443").unwrap();
+///
+/// assert!(checker.is_subset(&https, &port).unwrap().holds());
+/// let v = checker.is_subset(&port, &https).unwrap();
+/// assert!(!v.holds());
+/// assert_eq!(v.witness(), Some("0"));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LanguageChecker {
+    state_limit: usize,
+}
+
+/// The answer to a question asked of a `LanguageChecker`, along with a
+/// string that demonstrates it, if there is one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verdict {
+    holds: bool,
+    witness: Option<String>,
+}
+
+impl Verdict {
+    /// Returns true if the property checked holds.
+    pub fn holds(&self) -> bool {
+        self.holds
+    }
+
+    /// Returns a shortest string that demonstrates the answer.
+    ///
+    /// For `is_subset`, `is_equivalent` and `is_empty`, this is a
+    /// counterexample that's only present if the property doesn't hold. For
+    /// `intersects`, it's a string matched by both regexes, which is only
+    /// present if the property holds.
+    pub fn witness(&self) -> Option<&str> {
+        self.witness.as_ref().map(|s| &**s)
+    }
+}
+
+impl LanguageChecker {
+    /// Creates a checker that explores at most `DEFAULT_STATE_LIMIT`
+    /// states.
+    pub fn new() -> LanguageChecker {
+        LanguageChecker { state_limit: DEFAULT_STATE_LIMIT }
+    }
+
+    /// Sets the maximum number of states explored by each check.
+    pub fn state_limit(mut self, limit: usize) -> LanguageChecker {
+        self.state_limit = limit;
+        self
+    }
+
+    /// Checks whether every string matched by `a` is also matched by `b`.
+    ///
+    /// If not, then the witness is a string matched by `a` but not by `b`.
+    pub fn is_subset(&self, a: &Regex, b: &Regex) -> Result<Verdict, Error> {
+        let found = try!(self.search(&[a, b], Goal::Difference));
+        Ok(Verdict { holds: found.is_none(), witness: found })
+    }
+
+    /// Checks whether some string is matched by both `a` and `b`.
+    ///
+    /// If so, then the witness is such a string.
+    pub fn intersects(&self, a: &Regex, b: &Regex) -> Result<Verdict, Error> {
+        let found = try!(self.search(&[a, b], Goal::Intersection));
+        Ok(Verdict { holds: found.is_some(), witness: found })
+    }
+
+    /// Checks whether `a` and `b` match exactly the same strings.
+    ///
+    /// If not, then the witness is a string matched by only one of them.
+    pub fn is_equivalent(&self, a: &Regex, b: &Regex)
+                        -> Result<Verdict, Error> {
+        let found = try!(self.search(&[a, b], Goal::SymmetricDifference));
+        Ok(Verdict { holds: found.is_none(), witness: found })
+    }
+
+    /// Checks whether `a` doesn't match any string at all.
+    ///
+    /// If it does, then the witness is a string it matches.
+    pub fn is_empty(&self, a: &Regex) -> Result<Verdict, Error> {
+        let found = try!(self.search(&[a], Goal::Any));
+        Ok(Verdict { holds: found.is_none(), witness: found })
+    }
+
+    /// Returns a shortest string for which the acceptance of each regex
+    /// satisfies `goal`, if there is one.
+    fn search(&self, res: &[&Regex], goal: Goal)
+             -> Result<Option<String>, Error> {
+        let progs: Vec<_> = res.iter().map(|re| program(re)).collect();
+        let progs: Vec<&Program> = progs.iter().map(|p| &**p).collect();
+        let alphabet = alphabet(&progs);
+        let mut dfas: Vec<Dfa> = progs.iter().map(|&p| Dfa::new(p)).collect();
+
+        // Every explored state of the product, along with the state it was
+        // reached from and the character read to reach it.
+        let mut seen: HashMap<Vec<usize>, usize> = HashMap::new();
+        let mut parents: Vec<Option<(usize, char)>> = vec![];
+        let mut queue = VecDeque::new();
+
+        let start = vec![0; dfas.len()];
+        seen.insert(start.clone(), 0);
+        parents.push(None);
+        queue.push_back((start, 0));
+        while let Some((states, id)) = queue.pop_front() {
+            let accepts: Vec<bool> = dfas.iter_mut().zip(states.iter())
+                                         .map(|(d, &s)| d.accepts(s))
+                                         .collect();
+            if goal.is_met(&accepts) {
+                return Ok(Some(witness(&parents, id)))
+            }
+            let dead: Vec<bool> = dfas.iter().zip(states.iter())
+                                      .map(|(d, &s)| d.is_dead(s))
+                                      .collect();
+            if goal.is_unreachable(&dead) {
+                continue
+            }
+            for (block, &c) in alphabet.iter().enumerate() {
+                let next: Vec<usize> = dfas.iter_mut().zip(states.iter())
+                                           .map(|(d, &s)| d.next(s, block, c))
+                                           .collect();
+                if seen.contains_key(&next) {
+                    continue
+                }
+                if seen.len() >= self.state_limit {
+                    return Err(Error::TooManyStates(self.state_limit))
+                }
+                let next_id = parents.len();
+                seen.insert(next.clone(), next_id);
+                parents.push(Some((id, c)));
+                queue.push_back((next, next_id));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Builds the string read to reach the product state `id`.
+fn witness(parents: &[Option<(usize, char)>], mut id: usize) -> String {
+    let mut chars = vec![];
+    while let Some((parent, c)) = parents[id] {
+        chars.push(c);
+        id = parent;
+    }
+    chars.into_iter().rev().collect()
+}
+
+/// The acceptance of the regexes that a search is looking for.
+#[derive(Clone, Copy)]
+enum Goal {
+    /// Accepted by the first but not the second.
+    Difference,
+    /// Accepted by both.
+    Intersection,
+    /// Accepted by exactly one of the two.
+    SymmetricDifference,
+    /// Accepted by the only regex.
+    Any,
+}
+
+impl Goal {
+    fn is_met(self, accepts: &[bool]) -> bool {
+        match self {
+            Goal::Difference => accepts[0] && !accepts[1],
+            Goal::Intersection => accepts[0] && accepts[1],
+            Goal::SymmetricDifference => accepts[0] != accepts[1],
+            Goal::Any => accepts[0],
+        }
+    }
+
+    /// Returns true if the goal can't be met after any more characters,
+    /// given which DFAs can never accept again.
+    fn is_unreachable(self, dead: &[bool]) -> bool {
+        match self {
+            Goal::Difference | Goal::Any => dead[0],
+            Goal::Intersection => dead[0] || dead[1],
+            Goal::SymmetricDifference => dead[0] && dead[1],
+        }
+    }
+}
+
+/// A summary of the character before the current position, with everything
+/// assertions need to know about it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Prev {
+    /// The current position is the beginning of the text.
+    Start,
+    Char { word: bool, newline: bool },
+}
+
+impl Prev {
+    fn of(c: char) -> Prev {
+        Prev::Char { word: syntax::is_word_char(c), newline: c == '\n' }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct DState {
+    // The threads waiting to read the next character, sorted.
+    pcs: Vec<usize>,
+    prev: Prev,
+}
+
+/// A DFA built lazily from a program.
+struct Dfa<'p> {
+    prog: &'p Program,
+    states: Vec<DState>,
+    index: HashMap<DState, usize>,
+    // Whether each state accepts at the end of the text, once computed.
+    accepts: Vec<Option<bool>>,
+    // Transitions computed so far, keyed by state and alphabet block.
+    trans: HashMap<(usize, usize), usize>,
+}
+
+impl<'p> Dfa<'p> {
+    fn new(prog: &'p Program) -> Dfa<'p> {
+        let mut dfa = Dfa {
+            prog: prog,
+            states: vec![],
+            index: HashMap::new(),
+            accepts: vec![],
+            trans: HashMap::new(),
+        };
+        dfa.intern(DState { pcs: vec![0], prev: Prev::Start });
+        dfa
+    }
+
+    fn intern(&mut self, state: DState) -> usize {
+        if let Some(&i) = self.index.get(&state) {
+            return i
+        }
+        let i = self.states.len();
+        self.states.push(state.clone());
+        self.index.insert(state, i);
+        self.accepts.push(None);
+        i
+    }
+
+    fn is_dead(&self, s: usize) -> bool {
+        self.states[s].pcs.is_empty()
+    }
+
+    fn accepts(&mut self, s: usize) -> bool {
+        if let Some(yes) = self.accepts[s] {
+            return yes
+        }
+        let (_, matched) = self.closure(s, None);
+        self.accepts[s] = Some(matched);
+        matched
+    }
+
+    /// Returns the state reached from `s` by reading `c`, which represents
+    /// the alphabet block `block`.
+    fn next(&mut self, s: usize, block: usize, c: char) -> usize {
+        if let Some(&t) = self.trans.get(&(s, block)) {
+            return t
+        }
+        let (consuming, _) = self.closure(s, Some(c));
+        let mut pcs: Vec<usize> = consuming.into_iter().filter(|&pc| {
+            match self.prog.insts[pc] {
+                Inst::OneChar { c: rc, casei: false } => rc == c,
+                Inst::OneChar { c: rc, casei: true } => {
+                    syntax::simple_case_fold(rc) == syntax::simple_case_fold(c)
+                }
+                Inst::CharClass(ref cls) => cls.matches(c),
+                Inst::Any => true,
+                Inst::AnyNoNL => c != '\n',
+                _ => false,
+            }
+        }).map(|pc| pc + 1).collect();
+        pcs.sort();
+        pcs.dedup();
+        let t = self.intern(DState { pcs: pcs, prev: Prev::of(c) });
+        self.trans.insert((s, block), t);
+        t
+    }
+
+    /// Follows the threads of state `s` through empty instructions, given
+    /// the next character (or `None` at the end of the text). Returns the
+    /// instructions that consume a character and whether a `Match` was
+    /// reached.
+    fn closure(&self, s: usize, next: Option<char>) -> (Vec<usize>, bool) {
+        let prev = self.states[s].prev;
+        let is_begin = prev == Prev::Start;
+        let prev_word = match prev {
+            Prev::Start => false,
+            Prev::Char { word, .. } => word,
+        };
+        let prev_newline = match prev {
+            Prev::Start => false,
+            Prev::Char { newline, .. } => newline,
+        };
+        let next_word = next.map_or(false, syntax::is_word_char);
+
+        let mut seen = vec![false; self.prog.insts.len()];
+        let mut stack: Vec<usize> =
+            self.states[s].pcs.iter().rev().cloned().collect();
+        let mut consuming = vec![];
+        let mut matched = false;
+        while let Some(pc) = stack.pop() {
+            if seen[pc] {
+                continue
+            }
+            seen[pc] = true;
+            let pass = match self.prog.insts[pc] {
+                Inst::Match | Inst::MatchRule(_) => {
+                    matched = matched || next.is_none();
+                    false
+                }
+                Inst::OneChar { .. } | Inst::CharClass(_)
+                | Inst::Any | Inst::AnyNoNL => {
+                    consuming.push(pc);
+                    false
+                }
+                Inst::StartLine => is_begin || prev_newline,
+                Inst::EndLine => next.map_or(true, |c| c == '\n'),
+                Inst::StartText => is_begin,
+                Inst::EndText => next.is_none(),
+                Inst::WordBoundary => prev_word != next_word,
+                Inst::NotWordBoundary => prev_word == next_word,
+                Inst::Save(_) => true,
+                Inst::Jump(to) => {
+                    stack.push(to);
+                    false
+                }
+                Inst::Split(x, y) => {
+                    stack.push(y);
+                    stack.push(x);
+                    false
+                }
+            };
+            if pass {
+                stack.push(pc + 1);
+            }
+        }
+        (consuming, matched)
+    }
+}
+
+/// Partitions the alphabet into blocks of characters that no instruction of
+/// the programs can tell apart, and returns a character from each block.
+fn alphabet(progs: &[&Program]) -> Vec<char> {
+    let mut bounds = BTreeSet::new();
+    {
+        let mut add = |start: char, end: char| {
+            bounds.insert(start as u32);
+            bounds.insert(end as u32 + 1);
+        };
+        for prog in progs {
+            for inst in &prog.insts {
+                match *inst {
+                    Inst::OneChar { c, casei } => {
+                        add(c, c);
+                        if casei {
+                            let folded = syntax::simple_case_fold(c);
+                            add(folded, folded);
+                            for &(x, y) in case_folds() {
+                                if x == folded || y == folded {
+                                    add(x, x);
+                                    add(y, y);
+                                }
+                            }
+                        }
+                    }
+                    Inst::CharClass(ref cls) => {
+                        for r in cls.iter() {
+                            add(r.start, r.end);
+                        }
+                        if cls.is_case_insensitive() {
+                            // A character that folds into the class (or out
+                            // of it) must be in a block of its own.
+                            for &(x, _) in case_folds() {
+                                let raw = cls.iter().any(|r| {
+                                    r.start <= x && x <= r.end
+                                });
+                                if raw || cls.matches(x) {
+                                    add(x, x);
+                                }
+                            }
+                        }
+                    }
+                    Inst::AnyNoNL | Inst::StartLine | Inst::EndLine => {
+                        add('\n', '\n');
+                    }
+                    Inst::WordBoundary | Inst::NotWordBoundary => {
+                        for r in word_class().iter() {
+                            add(r.start, r.end);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    // Surrogates aren't characters.
+    bounds.insert(0);
+    bounds.insert(0xD800);
+    bounds.insert(0xE000);
+    bounds.insert(0x110000);
+
+    let bounds: Vec<u32> = bounds.into_iter().collect();
+    let mut reps = vec![];
+    for i in 0..bounds.len() - 1 {
+        let (start, end) = (bounds[i], bounds[i + 1]);
+        if start == 0xD800 || start >= 0x110000 {
+            continue
+        }
+        // Prefer a readable character for witnesses.
+        let rep = ['a', '0', 'A', ' ', '\n'].iter().cloned()
+            .find(|&c| start <= c as u32 && (c as u32) < end)
+            .unwrap_or_else(|| char::from_u32(start).unwrap());
+        reps.push(rep);
+    }
+    // The first block that leads to a witness is used, so try lowercase
+    // letters first.
+    reps.sort_by_key(|&c| (!c.is_lowercase(), c));
+    reps
+}
+
+fn case_folds() -> &'static [(char, char)] {
+    syntax::simple_case_folding_table()
+}
+
+fn word_class() -> syntax::CharClass {
+    match Expr::parse(r"\w") {
+        Ok(Expr::Class(cls)) => cls,
+        _ => unreachable!(),
+    }
+}
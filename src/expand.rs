@@ -0,0 +1,268 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::borrow::Cow;
+
+use re::{Captures, Error, Regex, Replacer};
+
+/// A replacement string that has been parsed ahead of time.
+///
+/// A template may contain references to capture groups, which are expanded
+/// for each match. The following forms are recognized:
+///
+/// * `$N` refers to the capture group with index `N`.
+/// * `$name` refers to the capture group named `name`.
+/// * `${N}` and `${name}` are the same as above, but the reference ends at
+///   the closing brace. This makes it possible to write `${1}0` or
+///   `${name}suffix`.
+/// * `$$` is a literal `$`.
+///
+/// Without braces, a reference is either the longest sequence of digits or
+/// the longest sequence of letters, digits and underscores starting with a
+/// letter or underscore. A `$` that doesn't start a valid reference is kept
+/// as is.
+///
+/// Parsing a template once and reusing it avoids scanning the replacement
+/// string for every match.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::{Regex, ReplacementTemplate};
+/// # fn main() {
+/// let re = Regex::new(r"(?P<num>\d+)").unwrap();
+/// let tpl = ReplacementTemplate::new("${num}0");
+/// assert!(tpl.validate(&re).is_ok());
+/// assert_eq!(re.replace_all("1 and 2", &tpl), "10 and 20");
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplacementTemplate {
+    pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Index(usize),
+    Name(String),
+}
+
+impl ReplacementTemplate {
+    /// Parses a replacement template.
+    ///
+    /// Parsing never fails. References to capture groups are checked against
+    /// a particular regex with `validate`.
+    pub fn new(template: &str) -> ReplacementTemplate {
+        let mut pieces = vec![];
+        let mut lit = String::new();
+        parse(template, |part| {
+            match part {
+                Part::Literal(s) => lit.push_str(s),
+                Part::Ref(r) => {
+                    if !lit.is_empty() {
+                        pieces.push(Piece::Literal(lit.clone()));
+                        lit.clear();
+                    }
+                    pieces.push(match r {
+                        Ref::Index(i) => Piece::Index(i),
+                        Ref::Name(name) => Piece::Name(name.to_string()),
+                    });
+                }
+            }
+        });
+        if !lit.is_empty() {
+            pieces.push(Piece::Literal(lit));
+        }
+        ReplacementTemplate { pieces: pieces }
+    }
+
+    /// Checks that every capture group referenced by this template exists
+    /// in `re`.
+    ///
+    /// If a reference doesn't correspond to any capture group, then an
+    /// `Error::UnknownGroup` is returned for the first such reference.
+    pub fn validate(&self, re: &Regex) -> Result<(), Error> {
+        let names: Vec<Option<String>> = re.names_iter().collect();
+        for piece in &self.pieces {
+            match *piece {
+                Piece::Literal(_) => {}
+                Piece::Index(i) => {
+                    if i >= names.len() {
+                        return Err(Error::UnknownGroup(i.to_string()));
+                    }
+                }
+                Piece::Name(ref name) => {
+                    let found = names.iter().any(|n| {
+                        n.as_ref().map_or(false, |n| n == name)
+                    });
+                    if !found {
+                        return Err(Error::UnknownGroup(name.clone()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the text of this template if it doesn't refer to any capture
+    /// groups.
+    fn literal(&self) -> Option<&str> {
+        if self.pieces.is_empty() {
+            return Some("");
+        }
+        match self.pieces[0] {
+            Piece::Literal(ref s) if self.pieces.len() == 1 => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Expands this template with the capture groups in `caps` and appends
+    /// the result to `dst`.
+    ///
+    /// References to capture groups that don't exist or didn't participate
+    /// in the match are replaced with the empty string.
+    pub fn expand(&self, caps: &Captures, dst: &mut String) {
+        for piece in &self.pieces {
+            match *piece {
+                Piece::Literal(ref s) => dst.push_str(s),
+                Piece::Index(i) => dst.push_str(caps.at(i).unwrap_or("")),
+                Piece::Name(ref name) => {
+                    dst.push_str(caps.name(name).unwrap_or(""))
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Replacer for &'a ReplacementTemplate {
+    fn reg_replace<'r>(&'r mut self, caps: &Captures) -> Cow<'r, str> {
+        let mut dst = String::new();
+        self.expand(caps, &mut dst);
+        Cow::Owned(dst)
+    }
+
+    fn no_expansion<'r>(&'r mut self) -> Option<Cow<'r, str>> {
+        self.literal().map(Cow::Borrowed)
+    }
+}
+
+impl Replacer for ReplacementTemplate {
+    fn reg_replace<'r>(&'r mut self, caps: &Captures) -> Cow<'r, str> {
+        let mut dst = String::new();
+        self.expand(caps, &mut dst);
+        Cow::Owned(dst)
+    }
+
+    fn no_expansion<'r>(&'r mut self) -> Option<Cow<'r, str>> {
+        self.literal().map(Cow::Borrowed)
+    }
+}
+
+/// Expands `template` with the capture groups in `caps` and appends the
+/// result to `dst`, without building a `ReplacementTemplate` first.
+pub fn expand_str(caps: &Captures, template: &str, dst: &mut String) {
+    parse(template, |part| {
+        let text = match part {
+            Part::Literal(s) => Some(s),
+            Part::Ref(Ref::Index(i)) => caps.at(i),
+            Part::Ref(Ref::Name(name)) => caps.name(name),
+        };
+        dst.push_str(text.unwrap_or(""));
+    });
+}
+
+/// A piece of a template as it's parsed: either literal text (with `$$`
+/// already unescaped) or a reference to a capture group.
+enum Part<'a> {
+    Literal(&'a str),
+    Ref(Ref<'a>),
+}
+
+/// Splits `template` into literal text and references to capture groups and
+/// calls `f` on each part, in order.
+fn parse<'a, F>(template: &'a str, mut f: F) where F: FnMut(Part<'a>) {
+    let mut rest = template;
+    while let Some(i) = rest.find('$') {
+        f(Part::Literal(&rest[..i]));
+        rest = &rest[i + 1..];
+        if rest.starts_with('$') {
+            f(Part::Literal("$"));
+            rest = &rest[1..];
+            continue;
+        }
+        match find_ref(rest) {
+            None => f(Part::Literal("$")),
+            Some((r, len)) => {
+                rest = &rest[len..];
+                f(Part::Ref(r));
+            }
+        }
+    }
+    f(Part::Literal(rest));
+}
+
+/// A reference to a capture group in a template.
+enum Ref<'a> {
+    Index(usize),
+    Name(&'a str),
+}
+
+impl<'a> Ref<'a> {
+    fn new(name: &'a str) -> Ref<'a> {
+        match name.parse::<usize>() {
+            Ok(i) => Ref::Index(i),
+            Err(_) => Ref::Name(name),
+        }
+    }
+}
+
+/// Parses a capture group reference at the beginning of `rep`, which is the
+/// text immediately following a `$`. On success, the reference is returned
+/// along with the number of bytes of `rep` it occupies.
+fn find_ref<'a>(rep: &'a str) -> Option<(Ref<'a>, usize)> {
+    let bytes = rep.as_bytes();
+    if bytes.first() == Some(&b'{') {
+        let end = match rep.find('}') {
+            None => return None,
+            Some(end) => end,
+        };
+        let name = &rep[1..end];
+        if name.is_empty() || !name.bytes().all(is_name_byte) {
+            return None;
+        }
+        return Some((Ref::new(name), end + 1));
+    }
+    let len = match bytes.first() {
+        Some(&b) if is_digit_byte(b) => {
+            bytes.iter().take_while(|&&b| is_digit_byte(b)).count()
+        }
+        Some(&b) if is_name_byte(b) => {
+            bytes.iter().take_while(|&&b| is_name_byte(b)).count()
+        }
+        _ => 0,
+    };
+    if len == 0 {
+        None
+    } else {
+        Some((Ref::new(&rep[..len]), len))
+    }
+}
+
+fn is_digit_byte(b: u8) -> bool {
+    b'0' <= b && b <= b'9'
+}
+
+fn is_name_byte(b: u8) -> bool {
+    is_digit_byte(b)
+    || (b'a' <= b && b <= b'z')
+    || (b'A' <= b && b <= b'Z')
+    || b == b'_'
+}
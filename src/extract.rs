@@ -0,0 +1,215 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use re::{Captures, FindCaptures, Regex};
+
+/// A type that can be built from the capture groups of a match.
+///
+/// This is usually implemented with `#[derive(FromCaptures)]`, which is
+/// available with the `derive` Cargo feature enabled. The derived
+/// implementation maps each named field to the capture group with the same
+/// name and parses the group's text with `FromStr`. A field of type
+/// `Option<T>` is `None` when its group didn't participate in the match.
+///
+/// The derive also accepts a `#[regex(r"...")]` attribute on the struct. If
+/// it's given, the pattern is parsed at compile time, every field is checked
+/// against its capture groups, and `pattern` returns it.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex;
+/// use regex::{Captures, CaptureError, FromCaptures, Regex};
+///
+/// struct Addr { host: String, port: u16 }
+///
+/// impl FromCaptures for Addr {
+///     fn from_captures(caps: &Captures) -> Result<Addr, CaptureError> {
+///         Ok(Addr {
+///             host: try!(caps.parse_name("host")),
+///             port: try!(caps.parse_name("port")),
+///         })
+///     }
+/// }
+///
+/// # fn main() {
+/// let re = Regex::new(r"(?P<host>[a-z.]+):(?P<port>\d+)").unwrap();
+/// let addr: Addr = re.captures_into("at example.com:80").unwrap().unwrap();
+/// assert_eq!(addr.host, "example.com");
+/// assert_eq!(addr.port, 80);
+///
+/// let err = re.captures_into::<Addr>("example.com:99999").unwrap();
+/// assert_eq!(err.err().unwrap().field(), "port");
+/// # }
+/// ```
+pub trait FromCaptures: Sized {
+    /// Builds a value from the capture groups of a single match.
+    fn from_captures(caps: &Captures) -> Result<Self, CaptureError>;
+
+    /// Returns the pattern whose matches this type is built from, if it has
+    /// one. By default, `None` is returned.
+    fn pattern() -> Option<&'static str> {
+        None
+    }
+}
+
+/// An error that occurred while converting a capture group to a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureError {
+    field: String,
+    kind: CaptureErrorKind,
+}
+
+/// The specific kind of error that occurred while converting a capture
+/// group to a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaptureErrorKind {
+    /// The regex has no capture group with the field's name.
+    NoGroup,
+    /// The group didn't participate in the match, but its field isn't
+    /// optional.
+    Missing,
+    /// The text of the group couldn't be parsed.
+    Parse {
+        /// The text of the group.
+        text: String,
+        /// The message of the error returned by `FromStr`.
+        message: String,
+    },
+}
+
+impl CaptureError {
+    /// Creates an error for the field (or capture group) named `field`.
+    pub fn new(field: &str, kind: CaptureErrorKind) -> CaptureError {
+        CaptureError { field: field.to_string(), kind: kind }
+    }
+
+    /// Returns the name of the field that couldn't be converted.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> &CaptureErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            CaptureErrorKind::NoGroup => {
+                write!(f, "No capture group named '{}'.", self.field)
+            }
+            CaptureErrorKind::Missing => {
+                write!(f, "Capture group '{}' did not participate in the \
+                           match.", self.field)
+            }
+            CaptureErrorKind::Parse { ref text, ref message } => {
+                write!(f, "Could not parse capture group '{}' from '{}': {}",
+                       self.field, text, message)
+            }
+        }
+    }
+}
+
+impl error::Error for CaptureError {
+    fn description(&self) -> &str {
+        match self.kind {
+            CaptureErrorKind::NoGroup => "no such capture group",
+            CaptureErrorKind::Missing => "capture group did not match",
+            CaptureErrorKind::Parse { .. } => "invalid capture group text",
+        }
+    }
+}
+
+impl Regex {
+    /// Returns the leftmost-first match in `text` converted to a `T`, or
+    /// `None` if there is no match.
+    ///
+    /// The conversion is done by `T::from_captures`. See `FromCaptures` for
+    /// an example.
+    pub fn captures_into<T>(&self, text: &str)
+                           -> Option<Result<T, CaptureError>>
+            where T: FromCaptures {
+        self.captures(text).map(|caps| T::from_captures(&caps))
+    }
+
+    /// Returns an iterator over all the non-overlapping matches in `text`,
+    /// each converted to a `T`.
+    ///
+    /// This is operationally the same as `captures_iter`, except it yields
+    /// the result of `T::from_captures` for every match.
+    pub fn captures_iter_into<'r, 't, T>(&'r self, text: &'t str)
+                                        -> CapturesInto<'r, 't, T>
+            where T: FromCaptures {
+        CapturesInto { it: self.captures_iter(text), _marker: PhantomData }
+    }
+}
+
+impl<'t> Captures<'t> {
+    /// Parses the text of the capture group named `name` with `FromStr`.
+    ///
+    /// An error is returned if there is no such group, if the group didn't
+    /// participate in the match or if its text can't be parsed.
+    pub fn parse_name<T>(&self, name: &str) -> Result<T, CaptureError>
+            where T: FromStr, T::Err: fmt::Display {
+        match try!(self.parse_name_opt(name)) {
+            None => Err(CaptureError::new(name, CaptureErrorKind::Missing)),
+            Some(v) => Ok(v),
+        }
+    }
+
+    /// Parses the text of the capture group named `name` with `FromStr`, or
+    /// returns `None` if the group didn't participate in the match.
+    ///
+    /// An error is returned if there is no such group or if its text can't
+    /// be parsed.
+    pub fn parse_name_opt<T>(&self, name: &str)
+                            -> Result<Option<T>, CaptureError>
+            where T: FromStr, T::Err: fmt::Display {
+        if !self.has_name(name) {
+            return Err(CaptureError::new(name, CaptureErrorKind::NoGroup));
+        }
+        let text = match self.name(name) {
+            None => return Ok(None),
+            Some(text) => text,
+        };
+        text.parse().map(Some).map_err(|err: T::Err| {
+            CaptureError::new(name, CaptureErrorKind::Parse {
+                text: text.to_string(),
+                message: err.to_string(),
+            })
+        })
+    }
+}
+
+/// An iterator that converts the capture groups of every non-overlapping
+/// match to a value of type `T`.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct CapturesInto<'r, 't, T> {
+    it: FindCaptures<'r, 't>,
+    _marker: PhantomData<T>,
+}
+
+impl<'r, 't, T: FromCaptures> Iterator for CapturesInto<'r, 't, T> {
+    type Item = Result<T, CaptureError>;
+
+    fn next(&mut self) -> Option<Result<T, CaptureError>> {
+        self.it.next().map(|caps| T::from_captures(&caps))
+    }
+}
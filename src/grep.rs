@@ -0,0 +1,453 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::ops::Range;
+use std::vec;
+
+use compile::Inst;
+use re::{self, FindMatches, Regex};
+
+/// Searches text line by line, like `grep`.
+///
+/// A line is selected if the regex matches somewhere in it (or, when
+/// inverted, if it doesn't). Selected lines are reported along with the
+/// requested number of context lines before and after them. Lines are
+/// terminated by `\n`, which isn't part of the line's text.
+///
+/// If the regex can't match `\n`, then each line is searched on its own, so
+/// `^` and `$` match at the beginning and end of every line. Otherwise, the
+/// entire text is searched as a whole and a line is selected if any match
+/// overlaps it. Matches are still found as the lines are reported, so the
+/// search doesn't run ahead of the line being reported by more than one
+/// match.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::{LineSearcher, Regex};
+/// # fn main() {
+/// let re = Regex::new(r"\d+").unwrap();
+/// let text = "one\ntwo 2\nthree\nfour 44\n";
+/// let lines: Vec<(usize, String)> = LineSearcher::new(&re)
+///     .search(text)
+///     .map(|line| (line.number(), line.as_str().to_string()))
+///     .collect();
+/// assert_eq!(lines, vec![
+///     (2, "two 2".to_string()), (4, "four 44".to_string()),
+/// ]);
+/// assert_eq!(LineSearcher::new(&re).invert(true).count(text), 2);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LineSearcher<'r> {
+    re: &'r Regex,
+    multi_line: bool,
+    invert: bool,
+    before: usize,
+    after: usize,
+}
+
+impl<'r> LineSearcher<'r> {
+    /// Creates a searcher for lines matching `re`, without any context.
+    pub fn new(re: &'r Regex) -> LineSearcher<'r> {
+        LineSearcher {
+            re: re,
+            multi_line: can_match_newline(re),
+            invert: false,
+            before: 0,
+            after: 0,
+        }
+    }
+
+    /// If `yes` is true, then lines that don't match are selected instead.
+    pub fn invert(mut self, yes: bool) -> LineSearcher<'r> {
+        self.invert = yes;
+        self
+    }
+
+    /// Sets the number of lines to report before every selected line.
+    pub fn before_context(mut self, lines: usize) -> LineSearcher<'r> {
+        self.before = lines;
+        self
+    }
+
+    /// Sets the number of lines to report after every selected line.
+    pub fn after_context(mut self, lines: usize) -> LineSearcher<'r> {
+        self.after = lines;
+        self
+    }
+
+    /// Sets the number of lines to report both before and after every
+    /// selected line.
+    pub fn context(self, lines: usize) -> LineSearcher<'r> {
+        self.before_context(lines).after_context(lines)
+    }
+
+    /// Returns an iterator over the selected lines in `text` and their
+    /// context lines, in order. No line is reported twice.
+    ///
+    /// `'s` is the lifetime of the searcher and `'t` is the lifetime of the
+    /// text.
+    pub fn search<'s, 't>(&'s self, text: &'t str) -> LineMatches<'s, 't> {
+        let matches = if self.multi_line {
+            Some(self.re.find_iter(text))
+        } else {
+            None
+        };
+        LineMatches {
+            searcher: self,
+            text: text,
+            pos: 0,
+            number: 0,
+            matches: matches,
+            found: VecDeque::new(),
+            context: Context::new(self),
+        }
+    }
+
+    /// Returns an iterator over the selected lines read from `rdr` and their
+    /// context lines, in order.
+    ///
+    /// If the regex can match `\n`, then all of the input is read before the
+    /// first line is returned. Otherwise, it's read one line at a time. An
+    /// error is returned if reading fails or the input isn't valid UTF-8.
+    pub fn search_reader<'s, R: BufRead>(&'s self, rdr: R)
+                                        -> ReaderLineMatches<'s, R> {
+        ReaderLineMatches {
+            searcher: self,
+            rdr: Some(rdr),
+            number: 0,
+            offset: 0,
+            buffered: None,
+            context: Context::new(self),
+        }
+    }
+
+    /// Returns the number of selected lines in `text`.
+    pub fn count(&self, text: &str) -> usize {
+        self.without_context().search(text).count()
+    }
+
+    /// Returns the number of selected lines read from `rdr`.
+    pub fn count_reader<R: BufRead>(&self, rdr: R) -> io::Result<usize> {
+        let searcher = self.without_context();
+        let mut n = 0;
+        for line in searcher.search_reader(rdr) {
+            try!(line);
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    fn without_context(&self) -> LineSearcher<'r> {
+        self.clone().context(0)
+    }
+
+    /// Returns the matches of the regex in a single line.
+    fn find_in_line(&self, line: &str) -> Vec<Range<usize>> {
+        self.re.find_iter(line).map(|m| m.range()).collect()
+    }
+}
+
+/// Returns true if some instruction of the regex could consume a `\n`.
+fn can_match_newline(re: &Regex) -> bool {
+    re::program(re).insts.iter().any(|inst| {
+        match *inst {
+            Inst::OneChar { c, .. } => c == '\n',
+            Inst::CharClass(ref cls) => cls.matches('\n'),
+            Inst::Any => true,
+            _ => false,
+        }
+    })
+}
+
+/// Indicates why a line was reported by a `LineSearcher`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    /// The line was selected.
+    Selected,
+    /// The line is context for a nearby selected line.
+    Context,
+}
+
+/// A line reported by a `LineSearcher`.
+///
+/// `'t` is the lifetime of the text searched. Lines read from a `BufRead`
+/// own their text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line<'t> {
+    kind: LineKind,
+    number: usize,
+    offset: usize,
+    text: Cow<'t, str>,
+    matches: Vec<Range<usize>>,
+}
+
+impl<'t> Line<'t> {
+    /// Returns whether the line was selected or is context.
+    pub fn kind(&self) -> LineKind {
+        self.kind
+    }
+
+    /// Returns true if the line was selected.
+    pub fn is_selected(&self) -> bool {
+        self.kind == LineKind::Selected
+    }
+
+    /// Returns the number of the line. The first line is `1`.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Returns the byte offset of the start of the line in the input.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the text of the line, without its terminator.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the byte ranges of the matches in the line, relative to the
+    /// start of the line.
+    ///
+    /// A match that spans several lines is clipped to each of them.
+    pub fn matches(&self) -> &[Range<usize>] {
+        &self.matches
+    }
+
+    /// Converts the line into one that owns its text.
+    pub fn into_owned(self) -> Line<'static> {
+        Line {
+            kind: self.kind,
+            number: self.number,
+            offset: self.offset,
+            text: Cow::Owned(self.text.into_owned()),
+            matches: self.matches,
+        }
+    }
+}
+
+/// Decides which lines are reported, keeping track of the lines that may be
+/// needed as context before the next selected line.
+struct Context<'t> {
+    invert: bool,
+    before: usize,
+    after: usize,
+    // Lines that may be reported as context before a selected line.
+    pending: VecDeque<Line<'t>>,
+    // The number of lines that are still reported as context after the
+    // last selected line.
+    after_left: usize,
+    ready: VecDeque<Line<'t>>,
+}
+
+impl<'t> Context<'t> {
+    fn new(searcher: &LineSearcher) -> Context<'t> {
+        Context {
+            invert: searcher.invert,
+            before: searcher.before,
+            after: searcher.after,
+            pending: VecDeque::new(),
+            after_left: 0,
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, mut line: Line<'t>) {
+        if line.matches.is_empty() == self.invert {
+            self.ready.extend(self.pending.drain(..));
+            line.kind = LineKind::Selected;
+            self.ready.push_back(line);
+            self.after_left = self.after;
+        } else if self.after_left > 0 {
+            self.after_left -= 1;
+            self.ready.push_back(line);
+        } else if self.before > 0 {
+            if self.pending.len() == self.before {
+                self.pending.pop_front();
+            }
+            self.pending.push_back(line);
+        }
+    }
+
+    fn pop(&mut self) -> Option<Line<'t>> {
+        self.ready.pop_front()
+    }
+}
+
+/// An iterator over the lines reported by a `LineSearcher` for a string.
+///
+/// `'s` is the lifetime of the searcher and `'t` is the lifetime of the
+/// text.
+pub struct LineMatches<'s, 't> {
+    searcher: &'s LineSearcher<'s>,
+    text: &'t str,
+    pos: usize,
+    number: usize,
+    // The matches in the entire text, when it isn't searched line by line.
+    matches: Option<FindMatches<'s, 't>>,
+    // The matches found so far that may overlap the current line or a
+    // later one.
+    found: VecDeque<Range<usize>>,
+    context: Context<'t>,
+}
+
+impl<'s, 't> LineMatches<'s, 't> {
+    /// Returns the matches overlapping the line spanning `start..end`,
+    /// where `next` is the start of the following line.
+    fn matches_in(&mut self, start: usize, end: usize, next: usize)
+                 -> Vec<Range<usize>> {
+        // An empty match at the end of the line belongs to it, even if it's
+        // the last line and has no terminator.
+        let after_line = |m: &Range<usize>| m.start >= next && m.start != end;
+        // Skip the matches that ended before this line.
+        while let Some(m) = self.found.front().cloned() {
+            if m.end > start || (m.end == start && m.start == start) {
+                break
+            }
+            self.found.pop_front();
+        }
+        // Search until a match is found after this line, or there are none
+        // left.
+        while self.found.back().map_or(true, |m| !after_line(m)) {
+            match self.matches.as_mut().and_then(|it| it.next()) {
+                None => break,
+                Some(m) => self.found.push_back(m.range()),
+            }
+        }
+        let mut found = vec![];
+        for m in &self.found {
+            if after_line(m) {
+                break
+            }
+            let s = if m.start > start { m.start } else { start };
+            let e = if m.end < end { m.end } else { end };
+            let s = if s > e { e } else { s };
+            found.push(s - start..e - start);
+        }
+        found
+    }
+}
+
+impl<'s, 't> Iterator for LineMatches<'s, 't> {
+    type Item = Line<'t>;
+
+    fn next(&mut self) -> Option<Line<'t>> {
+        loop {
+            if let Some(line) = self.context.pop() {
+                return Some(line)
+            }
+            if self.pos >= self.text.len() {
+                return None
+            }
+            let start = self.pos;
+            let (end, next) = match self.text[start..].find('\n') {
+                None => (self.text.len(), self.text.len()),
+                Some(i) => (start + i, start + i + 1),
+            };
+            self.pos = next;
+            self.number += 1;
+            let text = &self.text[start..end];
+            let matches = if self.searcher.multi_line {
+                self.matches_in(start, end, next)
+            } else {
+                self.searcher.find_in_line(text)
+            };
+            self.context.push(Line {
+                kind: LineKind::Context,
+                number: self.number,
+                offset: start,
+                text: Cow::Borrowed(text),
+                matches: matches,
+            });
+        }
+    }
+}
+
+/// An iterator over the lines reported by a `LineSearcher` for a reader.
+///
+/// `'s` is the lifetime of the searcher and `R` is the type of the reader.
+pub struct ReaderLineMatches<'s, R> {
+    searcher: &'s LineSearcher<'s>,
+    rdr: Option<R>,
+    number: usize,
+    offset: usize,
+    // All of the lines, when the input can't be searched line by line.
+    buffered: Option<vec::IntoIter<Line<'static>>>,
+    context: Context<'static>,
+}
+
+impl<'s, R: BufRead> ReaderLineMatches<'s, R> {
+    /// Reads all of the input and searches it at once.
+    fn read_all(&mut self, mut rdr: R) -> io::Result<()> {
+        let mut text = String::new();
+        try!(rdr.read_to_string(&mut text));
+        let lines: Vec<Line<'static>> =
+            self.searcher.search(&text).map(Line::into_owned).collect();
+        self.buffered = Some(lines.into_iter());
+        Ok(())
+    }
+}
+
+impl<'s, R: BufRead> Iterator for ReaderLineMatches<'s, R> {
+    type Item = io::Result<Line<'static>>;
+
+    fn next(&mut self) -> Option<io::Result<Line<'static>>> {
+        if self.searcher.multi_line {
+            if let Some(rdr) = self.rdr.take() {
+                if let Err(err) = self.read_all(rdr) {
+                    return Some(Err(err))
+                }
+            }
+            return self.buffered.as_mut().and_then(|lines| lines.next())
+                                         .map(Ok)
+        }
+        loop {
+            if let Some(line) = self.context.pop() {
+                return Some(Ok(line))
+            }
+            let mut text = String::new();
+            let n = match self.rdr.as_mut() {
+                None => return None,
+                Some(rdr) => rdr.read_line(&mut text),
+            };
+            match n {
+                Ok(0) => {
+                    self.rdr = None;
+                    continue
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    self.rdr = None;
+                    return Some(Err(err))
+                }
+            }
+            let offset = self.offset;
+            self.offset += text.len();
+            if text.ends_with('\n') {
+                text.pop();
+            }
+            self.number += 1;
+            let matches = self.searcher.find_in_line(&text);
+            self.context.push(Line {
+                kind: LineKind::Context,
+                number: self.number,
+                offset: offset,
+                text: Cow::Owned(text),
+                matches: matches,
+            });
+        }
+    }
+}
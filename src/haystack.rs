@@ -0,0 +1,278 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Arc;
+
+use compile::Program;
+use re::{Regex, Successive, program};
+use vm::{self, CaptureLocs};
+use vm::Anchor::Unanchored;
+use vm::MatchKind::{self, Exists, Location, Submatches};
+
+/// Text to search that may be stored in several chunks, like a rope or a
+/// gap buffer.
+///
+/// The bytes of all chunks, in order, make up the text. They should be valid
+/// UTF-8, but a character may be split between two chunks. Any byte that
+/// isn't part of valid UTF-8 is matched as `U+FFFD`.
+///
+/// Every offset used with a haystack is a byte offset from the beginning of
+/// the entire text.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::{Chunks, Regex};
+/// # fn main() {
+/// let rope = Chunks::new(vec!["hello wo", "rld, hé", "llo"]);
+/// let re = Regex::new(r"world|h.llo").unwrap();
+/// let found: Vec<_> = re.find_iter_haystack(&rope).map(|m| m.range())
+///                       .collect();
+/// assert_eq!(found, vec![0..5, 6..11, 13..19]);
+/// # }
+/// ```
+pub trait Haystack {
+    /// Returns the length of the entire text in bytes.
+    fn len(&self) -> usize;
+
+    /// Returns the non-empty chunk that contains the byte at offset `at`
+    /// along with the offset at which that chunk begins.
+    ///
+    /// This is only called with offsets less than `len()`.
+    fn chunk_at(&self, at: usize) -> (&[u8], usize);
+}
+
+impl Haystack for str {
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    fn chunk_at(&self, _: usize) -> (&[u8], usize) {
+        (self.as_bytes(), 0)
+    }
+}
+
+impl Haystack for String {
+    fn len(&self) -> usize {
+        String::len(self)
+    }
+
+    fn chunk_at(&self, _: usize) -> (&[u8], usize) {
+        (self.as_bytes(), 0)
+    }
+}
+
+impl Haystack for [u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn chunk_at(&self, _: usize) -> (&[u8], usize) {
+        (self, 0)
+    }
+}
+
+impl Haystack for VecDeque<u8> {
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+
+    fn chunk_at(&self, at: usize) -> (&[u8], usize) {
+        let (front, back) = self.as_slices();
+        if at < front.len() {
+            (front, 0)
+        } else {
+            (back, front.len())
+        }
+    }
+}
+
+/// A haystack made of a sequence of string slices.
+///
+/// This is useful for searching any text that can be iterated over in
+/// chunks without copying it into a single string first.
+#[derive(Clone, Debug)]
+pub struct Chunks<'a> {
+    chunks: Vec<&'a str>,
+    // The offset at which each chunk begins.
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl<'a> Chunks<'a> {
+    /// Creates a haystack from the given chunks, in order.
+    pub fn new<I>(chunks: I) -> Chunks<'a>
+            where I: IntoIterator<Item=&'a str> {
+        let mut hay = Chunks { chunks: vec![], starts: vec![], len: 0 };
+        for chunk in chunks {
+            if chunk.is_empty() {
+                continue
+            }
+            hay.chunks.push(chunk);
+            hay.starts.push(hay.len);
+            hay.len += chunk.len();
+        }
+        hay
+    }
+}
+
+impl<'a> Haystack for Chunks<'a> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn chunk_at(&self, at: usize) -> (&[u8], usize) {
+        let i = match self.starts.binary_search(&at) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        (self.chunks[i].as_bytes(), self.starts[i])
+    }
+}
+
+/// A single match of a regular expression in a `Haystack`.
+///
+/// Unlike a `Match`, it only knows the start and end byte offsets of the
+/// match, since the matched text may be split between chunks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HaystackMatch {
+    start: usize,
+    end: usize,
+}
+
+impl HaystackMatch {
+    /// Returns the starting byte offset of the match in the haystack.
+    #[inline]
+    pub fn start(&self) -> usize { self.start }
+
+    /// Returns the ending byte offset of the match in the haystack.
+    #[inline]
+    pub fn end(&self) -> usize { self.end }
+
+    /// Returns the range of byte offsets spanned by the match.
+    #[inline]
+    pub fn range(&self) -> Range<usize> { self.start..self.end }
+
+    /// Returns the length, in bytes, of the match.
+    #[inline]
+    pub fn len(&self) -> usize { self.end - self.start }
+
+    /// Returns true if and only if the match is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.start == self.end }
+}
+
+/// The capture groups of a single match in a `Haystack`.
+///
+/// The 0th capture always corresponds to the entire match.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HaystackCaptures {
+    locs: CaptureLocs,
+}
+
+impl HaystackCaptures {
+    /// Returns the match for the capture group `i`, or `None` if the group
+    /// didn't participate in the match.
+    pub fn get(&self, i: usize) -> Option<HaystackMatch> {
+        self.pos(i).map(|(s, e)| HaystackMatch { start: s, end: e })
+    }
+
+    /// Returns the start and end byte offsets of the capture group `i`, or
+    /// `None` if the group didn't participate in the match.
+    pub fn pos(&self, i: usize) -> Option<(usize, usize)> {
+        if i >= self.len() {
+            return None
+        }
+        match (self.locs[2 * i], self.locs[2 * i + 1]) {
+            (Some(s), Some(e)) => Some((s, e)),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of captured groups, including the entire match.
+    #[inline]
+    pub fn len(&self) -> usize { self.locs.len() / 2 }
+}
+
+fn exec<H>(prog: &Program, which: MatchKind, input: &H, start: usize)
+          -> CaptureLocs where H: ?Sized + Haystack {
+    vm::run(which, Unanchored, prog, input, start, input.len())
+}
+
+impl Regex {
+    /// Returns true if and only if the regex matches somewhere in the
+    /// haystack.
+    pub fn is_match_haystack<H: ?Sized + Haystack>(&self, input: &H) -> bool {
+        let caps = exec(&program(self), Exists, input, 0);
+        caps[0].is_some()
+    }
+
+    /// Returns the leftmost-first match in the haystack.
+    pub fn find_haystack<H>(&self, input: &H) -> Option<HaystackMatch>
+            where H: ?Sized + Haystack {
+        let caps = exec(&program(self), Location, input, 0);
+        match (caps[0], caps[1]) {
+            (Some(s), Some(e)) => Some(HaystackMatch { start: s, end: e }),
+            _ => None,
+        }
+    }
+
+    /// Returns the capture groups of the leftmost-first match in the
+    /// haystack, or `None` if there is no match.
+    pub fn captures_haystack<H>(&self, input: &H) -> Option<HaystackCaptures>
+            where H: ?Sized + Haystack {
+        let caps = exec(&program(self), Submatches, input, 0);
+        if caps[0].is_none() {
+            return None
+        }
+        Some(HaystackCaptures { locs: caps })
+    }
+
+    /// Returns an iterator over all the non-overlapping matches in the
+    /// haystack.
+    ///
+    /// `'h` is the lifetime of the haystack. The iterator shares the
+    /// compiled program with the regex, so it doesn't borrow the regex.
+    pub fn find_iter_haystack<'h, H>(&self, input: &'h H)
+                                    -> HaystackMatches<'h, H>
+            where H: ?Sized + Haystack {
+        HaystackMatches {
+            prog: program(self),
+            input: input,
+            matches: Successive::new(input),
+        }
+    }
+}
+
+/// An iterator over the non-overlapping matches in a `Haystack`.
+///
+/// `'h` is the lifetime of the haystack.
+pub struct HaystackMatches<'h, H: ?Sized + 'h> {
+    prog: Arc<Program>,
+    input: &'h H,
+    matches: Successive<'h, H>,
+}
+
+impl<'h, H: ?Sized + Haystack> Iterator for HaystackMatches<'h, H> {
+    type Item = HaystackMatch;
+
+    fn next(&mut self) -> Option<HaystackMatch> {
+        let (prog, input) = (&self.prog, self.input);
+        match self.matches.next(|at| Some(exec(prog, Location, input, at))) {
+            Some(Ok(caps)) => Some(HaystackMatch {
+                start: caps[0].unwrap(),
+                end: caps[1].unwrap(),
+            }),
+            _ => None,
+        }
+    }
+}
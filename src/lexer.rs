@@ -0,0 +1,242 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ops::Range;
+
+use compile::Program;
+use re::{DEFAULT_SIZE_LIMIT, Error};
+use syntax;
+use vm;
+
+/// Builds a `Lexer` from an ordered list of rules.
+///
+/// Every rule is a pattern. A token rule produces a token of the given kind
+/// and a skip rule (e.g., for whitespace or comments) consumes its text
+/// without producing anything.
+pub struct LexerBuilder<K> {
+    rules: Vec<(Option<K>, String)>,
+    error: K,
+    size_limit: usize,
+}
+
+impl<K> LexerBuilder<K> {
+    /// Creates a builder with no rules. Text that no rule matches is
+    /// reported as a token of kind `error`.
+    pub fn new(error: K) -> LexerBuilder<K> {
+        LexerBuilder {
+            rules: vec![],
+            error: error,
+            size_limit: DEFAULT_SIZE_LIMIT,
+        }
+    }
+
+    /// Adds a rule that produces a token of kind `kind` for text matched by
+    /// `pattern`.
+    pub fn token(mut self, kind: K, pattern: &str) -> LexerBuilder<K> {
+        self.rules.push((Some(kind), pattern.to_string()));
+        self
+    }
+
+    /// Adds a rule that silently consumes text matched by `pattern`.
+    pub fn skip(mut self, pattern: &str) -> LexerBuilder<K> {
+        self.rules.push((None, pattern.to_string()));
+        self
+    }
+
+    /// Sets the size limit of the compiled program, which has the same
+    /// meaning as in `Regex::with_size_limit`.
+    pub fn size_limit(mut self, size: usize) -> LexerBuilder<K> {
+        self.size_limit = size;
+        self
+    }
+
+    /// Compiles all of the rules into a single program.
+    ///
+    /// If any of the patterns is invalid or the program is too big, then an
+    /// error is returned.
+    pub fn build(self) -> Result<Lexer<K>, Error> {
+        let mut asts = Vec::with_capacity(self.rules.len());
+        let mut kinds = Vec::with_capacity(self.rules.len());
+        for (kind, pattern) in self.rules {
+            asts.push(try!(syntax::Expr::parse(&pattern)));
+            kinds.push(kind);
+        }
+        let any = syntax::Expr::Alternate(asts.clone());
+        let (search, _) = try!(Program::new(any, self.size_limit));
+        let prog = try!(Program::new_rules(asts, self.size_limit));
+        Ok(Lexer {
+            prog: prog,
+            search: search,
+            kinds: kinds,
+            error: self.error,
+        })
+    }
+}
+
+/// A lexer that splits text into tokens with a set of rules.
+///
+/// All of the rules are compiled into one program, so each token is found
+/// with a single pass over its text. At every position, the rule with the
+/// longest match wins. If several rules match text of the same length, then
+/// the rule that was added first wins. Empty matches are ignored.
+///
+/// Text that isn't matched by any rule is returned as a token of the error
+/// kind given to `LexerBuilder::new`. Consecutive unmatched characters are
+/// grouped into a single error token.
+///
+/// Assertions like `^` and `\b` are evaluated with respect to the entire
+/// text, not just the remaining input.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::LexerBuilder;
+/// # fn main() {
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// enum Tok { Num, Ident, Let, Eq, Error }
+///
+/// let lexer = LexerBuilder::new(Tok::Error)
+///     .token(Tok::Let, "let")
+///     .token(Tok::Ident, "[a-z]+")
+///     .token(Tok::Num, "[0-9]+")
+///     .token(Tok::Eq, "=")
+///     .skip(r"\s+")
+///     .build().unwrap();
+///
+/// let kinds: Vec<Tok> = lexer.tokens("let letter = 42 ?")
+///                            .map(|t| *t.kind()).collect();
+/// assert_eq!(kinds, vec![
+///     Tok::Let, Tok::Ident, Tok::Eq, Tok::Num, Tok::Error,
+/// ]);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Lexer<K> {
+    prog: Program,
+    /// Matches the text of any rule. It's used to find where the next token
+    /// starts after text that no rule matches.
+    search: Program,
+    kinds: Vec<Option<K>>,
+    error: K,
+}
+
+impl<K: Clone> Lexer<K> {
+    /// Returns an iterator over the tokens in `text`.
+    ///
+    /// `'l` is the lifetime of the lexer and `'t` is the lifetime of the
+    /// text.
+    pub fn tokens<'l, 't>(&'l self, text: &'t str) -> Tokens<'l, 't, K> {
+        Tokens { lexer: self, text: text, pos: 0 }
+    }
+
+    /// Returns the end of the longest non-empty match starting at `start`
+    /// along with the index of the rule that matched.
+    fn longest_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        match vm::run_longest(&self.prog, text, start) {
+            Some((end, rule)) if end > start => Some((end, rule)),
+            _ => None,
+        }
+    }
+
+    /// Returns the first position at or after `start` where some rule has a
+    /// non-empty match, or the end of `text` if there is none.
+    fn next_start(&self, text: &str, start: usize) -> usize {
+        vm::run_nonempty(&self.search, text, start).unwrap_or(text.len())
+    }
+}
+
+/// A single token produced by a `Lexer`.
+///
+/// `'t` is the lifetime of the text that was split into tokens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'t, K> {
+    kind: K,
+    text: &'t str,
+    start: usize,
+    end: usize,
+}
+
+impl<'t, K> Token<'t, K> {
+    /// Returns the kind of the rule that produced this token, or the error
+    /// kind if no rule matched its text.
+    pub fn kind(&self) -> &K {
+        &self.kind
+    }
+
+    /// Returns the text of this token.
+    pub fn as_str(&self) -> &'t str {
+        &self.text[self.start..self.end]
+    }
+
+    /// Returns the starting byte offset of this token.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the ending byte offset of this token.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the byte range of this token.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+/// An iterator over the tokens produced by a `Lexer`.
+///
+/// `'l` is the lifetime of the lexer and `'t` is the lifetime of the text.
+pub struct Tokens<'l, 't, K: 'l> {
+    lexer: &'l Lexer<K>,
+    text: &'t str,
+    pos: usize,
+}
+
+impl<'l, 't, K: Clone> Tokens<'l, 't, K> {
+    fn next_char(&self, pos: usize) -> usize {
+        pos + self.text[pos..].chars().next().map_or(1, |c| c.len_utf8())
+    }
+}
+
+impl<'l, 't, K: Clone> Iterator for Tokens<'l, 't, K> {
+    type Item = Token<'t, K>;
+
+    fn next(&mut self) -> Option<Token<'t, K>> {
+        while self.pos < self.text.len() {
+            let start = self.pos;
+            match self.lexer.longest_at(self.text, start) {
+                Some((end, rule)) => {
+                    self.pos = end;
+                    if let Some(ref kind) = self.lexer.kinds[rule] {
+                        return Some(Token {
+                            kind: kind.clone(),
+                            text: self.text,
+                            start: start,
+                            end: end,
+                        })
+                    }
+                }
+                None => {
+                    let end = self.next_char(start);
+                    let end = self.lexer.next_start(self.text, end);
+                    self.pos = end;
+                    return Some(Token {
+                        kind: self.lexer.error.clone(),
+                        text: self.text,
+                        start: start,
+                        end: end,
+                    })
+                }
+            }
+        }
+        None
+    }
+}
@@ -202,7 +202,7 @@
 //! # extern crate regex; use regex::Regex;
 //! # fn main() {
 //! let re = Regex::new(r"(?i)Δ+").unwrap();
-//! assert_eq!(re.find("ΔδΔ"), Some((0, 6)));
+//! assert_eq!(re.find("ΔδΔ").unwrap().as_str(), "ΔδΔ");
 //! # }
 //! ```
 //!
@@ -214,7 +214,7 @@
 //! # extern crate regex; use regex::Regex;
 //! # fn main() {
 //! let re = Regex::new(r"[\pN\p{Greek}\p{Cherokee}]+").unwrap();
-//! assert_eq!(re.find("abcΔᎠβⅠᏴγδⅡxyz"), Some((3, 23)));
+//! assert_eq!(re.find("abcΔᎠβⅠᏴγδⅡxyz").unwrap().range(), 3..23);
 //! # }
 //! ```
 //!
@@ -395,16 +395,53 @@
        html_root_url = "http://doc.rust-lang.org/regex/")]
 
 extern crate regex_syntax as syntax;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "derive")]
+extern crate regex_derive;
 
 pub use re::{
-    Regex, Error, Captures, SubCaptures, SubCapturesPos, SubCapturesNamed,
-    FindCaptures, FindMatches,
-    Replacer, NoExpand, RegexSplits, RegexSplitsN,
+    Regex, Error, Match, Captures, CaptureNames, SubCaptures, SubCapturesPos,
+    SubCapturesNamed, FindCaptures, FindMatches, FindOverlappingMatches,
+    RFindMatches, CancellableMatches, CancellableCaptures, Replacer,
+    ReplacerRef, NoExpand, RegexSplits, RegexSplitsN,
+    RegexRSplitsN, RegexSplitsCaptures, RegexSplitsInclusive,
+    RegexSplitsDelims, SplitItem,
     quote, is_match,
 };
+pub use cache::{RegexCache, CacheStats};
+pub use cancel::{CancelToken, Cancelled};
+pub use dfa::{LanguageChecker, Verdict, DEFAULT_STATE_LIMIT};
+pub use expand::ReplacementTemplate;
+pub use extract::{FromCaptures, CaptureError, CaptureErrorKind, CapturesInto};
+pub use haystack::{
+    Haystack, Chunks, HaystackMatch, HaystackCaptures, HaystackMatches,
+};
+pub use grep::{LineSearcher, Line, LineKind, LineMatches, ReaderLineMatches};
+pub use position::{Position, PositionTracker, FindPositions,
+                   FindCapturesPositions};
+pub use trace::{Trace, TraceStep, SlotUpdate};
+pub use lexer::{Lexer, LexerBuilder, Token, Tokens};
+#[cfg(feature = "derive")]
+pub use regex_derive::FromCaptures;
+#[cfg(feature = "serde")]
+pub use serialize::RegexOptions;
 
+mod cache;
+mod cancel;
 mod compile;
+mod compose;
+mod dfa;
+mod expand;
+mod extract;
+mod grep;
+mod haystack;
+mod lexer;
+mod position;
 mod re;
+#[cfg(feature = "serde")]
+mod serialize;
+mod trace;
 mod vm;
 
 /// The `native` module exists to support the `regex!` macro. Do not use.
@@ -431,6 +468,7 @@ pub mod native {
     pub use re::{ExDynamic, ExNative};
     pub use re::Regex::{Dynamic, Native};
     pub use vm::{CharReader, find_prefix};
+    pub use vm::Anchor::{self, Unanchored, AnchoredStart, AnchoredBoth};
     pub use vm::MatchKind::{self, Exists, Location, Submatches};
     pub use vm::StepState::{
         self, StepMatchEarlyReturn, StepMatch, StepContinue,
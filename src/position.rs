@@ -0,0 +1,214 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use re::{Captures, FindCaptures, FindMatches, Match, Regex};
+use vm::CharReader;
+
+/// A position in a string, measured in several units at once.
+///
+/// Lines and columns start at `0`, and lines are terminated by `\n`. This
+/// matches the positions used by the Language Server Protocol when the
+/// UTF-16 column is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// The offset in UTF-8 bytes.
+    pub byte: usize,
+    /// The offset in Unicode scalar values (`char`s).
+    pub char: usize,
+    /// The offset in UTF-16 code units.
+    pub utf16: usize,
+    /// The line number.
+    pub line: usize,
+    /// The offset from the start of the line in `char`s.
+    pub column: usize,
+    /// The offset from the start of the line in UTF-16 code units.
+    pub utf16_column: usize,
+}
+
+/// Converts byte offsets in a string to `Position`s.
+///
+/// The tracker remembers the last position it computed and only reads the
+/// text between it and the next offset requested. Converting increasing
+/// offsets, like the ones found by successive matches, therefore takes
+/// linear time in the length of the text overall.
+///
+/// The characters are read with a `CharReader` of the tracker's own rather
+/// than the one the VM searches with. The VM starts over for every match
+/// and skips ahead to literal prefixes, so it doesn't visit every character
+/// once, and counting positions in its inner loop would slow down every
+/// search, including the ones that don't need positions.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::PositionTracker;
+/// # fn main() {
+/// let mut tracker = PositionTracker::new("a\n€𝄞b");
+/// let pos = tracker.position(9);
+/// assert_eq!((pos.char, pos.utf16), (4, 5));
+/// assert_eq!((pos.line, pos.column, pos.utf16_column), (1, 2, 3));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PositionTracker<'t> {
+    text: &'t str,
+    chars: CharReader<'t>,
+    cur: Position,
+}
+
+impl<'t> PositionTracker<'t> {
+    /// Creates a tracker for positions in `text`.
+    pub fn new(text: &'t str) -> PositionTracker<'t> {
+        let mut chars = CharReader::new(text);
+        chars.set(0);
+        PositionTracker { text: text, chars: chars, cur: Position::default() }
+    }
+
+    /// Returns the position of the byte offset `byte`.
+    ///
+    /// Offsets before the last one requested are computed from the start of
+    /// the text again.
+    ///
+    /// # Panics
+    ///
+    /// If `byte` isn't on a character boundary of the text.
+    pub fn position(&mut self, byte: usize) -> Position {
+        assert!(self.text.is_char_boundary(byte),
+                "byte offset {} is not a character boundary", byte);
+        if byte < self.cur.byte {
+            self.cur = Position::default();
+            self.chars.set(0);
+        }
+        while self.cur.byte < byte {
+            // The reader's current character is the one at `cur.byte`.
+            let c = self.chars.cur.expect("offset within the text");
+            self.chars.advance();
+            let cur = &mut self.cur;
+            cur.byte += c.len_utf8();
+            cur.char += 1;
+            cur.utf16 += c.len_utf16();
+            if c == '\n' {
+                cur.line += 1;
+                cur.column = 0;
+                cur.utf16_column = 0;
+            } else {
+                cur.column += 1;
+                cur.utf16_column += c.len_utf16();
+            }
+        }
+        self.cur
+    }
+}
+
+impl Regex {
+    /// Returns an iterator over all the non-overlapping matches in `text`,
+    /// along with the positions of the start and end of each match.
+    ///
+    /// The positions are computed incrementally as the iterator advances,
+    /// so the whole iteration takes time linear in the length of `text`
+    /// (besides the search itself).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"\w+").unwrap();
+    /// let ends: Vec<_> = re.find_iter_positions("héllo\nwörld")
+    ///                      .map(|(_, start, end)| (start.line, end.column))
+    ///                      .collect();
+    /// assert_eq!(ends, vec![(0, 5), (1, 5)]);
+    /// # }
+    /// ```
+    pub fn find_iter_positions<'r, 't>(&'r self, text: &'t str)
+                                      -> FindPositions<'r, 't> {
+        FindPositions {
+            it: self.find_iter(text),
+            tracker: PositionTracker::new(text),
+        }
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups
+    /// matched in `text`, along with the positions of the start and end of
+    /// every group that participated in the match.
+    ///
+    /// As with `find_iter_positions`, positions are computed incrementally.
+    pub fn captures_iter_positions<'r, 't>(&'r self, text: &'t str)
+                                          -> FindCapturesPositions<'r, 't> {
+        FindCapturesPositions {
+            it: self.captures_iter(text),
+            tracker: PositionTracker::new(text),
+        }
+    }
+}
+
+/// An iterator over the non-overlapping matches of a regex, along with
+/// their start and end positions.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct FindPositions<'r, 't> {
+    it: FindMatches<'r, 't>,
+    tracker: PositionTracker<'t>,
+}
+
+impl<'r, 't> Iterator for FindPositions<'r, 't> {
+    type Item = (Match<'t>, Position, Position);
+
+    fn next(&mut self) -> Option<(Match<'t>, Position, Position)> {
+        self.it.next().map(|m| {
+            let start = self.tracker.position(m.start());
+            let end = self.tracker.position(m.end());
+            (m, start, end)
+        })
+    }
+}
+
+/// An iterator over the non-overlapping capture groups matched by a regex,
+/// along with the start and end positions of each group.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct FindCapturesPositions<'r, 't> {
+    it: FindCaptures<'r, 't>,
+    tracker: PositionTracker<'t>,
+}
+
+impl<'r, 't> Iterator for FindCapturesPositions<'r, 't> {
+    type Item = (Captures<'t>, Vec<Option<(Position, Position)>>);
+
+    fn next(&mut self)
+           -> Option<(Captures<'t>, Vec<Option<(Position, Position)>>)> {
+        let caps = match self.it.next() {
+            None => return None,
+            Some(caps) => caps,
+        };
+        // Groups may nest, so their offsets aren't in order. Visiting them
+        // in sorted order keeps the tracker moving forward.
+        let mut offsets = vec![];
+        for i in 0..caps.len() {
+            if let Some((s, e)) = caps.pos(i) {
+                offsets.push(s);
+                offsets.push(e);
+            }
+        }
+        offsets.sort();
+        offsets.dedup();
+        let positions: Vec<Position> =
+            offsets.iter().map(|&b| self.tracker.position(b)).collect();
+        let find = |b: usize| {
+            positions[offsets.binary_search(&b).unwrap()]
+        };
+        let spans = (0..caps.len()).map(|i| {
+            caps.pos(i).map(|(s, e)| (find(s), find(e)))
+        }).collect();
+        Some((caps, spans))
+    }
+}
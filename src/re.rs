@@ -9,22 +9,33 @@
 // except according to those terms.
 
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::collections::hash_map::Iter;
 use std::fmt;
+use std::io;
+use std::ops::{Index, Range};
+use std::slice;
+use std::vec;
+use std::sync::{Arc, Mutex};
 #[cfg(feature = "pattern")]
-use std::str::pattern::{Pattern, Searcher, SearchStep};
+use std::str::pattern::{Pattern, Searcher, ReverseSearcher, SearchStep};
 use std::str::FromStr;
 
+use cancel::{CancelToken, Cancelled};
 use compile::Program;
+use expand::expand_str;
+use haystack::Haystack;
 use syntax;
-use vm;
-use vm::CaptureLocs;
+use vm::{self, CaptureLocs, CharReader};
+use vm::Anchor::{self, Unanchored, AnchoredStart, AnchoredBoth};
 use vm::MatchKind::{self, Exists, Location, Submatches};
 
 use self::NamesIter::*;
 use self::Regex::*;
 
+/// The size limit used by `Regex::new`, in bytes.
+pub const DEFAULT_SIZE_LIMIT: usize = 10 * (1 << 20);
+
 /// Escapes all regular expression meta characters in `text`.
 ///
 /// The string returned may be safely used as a literal in a regular
@@ -51,14 +62,96 @@ pub fn is_match(regex: &str, text: &str) -> Result<bool, Error> {
     Regex::new(regex).map(|r| r.is_match(text))
 }
 
+/// Compiles `pattern` with the given size limit and modes.
+///
+/// The case insensitive flag is given to the parser rather than added to
+/// the pattern, so `as_str` and the positions in errors refer to `pattern`
+/// as given.
+pub fn compile_with_options(pattern: &str, modes: Modes, size_limit: usize)
+                           -> Result<Regex, Error> {
+    let (ast, spans) = try!(syntax::ExprBuilder::new()
+                                       .case_insensitive(modes.case_insensitive)
+                                       .parse_with_spans(pattern));
+    let ast = try!(modes.rewrite(ast));
+    from_ast(pattern, ast, spans, modes, size_limit)
+}
+
+/// The modes a regex can be compiled with that aren't part of its pattern.
+///
+/// Each one changes the expression parsed from the pattern, so a regex
+/// keeps them to be compiled the same way again (e.g., when it's
+/// serialized).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modes {
+    /// Whether the whole pattern matches case insensitively, as if it
+    /// started with `(?i)`.
+    pub case_insensitive: bool,
+    /// Whether case insensitive parts match with full case folding.
+    pub full_case_folding: bool,
+    /// Whether literals and classes match canonically equivalent text.
+    pub canonical_equivalence: bool,
+}
+
+impl Modes {
+    /// Rewrites an expression parsed from a pattern for these modes.
+    fn rewrite(&self, mut ast: syntax::Expr) -> Result<syntax::Expr, Error> {
+        if self.full_case_folding {
+            ast = ast.full_case_fold();
+        }
+        if self.canonical_equivalence {
+            ast = match ast.canonical_equivalence() {
+                None => {
+                    return Err(Error::TooManyMarkOrders(
+                        syntax::MAX_MARK_ORDERS))
+                }
+                Some(ast) => ast,
+            };
+        }
+        Ok(ast)
+    }
+}
+
+/// Compiles `ast`, which was parsed from `re` and rewritten for `modes`.
+/// `spans` are the spans of its capture groups in `re`.
+pub fn from_ast(re: &str, ast: syntax::Expr, spans: Vec<(usize, usize)>,
+                modes: Modes, size: usize) -> Result<Regex, Error> {
+    let (mut prog, names) = try!(Program::new(ast.clone(), size));
+    prog.whole_clusters = modes.canonical_equivalence;
+    let groups = Arc::new(GroupMap::new(&names));
+    Ok(Dynamic(ExDynamic {
+        original: re.to_string(),
+        names: names,
+        groups: groups,
+        spans: spans.into_iter().map(|(s, e)| s..e).collect(),
+        expr: Arc::new(ast),
+        modes: modes,
+        reversed: Arc::new(Mutex::new(None)),
+        prog: Arc::new(prog),
+    }))
+}
+
 /// An error that occurred during parsing or compiling a regular expression.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Error {
     /// A syntax error.
     Syntax(syntax::Error),
     /// The compiled program exceeded the set size limit.
     /// The argument is the size limit imposed.
     CompiledTooBig(usize),
+    /// A replacement template refers to a capture group that doesn't exist.
+    /// The argument is the reference as written in the template (either a
+    /// group index or a group name).
+    UnknownGroup(String),
+    /// Regexes being combined have more than one capture group with the
+    /// same name. The argument is that name.
+    DuplicateGroupName(String),
+    /// Comparing the languages of regexes needed more DFA states than
+    /// allowed. The argument is the state limit imposed.
+    TooManyStates(usize),
+    /// A character in a regex compiled with canonical equivalence has
+    /// combining marks that can be written in more orders than allowed.
+    /// The argument is the limit imposed.
+    TooManyMarkOrders(usize),
     /// Hints that destructuring should not be exhaustive.
     ///
     /// This enum may grow additional variants, so this makes sure clients
@@ -73,6 +166,10 @@ impl ::std::error::Error for Error {
         match *self {
             Error::Syntax(ref err) => err.description(),
             Error::CompiledTooBig(_) => "compiled program too big",
+            Error::UnknownGroup(_) => "unknown capture group in replacement",
+            Error::DuplicateGroupName(_) => "duplicate capture group name",
+            Error::TooManyStates(_) => "too many DFA states",
+            Error::TooManyMarkOrders(_) => "too many orders of combining marks",
             Error::__Nonexhaustive => unreachable!(),
         }
     }
@@ -93,6 +190,21 @@ impl fmt::Display for Error {
                 write!(f, "Compiled regex exceeds size limit of {} bytes.",
                        limit)
             }
+            Error::UnknownGroup(ref name) => {
+                write!(f, "Replacement refers to unknown capture group '{}'.",
+                       name)
+            }
+            Error::DuplicateGroupName(ref name) => {
+                write!(f, "Capture group name '{}' is used more than once.",
+                       name)
+            }
+            Error::TooManyStates(limit) => {
+                write!(f, "DFA exceeds state limit of {} states.", limit)
+            }
+            Error::TooManyMarkOrders(limit) => {
+                write!(f, "Combining marks can be written in more than {} \
+                           orders.", limit)
+            }
             Error::__Nonexhaustive => unreachable!(),
         }
     }
@@ -111,7 +223,8 @@ impl From<syntax::Error> for Error {
 /// or replace text. All searching is done with an implicit `.*?` at the
 /// beginning and end of an expression. To force an expression to match the
 /// whole string (or a prefix or a suffix), you must use an anchor like `^` or
-/// `$` (or `\A` and `\z`).
+/// `$` (or `\A` and `\z`), or use one of the anchored methods like
+/// `is_full_match` and `match_prefix`.
 ///
 /// While this crate will handle Unicode strings (whether in the regular
 /// expression or in the search text), all positions returned are **byte
@@ -122,8 +235,8 @@ impl From<syntax::Error> for Error {
 /// compiled regular expression and text to search, respectively.
 ///
 /// The only methods that allocate new strings are the string replacement
-/// methods. All other methods (searching and splitting) return borrowed
-/// pointers into the string given.
+/// methods, and only when there is something to replace. All other methods
+/// (searching and splitting) return borrowed pointers into the string given.
 ///
 /// # Examples
 ///
@@ -132,7 +245,9 @@ impl From<syntax::Error> for Error {
 /// ```rust
 /// # use regex::Regex;
 /// let re = Regex::new("[0-9]{3}-[0-9]{3}-[0-9]{4}").unwrap();
-/// assert_eq!(re.find("phone: 111-222-3333"), Some((7, 19)));
+/// let mat = re.find("phone: 111-222-3333").unwrap();
+/// assert_eq!((mat.start(), mat.end()), (7, 19));
+/// assert_eq!(mat.as_str(), "111-222-3333");
 /// ```
 ///
 /// # Using the `std::str::StrExt` methods with `Regex`
@@ -176,8 +291,19 @@ pub enum Regex {
 pub struct ExDynamic {
     original: String,
     names: Vec<Option<String>>,
+    groups: Arc<GroupMap>,
+    // The span of each capture group in `original`.
+    spans: Vec<Range<usize>>,
+    // The expression `prog` was compiled from. It may differ from the
+    // expression written in `original` when the regex was compiled with
+    // options that rewrite it, such as full case folding.
+    expr: Arc<syntax::Expr>,
+    modes: Modes,
+    // The program for searching in reverse, which is compiled from `expr`
+    // the first time it's needed. Clones of the regex share it.
+    reversed: Arc<Mutex<Option<Arc<Program>>>>,
     #[doc(hidden)]
-    pub prog: Program
+    pub prog: Arc<Program>
 }
 
 #[doc(hidden)]
@@ -187,7 +313,9 @@ pub struct ExNative {
     #[doc(hidden)]
     pub names: &'static &'static [Option<&'static str>],
     #[doc(hidden)]
-    pub prog: fn(MatchKind, &str, usize, usize) -> Vec<Option<usize>>
+    pub groups: &'static &'static [(&'static str, usize)],
+    #[doc(hidden)]
+    pub prog: fn(MatchKind, Anchor, &str, usize, usize) -> Vec<Option<usize>>
 }
 
 impl Copy for ExNative {}
@@ -212,13 +340,15 @@ impl fmt::Debug for Regex {
     }
 }
 
-/// Equality comparison is based on the original string. It is possible that
-/// different regular expressions have the same matching behavior, but are
-/// still compared unequal. For example, `\d+` and `\d\d*` match the same set
-/// of strings, but are not considered equal.
+/// Equality comparison is based on the original string and the modes it was
+/// compiled with (like case insensitivity given as an option or full case
+/// folding). It is possible that different regular expressions have the
+/// same matching behavior, but are still compared unequal. For example,
+/// `\d+` and `\d\d*` match the same set of strings, but are not considered
+/// equal.
 impl PartialEq for Regex {
     fn eq(&self, other: &Regex) -> bool {
-        self.as_str() == other.as_str()
+        self.as_str() == other.as_str() && modes(self) == modes(other)
     }
 }
 
@@ -239,7 +369,7 @@ impl Regex {
     ///
     /// If an invalid expression is given, then an error is returned.
     pub fn new(re: &str) -> Result<Regex, Error> {
-        Regex::with_size_limit(10 * (1 << 20), re)
+        Regex::with_size_limit(DEFAULT_SIZE_LIMIT, re)
     }
 
     /// Compiles a dynamic regular expression with the given size limit.
@@ -250,13 +380,64 @@ impl Regex {
     ///
     /// The default size limit used in `new` is 10MB.
     pub fn with_size_limit(size: usize, re: &str) -> Result<Regex, Error> {
-        let ast = try!(syntax::Expr::parse(re));
-        let (prog, names) = try!(Program::new(ast, size));
-        Ok(Dynamic(ExDynamic {
-            original: re.to_string(),
-            names: names,
-            prog: prog,
-        }))
+        let (ast, spans) = try!(syntax::Expr::parse_with_spans(re));
+        from_ast(re, ast, spans, Modes::default(), size)
+    }
+
+    /// Compiles a dynamic regular expression whose case insensitive parts
+    /// match with full case folding, using the default size limit.
+    ///
+    /// With full case folding, a character may match several characters
+    /// and vice versa, e.g., `(?i)straße` matches `STRASSE` and `(?i)fi`
+    /// matches `ﬁ`. See `regex_syntax::Expr::full_case_fold` for details.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::with_full_case_folding(r"(?i)\bstraße\b").unwrap();
+    /// assert!(re.is_match("Hauptstraße, STRASSE, Straße"));
+    /// assert_eq!(re.find_iter("STRASSE Strasse").count(), 2);
+    /// assert!(!Regex::new(r"(?i)straße").unwrap().is_match("STRASSE"));
+    /// # }
+    /// ```
+    pub fn with_full_case_folding(re: &str) -> Result<Regex, Error> {
+        let modes = Modes { full_case_folding: true, ..Modes::default() };
+        compile_with_options(re, modes, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// Compiles a dynamic regular expression whose literals and classes
+    /// also match canonically equivalent text, using the default size
+    /// limit.
+    ///
+    /// Canonically equivalent text is the same text written with different
+    /// code points, e.g., `é` precomposed (U+00E9) or as `e` followed by a
+    /// combining acute accent (U+0301). The haystack isn't normalized, so
+    /// matches are positions in the original text. See
+    /// `regex_syntax::Expr::canonical_equivalence` for details.
+    ///
+    /// A match never ends between a character and the combining marks
+    /// after it, so `e` doesn't match the start of `e\u{301}`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::with_canonical_equivalence(r"caf(é)").unwrap();
+    /// let text = "café, cafe\u{301}";
+    /// let found: Vec<_> = re.find_iter(text).map(|m| m.as_str()).collect();
+    /// assert_eq!(found, vec!["café", "cafe\u{301}"]);
+    /// assert!(!Regex::new(r"café").unwrap().is_match("cafe\u{301}"));
+    /// # }
+    /// ```
+    pub fn with_canonical_equivalence(re: &str) -> Result<Regex, Error> {
+        let modes = Modes {
+            canonical_equivalence: true,
+            ..Modes::default()
+        };
+        compile_with_options(re, modes, DEFAULT_SIZE_LIMIT)
     }
 
 
@@ -279,8 +460,8 @@ impl Regex {
         has_match(&exec(self, Exists, text))
     }
 
-    /// Returns the start and end byte range of the leftmost-first match in
-    /// `text`. If no match exists, then `None` is returned.
+    /// Returns the leftmost-first match in `text`. If no match exists, then
+    /// `None` is returned.
     ///
     /// Note that this should only be used if you want to discover the position
     /// of the match. Testing the existence of a match is faster if you use
@@ -295,22 +476,23 @@ impl Regex {
     /// # extern crate regex; use regex::Regex;
     /// # fn main() {
     /// let text = "I categorically deny having triskaidekaphobia.";
-    /// let pos = Regex::new(r"\b\w{13}\b").unwrap().find(text);
-    /// assert_eq!(pos, Some((2, 15)));
+    /// let mat = Regex::new(r"\b\w{13}\b").unwrap().find(text).unwrap();
+    /// assert_eq!((mat.start(), mat.end()), (2, 15));
+    /// assert_eq!(mat.as_str(), "categorically");
     /// # }
     /// ```
-    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+    pub fn find<'t>(&self, text: &'t str) -> Option<Match<'t>> {
         let caps = exec(self, Location, text);
         if has_match(&caps) {
-            Some((caps[0].unwrap(), caps[1].unwrap()))
+            Some(Match::new(text, caps[0].unwrap(), caps[1].unwrap()))
         } else {
             None
         }
     }
 
     /// Returns an iterator for each successive non-overlapping match in
-    /// `text`, returning the start and end byte indices with respect to
-    /// `text`.
+    /// `text`. Each match knows its start and end byte indices with respect
+    /// to `text`.
     ///
     /// # Example
     ///
@@ -321,22 +503,123 @@ impl Regex {
     /// # extern crate regex; use regex::Regex;
     /// # fn main() {
     /// let text = "Retroactively relinquishing remunerations is reprehensible.";
-    /// for pos in Regex::new(r"\b\w{13}\b").unwrap().find_iter(text) {
-    ///     println!("{:?}", pos);
+    /// for mat in Regex::new(r"\b\w{13}\b").unwrap().find_iter(text) {
+    ///     println!("{:?}", mat.range());
     /// }
     /// // Output:
-    /// // (0, 13)
-    /// // (14, 27)
-    /// // (28, 41)
-    /// // (45, 58)
+    /// // 0..13
+    /// // 14..27
+    /// // 28..41
+    /// // 45..58
     /// # }
     /// ```
     pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> FindMatches<'r, 't> {
         FindMatches {
             re: self,
             search: text,
-            last_end: 0,
-            last_match: None,
+            matches: Successive::new(text),
+        }
+    }
+
+    /// Returns an iterator over the leftmost-first match at every position
+    /// in `text`, including matches that overlap.
+    ///
+    /// Each match yielded is the one `find` would report if the search began
+    /// at its start. After a match starting at `i`, the search resumes at the
+    /// character following `i` instead of at the end of the match. Matches
+    /// are yielded in order of their start.
+    ///
+    /// To get every match at each start, rather than only the one `find`
+    /// would prefer, call `all_ends` on the iterator.
+    ///
+    /// # Example
+    ///
+    /// Count every occurrence of a motif, even when occurrences overlap:
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"aa").unwrap();
+    /// assert_eq!(re.find_iter("aaaa").count(), 2);
+    /// let starts: Vec<usize> =
+    ///     re.find_overlapping_iter("aaaa").map(|m| m.start()).collect();
+    /// assert_eq!(starts, vec![0, 1, 2]);
+    /// # }
+    /// ```
+    pub fn find_overlapping_iter<'r, 't>(&'r self, text: &'t str)
+                                        -> FindOverlappingMatches<'r, 't> {
+        FindOverlappingMatches {
+            re: self,
+            search: text,
+            next_start: 0,
+            all_ends: None,
+        }
+    }
+
+    /// Returns the last match in `text`, searching from its end. If no match
+    /// exists, then `None` is returned.
+    ///
+    /// A reverse search runs the reversed expression over the reversed text
+    /// with the usual leftmost-first semantics. So the match returned is the
+    /// one that *ends* as far to the right as possible. Among the matches
+    /// ending there, earlier alternatives are still preferred, and greedy
+    /// repetitions extend as far to the *left* as possible.
+    ///
+    /// This is not always the last match yielded by `find_iter`. Since that
+    /// iterator resumes after each match, it can skip over a match that ends
+    /// further right, and a forward search may also prefer a match that ends
+    /// earlier. For example, `b|ab` finds `ab` searching forward in `ab`,
+    /// but `b` searching in reverse.
+    ///
+    /// The reversed expression is compiled by the first reverse search and
+    /// kept with the regex. (For a regex compiled with `regex!`, it's shared
+    /// by every such regex with the same pattern.) The text isn't copied.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"\d+").unwrap();
+    /// let mat = re.rfind("a1b22c333d").unwrap();
+    /// assert_eq!((mat.start(), mat.end()), (6, 9));
+    ///
+    /// let re = Regex::new(r"b|ab").unwrap();
+    /// assert_eq!(re.find("ab").unwrap().as_str(), "ab");
+    /// assert_eq!(re.rfind("ab").unwrap().as_str(), "b");
+    /// # }
+    /// ```
+    pub fn rfind<'t>(&self, text: &'t str) -> Option<Match<'t>> {
+        self.rfind_iter(text).next()
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `text`, starting from its end and moving toward its beginning.
+    ///
+    /// Each match is the one `rfind` would report on the text preceding the
+    /// previous match. See `rfind` for how reverse matches relate to
+    /// forward ones.
+    ///
+    /// The iterator reads `text` backwards rather than copying it. As with
+    /// `rfind`, the reversed expression is only compiled by the first reverse
+    /// search.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"\d+").unwrap();
+    /// let nums: Vec<&str> =
+    ///     re.rfind_iter("a1b22c333d").map(|m| m.as_str()).collect();
+    /// assert_eq!(nums, vec!["333", "22", "1"]);
+    /// # }
+    /// ```
+    pub fn rfind_iter<'t>(&self, text: &'t str) -> RFindMatches<'t> {
+        RFindMatches {
+            prog: self.reversed(),
+            search: text,
+            matches: Successive::new_reversed(text),
         }
     }
 
@@ -396,6 +679,93 @@ impl Regex {
         Captures::new(self, text, caps)
     }
 
+    /// Returns true if and only if the regex matches all of `text`.
+    ///
+    /// This is like surrounding the expression with `\A(?:` and `)\z`, except
+    /// the expression doesn't need to be rewritten. The search is anchored at
+    /// both ends, so it never looks for a match that starts after the
+    /// beginning of `text`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"a|ab").unwrap();
+    /// assert!(re.is_full_match("ab"));
+    /// assert!(!re.is_full_match("abc"));
+    /// assert!(!re.is_full_match("cab"));
+    /// # }
+    /// ```
+    pub fn is_full_match(&self, text: &str) -> bool {
+        has_match(&exec_anchored(self, Exists, AnchoredBoth, text))
+    }
+
+    /// Returns the leftmost-first match that begins at the start of `text`.
+    /// If no such match exists, then `None` is returned.
+    ///
+    /// This is like prefixing the expression with `\A`, except the
+    /// expression doesn't need to be rewritten.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"\d+").unwrap();
+    /// assert_eq!(re.match_prefix("123abc").unwrap().as_str(), "123");
+    /// assert!(re.match_prefix("abc123").is_none());
+    /// # }
+    /// ```
+    pub fn match_prefix<'t>(&self, text: &'t str) -> Option<Match<'t>> {
+        let caps = exec_anchored(self, Location, AnchoredStart, text);
+        if has_match(&caps) {
+            Some(Match::new(text, caps[0].unwrap(), caps[1].unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the capture groups of a match that spans all of `text`. If
+    /// `is_full_match` would return false, then `None` is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"(\w+)=(\w+)").unwrap();
+    /// let caps = re.captures_full("key=value").unwrap();
+    /// assert_eq!(caps.at(1), Some("key"));
+    /// assert_eq!(caps.at(2), Some("value"));
+    /// assert!(re.captures_full("key=value;").is_none());
+    /// # }
+    /// ```
+    pub fn captures_full<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        let caps = exec_anchored(self, Submatches, AnchoredBoth, text);
+        Captures::new(self, text, caps)
+    }
+
+    /// Returns the capture groups of the leftmost-first match that begins at
+    /// the start of `text`. If `match_prefix` would return `None`, then
+    /// `None` is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"(\w+)=").unwrap();
+    /// let caps = re.captures_prefix("key=value").unwrap();
+    /// assert_eq!(caps.at(1), Some("key"));
+    /// assert!(re.captures_prefix(" key=value").is_none());
+    /// # }
+    /// ```
+    pub fn captures_prefix<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        let caps = exec_anchored(self, Submatches, AnchoredStart, text);
+        Captures::new(self, text, caps)
+    }
+
     /// Returns an iterator over all the non-overlapping capture groups matched
     /// in `text`. This is operationally the same as `find_iter` (except it
     /// yields information about submatches).
@@ -425,8 +795,7 @@ impl Regex {
         FindCaptures {
             re: self,
             search: text,
-            last_match: None,
-            last_end: 0,
+            matches: Successive::new(text),
         }
     }
 
@@ -487,12 +856,151 @@ impl Regex {
         }
     }
 
+    /// Returns an iterator of at most `limit` substrings of `text` delimited
+    /// by a match of the regular expression, starting from the end of
+    /// `text`. (A `limit` of `0` will return no substrings.)
+    ///
+    /// This is the reverse of `splitn`: the substrings are yielded from last
+    /// to first, and the remainder at the beginning of the string is the
+    /// last element in the iterator. The delimiters are the matches yielded
+    /// by `rfind_iter`.
+    ///
+    /// This method will *not* copy the text given.
+    ///
+    /// # Example
+    ///
+    /// Split off the last two path components:
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"/+").unwrap();
+    /// let fields: Vec<&str> = re.rsplitn("usr//local/bin/rg", 3).collect();
+    /// assert_eq!(fields, vec!("rg", "bin", "usr//local"));
+    /// # }
+    /// ```
+    pub fn rsplitn<'t>(&self, text: &'t str, limit: usize)
+                      -> RegexRSplitsN<'t> {
+        RegexRSplitsN {
+            finder: self.rfind_iter(text),
+            last: text.len(),
+            cur: 0,
+            limit: limit,
+        }
+    }
+
+    /// Returns an iterator of substrings of `text` delimited by a match of
+    /// the regular expression, with the capture groups of each delimiter
+    /// interleaved between them.
+    ///
+    /// This is like `re.split` in Python. The substrings between delimiters
+    /// are exactly those yielded by `split`, and each one is followed by
+    /// the capture groups `1`, `2`, ... of the delimiter that ends it. A
+    /// capture group that didn't participate in the match is yielded as
+    /// `None`. (If the regex has no capture groups, then this yields the
+    /// same substrings as `split`.)
+    ///
+    /// This method will *not* copy the text given.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"\s*([,;])\s*|(/)").unwrap();
+    /// let fields: Vec<_> = re.split_captures("a , b;c/d").collect();
+    /// assert_eq!(fields, vec![
+    ///     Some("a"), Some(","), None,
+    ///     Some("b"), Some(";"), None,
+    ///     Some("c"), None, Some("/"),
+    ///     Some("d"),
+    /// ]);
+    /// # }
+    /// ```
+    pub fn split_captures<'r, 't>(&'r self, text: &'t str)
+                                 -> RegexSplitsCaptures<'r, 't> {
+        RegexSplitsCaptures {
+            finder: self.captures_iter(text),
+            last: 0,
+            caps: None,
+            group: 0,
+        }
+    }
+
+    /// Returns an iterator of substrings of `text` that each end with a
+    /// match of the regular expression. That is, every delimiter is attached
+    /// to the substring preceding it.
+    ///
+    /// The last substring is the text following the last delimiter, unless
+    /// it is empty.
+    ///
+    /// This method will *not* copy the text given.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"[.!?]+\s*").unwrap();
+    /// let text = "Hi! How are you? Fine.";
+    /// let sentences: Vec<&str> = re.split_inclusive(text).collect();
+    /// assert_eq!(sentences, vec!["Hi! ", "How are you? ", "Fine."]);
+    /// # }
+    /// ```
+    pub fn split_inclusive<'r, 't>(&'r self, text: &'t str)
+                                  -> RegexSplitsInclusive<'r, 't> {
+        RegexSplitsInclusive {
+            finder: self.find_iter(text),
+            last: 0,
+        }
+    }
+
+    /// Returns an iterator over the text between matches of the regular
+    /// expression and the matches themselves, in order.
+    ///
+    /// Text between matches is yielded as `SplitItem::Text` and each match
+    /// is yielded as `SplitItem::Delim` with its capture groups. Empty text
+    /// (e.g., between two adjacent matches) is skipped. Concatenating every
+    /// item in order gives back `text`.
+    ///
+    /// # Example
+    ///
+    /// Tokenize a template with `{{name}}` placeholders in one pass:
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// use regex::SplitItem;
+    ///
+    /// let re = Regex::new(r"\{\{(\w+)\}\}").unwrap();
+    /// let mut out = String::new();
+    /// for item in re.split_delims("Hi {{name}}, you are {{age}}.") {
+    ///     match item {
+    ///         SplitItem::Text(text) => out.push_str(text),
+    ///         SplitItem::Delim(caps) => {
+    ///             out.push_str(&caps.at(1).unwrap().to_uppercase())
+    ///         }
+    ///     }
+    /// }
+    /// assert_eq!(out, "Hi NAME, you are AGE.");
+    /// # }
+    /// ```
+    pub fn split_delims<'r, 't>(&'r self, text: &'t str)
+                               -> RegexSplitsDelims<'r, 't> {
+        RegexSplitsDelims {
+            finder: self.captures_iter(text),
+            last: 0,
+            delim: None,
+        }
+    }
+
     /// Replaces the leftmost-first match with the replacement provided.
     /// The replacement can be a regular string (where `$N` and `$name` are
     /// expanded to match capture groups) or a function that takes the matches'
     /// `Captures` and returns the replaced string.
     ///
-    /// If no match is found, then a copy of the string is returned unchanged.
+    /// If no match is found, then the string is returned unchanged, borrowed
+    /// from `text`. Otherwise, a new string is allocated.
     ///
     /// # Examples
     ///
@@ -540,6 +1048,23 @@ impl Regex {
     /// Note that using `$2` instead of `$first` or `$1` instead of `$last`
     /// would produce the same result. To write a literal `$` use `$$`.
     ///
+    /// A reference may also be written with braces, as in `${first}`, which
+    /// is useful when the reference is immediately followed by text that
+    /// would otherwise be read as part of the name:
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"(?P<first>\w+)\s+(?P<second>\w+)").unwrap();
+    /// let result = re.replace("deep fried", "${first}_$second");
+    /// assert_eq!(result, "deep_fried");
+    /// # }
+    /// ```
+    ///
+    /// When the same replacement is used many times, it can be parsed once
+    /// into a `ReplacementTemplate`, which can also check that every group
+    /// it refers to exists.
+    ///
     /// Finally, sometimes you just want to replace a literal string with no
     /// submatch expansion. This can be done by wrapping a string with
     /// `NoExpand`:
@@ -554,7 +1079,8 @@ impl Regex {
     /// assert_eq!(result, "$2 $last");
     /// # }
     /// ```
-    pub fn replace<R: Replacer>(&self, text: &str, rep: R) -> String {
+    pub fn replace<'t, R: Replacer>(&self, text: &'t str, rep: R)
+                                   -> Cow<'t, str> {
         self.replacen(text, 1, rep)
     }
 
@@ -564,7 +1090,8 @@ impl Regex {
     ///
     /// See the documentation for `replace` for details on how to access
     /// submatches in the replacement string.
-    pub fn replace_all<R: Replacer>(&self, text: &str, rep: R) -> String {
+    pub fn replace_all<'t, R: Replacer>(&self, text: &'t str, rep: R)
+                                       -> Cow<'t, str> {
         self.replacen(text, 0, rep)
     }
 
@@ -574,50 +1101,502 @@ impl Regex {
     ///
     /// See the documentation for `replace` for details on how to access
     /// submatches in the replacement string.
-    pub fn replacen<R: Replacer>
-                   (&self, text: &str, limit: usize, mut rep: R) -> String {
-        let mut new = String::with_capacity(text.len());
-        let mut last_match = 0;
-
-        for (i, cap) in self.captures_iter(text).enumerate() {
-            // It'd be nicer to use the 'take' iterator instead, but it seemed
-            // awkward given that '0' => no limit.
-            if limit > 0 && i >= limit {
-                break
+    pub fn replacen<'t, R: Replacer>
+                   (&self, text: &'t str, limit: usize, rep: R)
+                   -> Cow<'t, str> {
+        let mut new = String::new();
+        let last_match = self.replacen_with(text, limit, rep, |s| {
+            if new.capacity() == 0 {
+                new.reserve(text.len());
+            }
+            new.push_str(s);
+            Ok::<(), fmt::Error>(())
+        }).unwrap();
+        match last_match {
+            None => Cow::Borrowed(text),
+            Some(e) => {
+                new.push_str(&text[e..]);
+                Cow::Owned(new)
             }
-
-            let (s, e) = cap.pos(0).unwrap(); // captures only reports matches
-            new.push_str(&text[last_match..s]);
-            new.push_str(&rep.reg_replace(&cap));
-            last_match = e;
         }
-        new.push_str(&text[last_match..]);
-        return new;
     }
 
-    /// Returns the original string of this regex.
-    pub fn as_str<'a>(&'a self) -> &'a str {
-        match *self {
-            Dynamic(ExDynamic { ref original, .. }) => original,
-            Native(ExNative { ref original, .. }) => original,
+    /// Replaces all non-overlapping matches in `text` with the string returned
+    /// by `rep`, stopping at the first error.
+    ///
+    /// This is like `replace_all` with a closure, except that the closure may
+    /// fail. If it does, then no more matches are searched and its error is
+    /// returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::{Captures, Regex};
+    /// # fn main() {
+    /// use std::num::ParseIntError;
+    ///
+    /// let re = Regex::new(r"\S+").unwrap();
+    /// let double = |caps: &Captures| -> Result<String, ParseIntError> {
+    ///     let n: u32 = try!(caps.at(0).unwrap().parse());
+    ///     Ok((2 * n).to_string())
+    /// };
+    /// assert_eq!(re.try_replace_all("1 2 3", double).unwrap(), "2 4 6");
+    /// assert!(re.try_replace_all("1 two 3", double).is_err());
+    /// # }
+    /// ```
+    pub fn try_replace_all<'t, F, T, E>(&self, text: &'t str, mut rep: F)
+                                       -> Result<Cow<'t, str>, E>
+            where F: FnMut(&Captures) -> Result<T, E>,
+                  T: Into<Cow<'static, str>> {
+        // A replacer can't fail, so the error is kept aside until the
+        // replacement is written, which is where the search is stopped.
+        let failed = RefCell::new(None);
+        let mut new = String::new();
+        let last_match = try!(self.replacen_with(text, 0, |caps: &Captures| {
+            match rep(caps) {
+                Ok(s) => s.into(),
+                Err(err) => {
+                    *failed.borrow_mut() = Some(err);
+                    Cow::Borrowed("")
+                }
+            }
+        }, |s| {
+            if let Some(err) = failed.borrow_mut().take() {
+                return Err(err);
+            }
+            if new.capacity() == 0 {
+                new.reserve(text.len());
+            }
+            new.push_str(s);
+            Ok(())
+        }));
+        match last_match {
+            None => Ok(Cow::Borrowed(text)),
+            Some(e) => {
+                new.push_str(&text[e..]);
+                Ok(Cow::Owned(new))
+            }
         }
     }
 
-    #[doc(hidden)]
-    pub fn names_iter<'a>(&'a self) -> NamesIter<'a> {
-        match *self {
-            Native(ref n) => NamesIterNative(n.names.iter()),
-            Dynamic(ref d) => NamesIterDynamic(d.names.iter())
-        }
+    /// Replaces all non-overlapping matches in `text` with the replacement
+    /// provided and appends the result to `dst`.
+    ///
+    /// This is useful for reusing a single buffer across many replacements.
+    /// Note that `text` is appended to `dst` even if there are no matches.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"\s+").unwrap();
+    /// let mut dst = String::new();
+    /// for line in &["a  b", "c\td"] {
+    ///     re.replace_all_into(line, " ", &mut dst);
+    ///     dst.push('\n');
+    /// }
+    /// assert_eq!(dst, "a b\nc d\n");
+    /// # }
+    /// ```
+    pub fn replace_all_into<R: Replacer>(&self, text: &str, rep: R,
+                                         dst: &mut String) {
+        self.replace_all_fmt(text, rep, dst).unwrap()
     }
 
-    fn names_len(&self) -> usize {
-        match *self {
-            Native(ref n) => n.names.len(),
-            Dynamic(ref d) => d.names.len()
-        }
+    /// Replaces all non-overlapping matches in `text` with the replacement
+    /// provided and writes the result to `dst`.
+    ///
+    /// The result is written piece by piece as matches are found, so it is
+    /// never built in memory as a whole. If writing fails, then the search is
+    /// stopped and the error is returned.
+    pub fn replace_all_fmt<W: fmt::Write, R: Replacer>
+                          (&self, text: &str, rep: R, dst: &mut W)
+                          -> fmt::Result {
+        let last_match =
+            try!(self.replacen_with(text, 0, rep, |s| dst.write_str(s)));
+        dst.write_str(&text[last_match.unwrap_or(0)..])
     }
 
+    /// Replaces all non-overlapping matches in `text` with the replacement
+    /// provided and writes the result as UTF-8 to `dst`.
+    ///
+    /// The result is written piece by piece as matches are found, so it is
+    /// never built in memory as a whole. This makes it possible to rewrite a
+    /// large document straight to a file or socket. (Wrapping `dst` in a
+    /// `std::io::BufWriter` is recommended, since many small writes may be
+    /// issued.) If writing fails, then the search is stopped and the error is
+    /// returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"(?P<n>\d+)").unwrap();
+    /// let mut out: Vec<u8> = vec![];
+    /// re.replace_all_io("1 + 2", "<$n>", &mut out).unwrap();
+    /// assert_eq!(out, b"<1> + <2>");
+    /// # }
+    /// ```
+    pub fn replace_all_io<W: io::Write, R: Replacer>
+                         (&self, text: &str, rep: R, dst: &mut W)
+                         -> io::Result<()> {
+        let last_match = try!(self.replacen_with(text, 0, rep, |s| {
+            dst.write_all(s.as_bytes())
+        }));
+        dst.write_all(text[last_match.unwrap_or(0)..].as_bytes())
+    }
+
+    /// Returns true if and only if the regex matches `text`, unless the
+    /// search is cancelled through `token` first.
+    pub fn is_match_cancellable(&self, text: &str, token: &CancelToken)
+                               -> Result<bool, Cancelled> {
+        match vm::run_cancellable(Exists, Unanchored, &program(self), text, 0,
+                                  text.len(), token.flag()) {
+            None => Err(Cancelled::new(0)),
+            Some(caps) => Ok(has_match(&caps)),
+        }
+    }
+
+    /// Returns the leftmost-first match in `text`, unless the search is
+    /// cancelled through `token` first.
+    pub fn find_cancellable<'t>(&self, text: &'t str, token: &CancelToken)
+                               -> Result<Option<Match<'t>>, Cancelled> {
+        match self.find_iter_cancellable(text, token).next() {
+            None => Ok(None),
+            Some(result) => result.map(Some),
+        }
+    }
+
+    /// Returns an iterator over all the non-overlapping matches in `text`,
+    /// which checks `token` while it searches.
+    ///
+    /// If the token is cancelled, then the iterator yields a `Cancelled`
+    /// error with the position where the search stopped, and then ends.
+    pub fn find_iter_cancellable<'r, 't, 'c>(&'r self, text: &'t str,
+                                             token: &'c CancelToken)
+                                            -> CancellableMatches<'r, 't, 'c> {
+        CancellableMatches {
+            re: self,
+            search: text,
+            token: token,
+            matches: Successive::new(text),
+        }
+    }
+
+    /// Returns an iterator over the capture groups of all the
+    /// non-overlapping matches in `text`, which checks `token` while it
+    /// searches.
+    ///
+    /// If the token is cancelled, then the iterator yields a `Cancelled`
+    /// error with the position where the search stopped, and then ends.
+    pub fn captures_iter_cancellable<'r, 't, 'c>
+                                    (&'r self, text: &'t str,
+                                     token: &'c CancelToken)
+                                    -> CancellableCaptures<'r, 't, 'c> {
+        CancellableCaptures {
+            re: self,
+            search: text,
+            token: token,
+            matches: Successive::new(text),
+        }
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the replacement
+    /// provided, unless the search is cancelled through `token` first.
+    pub fn replace_all_cancellable<'t, R: Replacer>
+                                  (&self, text: &'t str, rep: R,
+                                   token: &CancelToken)
+                                  -> Result<Cow<'t, str>, Cancelled> {
+        let mut new = String::new();
+        let last_match = try!(self.replacen_cancellable_with(
+            text, 0, rep, Some(token), |s| {
+                new.push_str(s);
+                Ok::<(), fmt::Error>(())
+            }).unwrap());
+        match last_match {
+            None => Ok(Cow::Borrowed(text)),
+            Some(e) => {
+                new.push_str(&text[e..]);
+                Ok(Cow::Owned(new))
+            }
+        }
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the replacement
+    /// provided and appends the result to `dst`, unless the search is
+    /// cancelled through `token` first.
+    ///
+    /// If the search is cancelled, then `dst` holds the result for the text
+    /// before the position reported by the error, so the work can be
+    /// resumed from there.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::{Captures, CancelToken, Regex};
+    /// # fn main() {
+    /// let re = Regex::new(r"\d").unwrap();
+    /// let token = CancelToken::new();
+    /// let mut dst = String::new();
+    /// let text = "a1b2";
+    /// let mut n = 0;
+    /// let result = re.replace_all_cancellable_into(text, |_: &Captures| {
+    ///     // Pretend the client disconnects after the first replacement.
+    ///     n += 1;
+    ///     token.cancel();
+    ///     format!("<{}>", n)
+    /// }, &mut dst, &token);
+    /// let pos = result.unwrap_err().position();
+    /// assert_eq!(pos, 2);
+    /// assert_eq!(dst, "a<1>");
+    ///
+    /// // Resume with a fresh token.
+    /// let token = CancelToken::new();
+    /// re.replace_all_cancellable_into(&text[pos..], "#", &mut dst, &token)
+    ///   .unwrap();
+    /// assert_eq!(dst, "a<1>b#");
+    /// # }
+    /// ```
+    pub fn replace_all_cancellable_into<R: Replacer>
+                                       (&self, text: &str, rep: R,
+                                        dst: &mut String, token: &CancelToken)
+                                       -> Result<(), Cancelled> {
+        let last_match = try!(self.replacen_cancellable_with(
+            text, 0, rep, Some(token), |s| {
+                dst.push_str(s);
+                Ok::<(), fmt::Error>(())
+            }).unwrap());
+        dst.push_str(&text[last_match.unwrap_or(0)..]);
+        Ok(())
+    }
+
+    /// Passes every piece of the result of replacing at most `limit` matches
+    /// in `text` to `write`, up to the end of the last match replaced. The
+    /// position of that end is returned, or `None` if nothing matched. (In
+    /// either case, it is up to the caller to write the rest of `text`.)
+    fn replacen_with<R, F, E>(&self, text: &str, limit: usize, rep: R,
+                              write: F) -> Result<Option<usize>, E>
+            where R: Replacer, F: FnMut(&str) -> Result<(), E> {
+        // Without a token, the search can't be cancelled.
+        self.replacen_cancellable_with(text, limit, rep, None, write)
+            .map(|result| result.unwrap())
+    }
+
+    /// Does the same as `replacen_with`, except the search is checked
+    /// against `token`, if one is given.
+    ///
+    /// If the search is cancelled, then the result is passed to `write` up
+    /// to the position where the search stopped, and the error is returned
+    /// instead of the end of the last match.
+    fn replacen_cancellable_with<R, F, E>(&self, text: &str, limit: usize,
+                                          mut rep: R,
+                                          token: Option<&CancelToken>,
+                                          mut write: F)
+                                         -> Result<Result<Option<usize>,
+                                                          Cancelled>, E>
+            where R: Replacer, F: FnMut(&str) -> Result<(), E> {
+        // If the replacement doesn't depend on the match, then there's no
+        // need to find the capture groups.
+        let which = match rep.no_expansion() {
+            Some(_) => Location,
+            None => Submatches,
+        };
+        let mut matches = Successive::new(text);
+        let mut last_match = None;
+        let mut n = 0;
+        // It'd be nicer to use the 'take' iterator instead, but it seemed
+        // awkward given that '0' => no limit.
+        while limit == 0 || n < limit {
+            let next = matches.next(|at| self.search_from(which, text, at,
+                                                          token));
+            let caps = match next {
+                None => break,
+                Some(Ok(caps)) => caps,
+                Some(Err(err)) => {
+                    let e = last_match.unwrap_or(0);
+                    try!(write(&text[e..err.position()]));
+                    return Ok(Err(err))
+                }
+            };
+            let (s, e) = (caps[0].unwrap(), caps[1].unwrap());
+            try!(write(&text[last_match.unwrap_or(0)..s]));
+            match rep.no_expansion() {
+                Some(fixed) => try!(write(&fixed)),
+                None => {
+                    // The locations always describe a match.
+                    let caps = Captures::new(self, text, caps).unwrap();
+                    try!(write(&rep.reg_replace(&caps)));
+                }
+            }
+            last_match = Some(e);
+            n += 1;
+        }
+        Ok(Ok(last_match))
+    }
+
+    /// Searches `text` from byte index `at` to its end, checking `token`
+    /// if one is given. `None` is returned if the search was cancelled.
+    fn search_from(&self, which: MatchKind, text: &str, at: usize,
+                   token: Option<&CancelToken>) -> Option<CaptureLocs> {
+        match token {
+            None => {
+                Some(exec_slice(self, which, Unanchored, text, at, text.len()))
+            }
+            Some(token) => {
+                vm::run_cancellable(which, Unanchored, &program(self), text,
+                                    at, text.len(), token.flag())
+            }
+        }
+    }
+
+    /// Returns the original string of this regex.
+    pub fn as_str<'a>(&'a self) -> &'a str {
+        match *self {
+            Dynamic(ExDynamic { ref original, .. }) => original,
+            Native(ExNative { ref original, .. }) => original,
+        }
+    }
+
+    /// Returns true if the case insensitive parts of this regex match with
+    /// full case folding, i.e., if it was compiled with
+    /// `Regex::with_full_case_folding`.
+    pub fn full_case_folding(&self) -> bool {
+        modes(self).full_case_folding
+    }
+
+    /// Returns true if the literals and classes of this regex also match
+    /// canonically equivalent text, i.e., if it was compiled with
+    /// `Regex::with_canonical_equivalence`.
+    pub fn canonical_equivalence(&self) -> bool {
+        modes(self).canonical_equivalence
+    }
+
+    /// Returns the number of capture groups in this regex, including the
+    /// group `0` that corresponds to the entire match.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"(a)(?:b)(?P<c>c)").unwrap();
+    /// assert_eq!(re.captures_len(), 3);
+    /// # }
+    /// ```
+    pub fn captures_len(&self) -> usize {
+        match *self {
+            Native(ref n) => n.names.len(),
+            Dynamic(ref d) => d.names.len(),
+        }
+    }
+
+    /// Returns an iterator over the names of all capture groups, in order of
+    /// the group index. Unnamed groups (including group `0`) yield `None`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"(?P<y>\d{4})-(\d{2})-(?P<d>\d{2})").unwrap();
+    /// let names: Vec<_> = re.capture_names().collect();
+    /// assert_eq!(names, vec![None, Some("y"), None, Some("d")]);
+    /// # }
+    /// ```
+    pub fn capture_names<'r>(&'r self) -> CaptureNames<'r> {
+        match *self {
+            Native(ref n) => CaptureNames(NamesKind::Native(n.names.iter())),
+            Dynamic(ref d) => {
+                CaptureNames(NamesKind::Dynamic(d.names.iter()))
+            }
+        }
+    }
+
+    /// Returns the index of the capture group named `name`, or `None` if
+    /// there is no such group.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"(?P<y>\d{4})-(\d{2})-(?P<d>\d{2})").unwrap();
+    /// assert_eq!(re.capture_index("d"), Some(3));
+    /// assert_eq!(re.capture_index("m"), None);
+    /// # }
+    /// ```
+    pub fn capture_index(&self, name: &str) -> Option<usize> {
+        self.named_groups().pos(name)
+    }
+
+    /// Returns the byte span of each capture group in the pattern returned
+    /// by `as_str`, indexed by the group index.
+    ///
+    /// A group's span includes its parentheses (and its name, if any). The
+    /// span of group `0` is the entire pattern.
+    ///
+    /// The spans are found when the regex is compiled. (A regex compiled
+    /// with `regex!` parses its pattern again on every call.)
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"(?P<y>\d{4})-(\d{2})").unwrap();
+    /// let spans = re.capture_spans();
+    /// assert_eq!(&re.as_str()[spans[1].clone()], r"(?P<y>\d{4})");
+    /// assert_eq!(&re.as_str()[spans[2].clone()], r"(\d{2})");
+    /// # }
+    /// ```
+    pub fn capture_spans(&self) -> Vec<Range<usize>> {
+        match *self {
+            Dynamic(ExDynamic { ref spans, .. }) => spans.clone(),
+            Native(ExNative { original, .. }) => {
+                // The pattern was already checked by the `regex!` macro.
+                let (_, spans) =
+                    syntax::Expr::parse_with_spans(original).unwrap();
+                spans.into_iter().map(|(s, e)| s..e).collect()
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn names_iter<'a>(&'a self) -> NamesIter<'a> {
+        match *self {
+            Native(ref n) => NamesIterNative(n.names.iter()),
+            Dynamic(ref d) => NamesIterDynamic(d.names.iter())
+        }
+    }
+
+    /// Returns the program that matches the reverse of every string this
+    /// regex matches, which is used to search text backwards.
+    fn reversed(&self) -> Arc<Program> {
+        match *self {
+            Dynamic(ExDynamic { ref expr, ref reversed, modes, .. }) => {
+                let mut reversed = reversed.lock().unwrap();
+                reversed.get_or_insert_with(|| {
+                    // Reversing an expression doesn't change the size of its
+                    // compiled program, which was already checked.
+                    let size = ::std::usize::MAX;
+                    let (mut prog, _) =
+                        Program::new_reversed((**expr).clone(), size).unwrap();
+                    prog.whole_clusters = modes.canonical_equivalence;
+                    Arc::new(prog)
+                }).clone()
+            }
+            Native(ExNative { original, .. }) => native_program(original, true),
+        }
+    }
+
+    fn named_groups(&self) -> NamedGroups {
+        match *self {
+            Native(ref n) => NamedGroups::Native(*n.groups),
+            Dynamic(ref d) => NamedGroups::Dynamic(d.groups.clone()),
+        }
+    }
 }
 
 pub enum NamesIter<'a> {
@@ -636,6 +1615,111 @@ impl<'a> Iterator for NamesIter<'a> {
     }
 }
 
+/// An iterator over the names of all capture groups of a regex.
+///
+/// `'r` is the lifetime of the compiled expression.
+pub struct CaptureNames<'r>(NamesKind<'r>);
+
+enum NamesKind<'r> {
+    Native(slice::Iter<'r, Option<&'static str>>),
+    Dynamic(slice::Iter<'r, Option<String>>),
+}
+
+impl<'r> Iterator for CaptureNames<'r> {
+    type Item = Option<&'r str>;
+
+    fn next(&mut self) -> Option<Option<&'r str>> {
+        match self.0 {
+            NamesKind::Native(ref mut it) => it.next().map(|n| *n),
+            NamesKind::Dynamic(ref mut it) => {
+                it.next().map(|n| n.as_ref().map(|s| &**s))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.0 {
+            NamesKind::Native(ref it) => it.size_hint(),
+            NamesKind::Dynamic(ref it) => it.size_hint(),
+        }
+    }
+}
+
+/// The named capture groups of a regex, mapped to their indices.
+///
+/// This is built once when the regex is compiled and is shared by every
+/// `Captures` value produced by that regex. Names are kept in the order in
+/// which their groups appear in the regex.
+#[derive(Clone)]
+enum NamedGroups {
+    Native(&'static [(&'static str, usize)]),
+    Dynamic(Arc<GroupMap>),
+}
+
+impl NamedGroups {
+    fn pos(&self, name: &str) -> Option<usize> {
+        match *self {
+            NamedGroups::Native(groups) => {
+                groups.iter().find(|&&(n, _)| n == name).map(|&(_, i)| i)
+            }
+            NamedGroups::Dynamic(ref groups) => {
+                groups.index.get(name).map(|&i| i)
+            }
+        }
+    }
+
+    fn iter<'n>(&'n self) -> NamedGroupsIter<'n> {
+        match *self {
+            NamedGroups::Native(groups) => {
+                NamedGroupsIter::Native(groups.iter())
+            }
+            NamedGroups::Dynamic(ref groups) => {
+                NamedGroupsIter::Dynamic(groups.ordered.iter())
+            }
+        }
+    }
+}
+
+/// The name to index map of a dynamic regex.
+struct GroupMap {
+    ordered: Vec<(String, usize)>,
+    index: HashMap<String, usize>,
+}
+
+impl GroupMap {
+    fn new(names: &[Option<String>]) -> GroupMap {
+        let mut ordered = vec![];
+        let mut index = HashMap::new();
+        for (i, name) in names.iter().enumerate() {
+            if let Some(ref name) = *name {
+                ordered.push((name.clone(), i));
+                index.insert(name.clone(), i);
+            }
+        }
+        GroupMap { ordered: ordered, index: index }
+    }
+}
+
+enum NamedGroupsIter<'n> {
+    Native(slice::Iter<'static, (&'static str, usize)>),
+    Dynamic(slice::Iter<'n, (String, usize)>),
+}
+
+impl<'n> Iterator for NamedGroupsIter<'n> {
+    type Item = (&'n str, usize);
+
+    fn next(&mut self) -> Option<(&'n str, usize)> {
+        match *self {
+            NamedGroupsIter::Native(ref mut it) => {
+                it.next().map(|&(name, i)| (name, i))
+            }
+            NamedGroupsIter::Dynamic(ref mut it) => {
+                it.next().map(|&(ref name, i)| (&**name, i))
+            }
+        }
+    }
+}
+
 /// NoExpand indicates literal string replacement.
 ///
 /// It can be used with `replace` and `replace_all` to do a literal
@@ -646,6 +1730,11 @@ impl<'a> Iterator for NamesIter<'a> {
 pub struct NoExpand<'t>(pub &'t str);
 
 /// Replacer describes types that can be used to replace matches in a string.
+///
+/// It is implemented for strings (with `$name` expansion), `NoExpand`,
+/// `ReplacementTemplate` and closures of type `FnMut(&Captures) -> T` where
+/// `T` is `String`, `&'static str` or `Cow<'static, str>`. The string a
+/// closure returns is used as is, without being copied.
 pub trait Replacer {
     /// Returns a possibly owned string that is used to replace the match
     /// corresponding to the `caps` capture group.
@@ -653,6 +1742,52 @@ pub trait Replacer {
     /// The `'a` lifetime refers to the lifetime of a borrowed string when
     /// a new owned string isn't needed (e.g., for `NoExpand`).
     fn reg_replace<'a>(&'a mut self, caps: &Captures) -> Cow<'a, str>;
+
+    /// Returns a fixed replacement string if this replacer doesn't depend on
+    /// the match at all.
+    ///
+    /// When this returns a string, the replacement methods use it for every
+    /// match without calling `reg_replace`, which means capture groups don't
+    /// need to be found. By default, `None` is returned.
+    fn no_expansion<'a>(&'a mut self) -> Option<Cow<'a, str>> {
+        None
+    }
+
+    /// Returns a replacer that borrows this one, so that it can be passed to
+    /// the replacement methods (which take their replacer by value) and then
+    /// be used again.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::{Captures, Regex, Replacer};
+    /// # fn main() {
+    /// let re = Regex::new(r"\d+").unwrap();
+    /// let mut count = 0;
+    /// let mut rep = |_: &Captures| { count += 1; count.to_string() };
+    /// assert_eq!(re.replace_all("a7 b7", rep.by_ref()), "a1 b2");
+    /// assert_eq!(re.replace_all("c7", rep.by_ref()), "c3");
+    /// # }
+    /// ```
+    fn by_ref<'r>(&'r mut self) -> ReplacerRef<'r, Self> {
+        ReplacerRef(self)
+    }
+}
+
+/// A replacer that borrows another replacer. It is created by
+/// `Replacer::by_ref`.
+///
+/// `'a` is the lifetime of the borrow.
+pub struct ReplacerRef<'a, R: ?Sized + 'a>(&'a mut R);
+
+impl<'a, R: Replacer + ?Sized + 'a> Replacer for ReplacerRef<'a, R> {
+    fn reg_replace<'r>(&'r mut self, caps: &Captures) -> Cow<'r, str> {
+        self.0.reg_replace(caps)
+    }
+
+    fn no_expansion<'r>(&'r mut self) -> Option<Cow<'r, str>> {
+        self.0.no_expansion()
+    }
 }
 
 impl<'t> Replacer for NoExpand<'t> {
@@ -660,30 +1795,202 @@ impl<'t> Replacer for NoExpand<'t> {
         let NoExpand(s) = *self;
         Cow::Borrowed(s)
     }
+
+    fn no_expansion<'a>(&'a mut self) -> Option<Cow<'a, str>> {
+        let NoExpand(s) = *self;
+        Some(Cow::Borrowed(s))
+    }
 }
 
 impl<'t> Replacer for &'t str {
     fn reg_replace<'a>(&'a mut self, caps: &Captures) -> Cow<'a, str> {
-        Cow::Owned(caps.expand(*self))
+        let mut dst = String::new();
+        expand_str(caps, *self, &mut dst);
+        Cow::Owned(dst)
+    }
+
+    fn no_expansion<'a>(&'a mut self) -> Option<Cow<'a, str>> {
+        if self.contains('$') {
+            None
+        } else {
+            Some(Cow::Borrowed(*self))
+        }
+    }
+}
+
+impl<F, T> Replacer for F
+        where F: FnMut(&Captures) -> T, T: Into<Cow<'static, str>> {
+    fn reg_replace<'a>(&'a mut self, caps: &Captures) -> Cow<'a, str> {
+        (*self)(caps).into()
+    }
+}
+
+/// Yields all substrings delimited by a regular expression match.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the string being split.
+pub struct RegexSplits<'r, 't> {
+    finder: FindMatches<'r, 't>,
+    last: usize,
+}
+
+impl<'r, 't> Iterator for RegexSplits<'r, 't> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<&'t str> {
+        let text = self.finder.search;
+        match self.finder.next() {
+            None => {
+                if self.last >= text.len() {
+                    None
+                } else {
+                    let s = &text[self.last..];
+                    self.last = text.len();
+                    Some(s)
+                }
+            }
+            Some(m) => {
+                let matched = &text[self.last..m.start()];
+                self.last = m.end();
+                Some(matched)
+            }
+        }
+    }
+}
+
+/// Yields at most `N` substrings delimited by a regular expression match.
+///
+/// The last substring will be whatever remains after splitting.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the string being split.
+pub struct RegexSplitsN<'r, 't> {
+    splits: RegexSplits<'r, 't>,
+    cur: usize,
+    limit: usize,
+}
+
+impl<'r, 't> Iterator for RegexSplitsN<'r, 't> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<&'t str> {
+        let text = self.splits.finder.search;
+        if self.cur >= self.limit {
+            None
+        } else {
+            self.cur += 1;
+            if self.cur >= self.limit {
+                Some(&text[self.splits.last..])
+            } else {
+                self.splits.next()
+            }
+        }
+    }
+}
+
+/// Yields at most `N` substrings delimited by a regular expression match,
+/// starting from the end of the string.
+///
+/// The last substring will be whatever remains at the beginning of the
+/// string after splitting.
+///
+/// `'t` is the lifetime of the string being split.
+pub struct RegexRSplitsN<'t> {
+    finder: RFindMatches<'t>,
+    last: usize,
+    cur: usize,
+    limit: usize,
+}
+
+impl<'t> Iterator for RegexRSplitsN<'t> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<&'t str> {
+        let text = self.finder.search;
+        if self.cur >= self.limit {
+            return None
+        }
+        self.cur += 1;
+        if self.cur >= self.limit {
+            return Some(&text[..self.last])
+        }
+        match self.finder.next() {
+            None => {
+                if self.last == 0 {
+                    None
+                } else {
+                    let s = &text[..self.last];
+                    self.last = 0;
+                    Some(s)
+                }
+            }
+            Some(m) => {
+                let matched = &text[m.end()..self.last];
+                self.last = m.start();
+                Some(matched)
+            }
+        }
     }
 }
 
-impl<F> Replacer for F where F: FnMut(&Captures) -> String {
-    fn reg_replace<'a>(&'a mut self, caps: &Captures) -> Cow<'a, str> {
-        Cow::Owned((*self)(caps))
+/// Yields all substrings delimited by a regular expression match, with the
+/// capture groups of each delimiter interleaved between them.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the string being split.
+pub struct RegexSplitsCaptures<'r, 't> {
+    finder: FindCaptures<'r, 't>,
+    last: usize,
+    caps: Option<Captures<'t>>,
+    group: usize,
+}
+
+impl<'r, 't> Iterator for RegexSplitsCaptures<'r, 't> {
+    type Item = Option<&'t str>;
+
+    fn next(&mut self) -> Option<Option<&'t str>> {
+        if let Some(ref caps) = self.caps {
+            if self.group < caps.len() {
+                self.group += 1;
+                return Some(caps.at(self.group - 1));
+            }
+        }
+        self.caps = None;
+
+        let text = self.finder.search;
+        match self.finder.next() {
+            None => {
+                if self.last >= text.len() {
+                    None
+                } else {
+                    let s = &text[self.last..];
+                    self.last = text.len();
+                    Some(Some(s))
+                }
+            }
+            Some(caps) => {
+                let (s, e) = caps.pos(0).unwrap();
+                let matched = &text[self.last..s];
+                self.last = e;
+                self.caps = Some(caps);
+                self.group = 1;
+                Some(Some(matched))
+            }
+        }
     }
 }
 
-/// Yields all substrings delimited by a regular expression match.
+/// Yields all substrings that end with a regular expression match, followed
+/// by the rest of the string.
 ///
 /// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
 /// of the string being split.
-pub struct RegexSplits<'r, 't> {
+pub struct RegexSplitsInclusive<'r, 't> {
     finder: FindMatches<'r, 't>,
     last: usize,
 }
 
-impl<'r, 't> Iterator for RegexSplits<'r, 't> {
+impl<'r, 't> Iterator for RegexSplitsInclusive<'r, 't> {
     type Item = &'t str;
 
     fn next(&mut self) -> Option<&'t str> {
@@ -698,45 +2005,114 @@ impl<'r, 't> Iterator for RegexSplits<'r, 't> {
                     Some(s)
                 }
             }
-            Some((s, e)) => {
-                let matched = &text[self.last..s];
-                self.last = e;
+            Some(m) => {
+                let matched = &text[self.last..m.end()];
+                self.last = m.end();
                 Some(matched)
             }
         }
     }
 }
 
-/// Yields at most `N` substrings delimited by a regular expression match.
+/// A piece of text yielded by `Regex::split_delims`.
 ///
-/// The last substring will be whatever remains after splitting.
+/// `'t` is the lifetime of the string being split.
+pub enum SplitItem<'t> {
+    /// Text between two matches (or before the first match or after the last
+    /// match). It is never empty.
+    Text(&'t str),
+    /// A match of the regular expression, with its capture groups.
+    Delim(Captures<'t>),
+}
+
+/// Yields the text between regular expression matches and the matches
+/// themselves, in order.
 ///
 /// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
 /// of the string being split.
-pub struct RegexSplitsN<'r, 't> {
-    splits: RegexSplits<'r, 't>,
-    cur: usize,
-    limit: usize,
+pub struct RegexSplitsDelims<'r, 't> {
+    finder: FindCaptures<'r, 't>,
+    last: usize,
+    delim: Option<Captures<'t>>,
 }
 
-impl<'r, 't> Iterator for RegexSplitsN<'r, 't> {
-    type Item = &'t str;
+impl<'r, 't> Iterator for RegexSplitsDelims<'r, 't> {
+    type Item = SplitItem<'t>;
 
-    fn next(&mut self) -> Option<&'t str> {
-        let text = self.splits.finder.search;
-        if self.cur >= self.limit {
-            None
-        } else {
-            self.cur += 1;
-            if self.cur >= self.limit {
-                Some(&text[self.splits.last..])
-            } else {
-                self.splits.next()
+    fn next(&mut self) -> Option<SplitItem<'t>> {
+        if let Some(caps) = self.delim.take() {
+            return Some(SplitItem::Delim(caps));
+        }
+        let text = self.finder.search;
+        match self.finder.next() {
+            None => {
+                if self.last >= text.len() {
+                    None
+                } else {
+                    let s = &text[self.last..];
+                    self.last = text.len();
+                    Some(SplitItem::Text(s))
+                }
+            }
+            Some(caps) => {
+                let (s, e) = caps.pos(0).unwrap();
+                let before = &text[self.last..s];
+                self.last = e;
+                if before.is_empty() {
+                    Some(SplitItem::Delim(caps))
+                } else {
+                    self.delim = Some(caps);
+                    Some(SplitItem::Text(before))
+                }
             }
         }
     }
 }
 
+/// A single match of a regular expression in some text.
+///
+/// A `Match` knows its start and end byte indices with respect to the text
+/// that was searched, and can therefore return the matched substring without
+/// any slicing by the caller.
+///
+/// `'t` is the lifetime of the matched text.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Match<'t> {
+    text: &'t str,
+    start: usize,
+    end: usize,
+}
+
+impl<'t> Match<'t> {
+    fn new(text: &'t str, start: usize, end: usize) -> Match<'t> {
+        Match { text: text, start: start, end: end }
+    }
+
+    /// Returns the starting byte offset of the match in the text searched.
+    #[inline]
+    pub fn start(&self) -> usize { self.start }
+
+    /// Returns the ending byte offset of the match in the text searched.
+    #[inline]
+    pub fn end(&self) -> usize { self.end }
+
+    /// Returns the range of byte offsets spanned by the match.
+    #[inline]
+    pub fn range(&self) -> Range<usize> { self.start..self.end }
+
+    /// Returns the matched text.
+    #[inline]
+    pub fn as_str(&self) -> &'t str { &self.text[self.start..self.end] }
+
+    /// Returns the length, in bytes, of the matched text.
+    #[inline]
+    pub fn len(&self) -> usize { self.end - self.start }
+
+    /// Returns true if and only if the match is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.start == self.end }
+}
+
 /// Captures represents a group of captured strings for a single match.
 ///
 /// The 0th capture always corresponds to the entire match. Each subsequent
@@ -747,11 +2123,15 @@ impl<'r, 't> Iterator for RegexSplitsN<'r, 't> {
 ///
 /// Positions returned from a capture group are always byte indices.
 ///
+/// Captures can also be indexed by group number or name, e.g., `caps[1]` or
+/// `caps["year"]`. Indexing panics if the group doesn't exist or didn't
+/// participate in the match.
+///
 /// `'t` is the lifetime of the matched text.
 pub struct Captures<'t> {
     text: &'t str,
     locs: CaptureLocs,
-    named: Option<HashMap<String, usize>>,
+    named: NamedGroups,
 }
 
 impl<'t> Captures<'t> {
@@ -760,26 +2140,10 @@ impl<'t> Captures<'t> {
         if !has_match(&locs) {
             return None
         }
-
-        let named =
-            if re.names_len() == 0 {
-                None
-            } else {
-                let mut named = HashMap::new();
-                for (i, name) in re.names_iter().enumerate() {
-                    match name {
-                        None => {},
-                        Some(name) => {
-                            named.insert(name, i);
-                        }
-                    }
-                }
-                Some(named)
-            };
         Some(Captures {
             text: search,
             locs: locs,
-            named: named,
+            named: re.named_groups(),
         })
     }
 
@@ -797,29 +2161,49 @@ impl<'t> Captures<'t> {
         Some((self.locs[s].unwrap(), self.locs[e].unwrap()))
     }
 
+    /// Returns the match for the capture group `i`. If `i` isn't a valid
+    /// capture group or didn't match anything, then `None` is returned.
+    pub fn get(&self, i: usize) -> Option<Match<'t>> {
+        self.pos(i).map(|(s, e)| Match::new(self.text, s, e))
+    }
+
     /// Returns the matched string for the capture group `i`.  If `i` isn't
     /// a valid capture group or didn't match anything, then `None` is
     /// returned.
     pub fn at(&self, i: usize) -> Option<&'t str> {
-        match self.pos(i) {
-            None => None,
-            Some((s, e)) => Some(&self.text[s..e])
-        }
+        self.get(i).map(|m| m.as_str())
+    }
+
+    /// Returns the match for the capture group named `name`. If `name` isn't
+    /// a valid capture group or didn't match anything, then `None` is
+    /// returned.
+    pub fn get_name(&self, name: &str) -> Option<Match<'t>> {
+        self.named.pos(name).and_then(|i| self.get(i))
     }
 
     /// Returns the matched string for the capture group named `name`.  If
     /// `name` isn't a valid capture group or didn't match anything, then
     /// `None` is returned.
     pub fn name(&self, name: &str) -> Option<&'t str> {
-        match self.named {
-            None => None,
-            Some(ref h) => {
-                match h.get(name) {
-                    None => None,
-                    Some(i) => self.at(*i),
-                }
-            }
-        }
+        self.get_name(name).map(|m| m.as_str())
+    }
+
+    /// Returns true if and only if the regex has a capture group `i`,
+    /// regardless of whether it participated in this match.
+    ///
+    /// Together with `get`, this tells apart a group that doesn't exist from
+    /// a group that didn't match anything.
+    pub fn has_group(&self, i: usize) -> bool {
+        i < self.len()
+    }
+
+    /// Returns true if and only if the regex has a capture group named
+    /// `name`, regardless of whether it participated in this match.
+    ///
+    /// Together with `get_name`, this tells apart a group that doesn't exist
+    /// from a group that didn't match anything.
+    pub fn has_name(&self, name: &str) -> bool {
+        self.named.pos(name).is_some()
     }
 
     /// Creates an iterator of all the capture groups in order of appearance
@@ -836,10 +2220,10 @@ impl<'t> Captures<'t> {
     }
 
     /// Creates an iterator of all named groups as an tuple with the group
-    /// name and the value. The iterator returns these values in arbitrary
-    /// order.
+    /// name and the value. The iterator returns these values in order of
+    /// appearance in the regular expression.
     pub fn iter_named(&'t self) -> SubCapturesNamed<'t> {
-        SubCapturesNamed { caps: self, inner: self.named.as_ref().map(|n| n.iter()) }
+        SubCapturesNamed { caps: self, inner: self.named.iter() }
     }
 
     /// Expands all instances of `$name` in `text` to the corresponding capture
@@ -848,33 +2232,20 @@ impl<'t> Captures<'t> {
     /// `name` may be an integer corresponding to the index of the
     /// capture group (counted by order of opening parenthesis where `0` is the
     /// entire match) or it can be a name (consisting of letters, digits or
-    /// underscores) corresponding to a named capture group.
+    /// underscores) corresponding to a named capture group. The name may be
+    /// enclosed in braces, as in `${name}`, to separate it from the text that
+    /// follows.
     ///
     /// If `name` isn't a valid capture group (whether the name doesn't exist or
     /// isn't a valid index), then it is replaced with the empty string.
     ///
     /// To write a literal `$` use `$$`.
+    ///
+    /// See `ReplacementTemplate` for a way to parse `text` only once.
     pub fn expand(&self, text: &str) -> String {
-        // How evil can you get?
-        let re = Regex::new(r"(?x)
-          (?P<before>^|\b|[^$]) # Ignore `$$name`.
-          \$
-          (?P<name> # Match the actual capture name. Can be...
-            [0-9]+  # A sequence of digits (for indexed captures), or...
-            |
-            [_a-zA-Z][_0-9a-zA-Z]* # A name for named captures.
-          )
-        ").unwrap();
-        let text = re.replace_all(text, |refs: &Captures| -> String {
-            let before = refs.name("before").unwrap_or("");
-            let name = refs.name("name").unwrap_or("");
-            format!("{}{}", before, match name.parse::<usize>() {
-                Err(_) => self.name(name).unwrap_or("").to_string(),
-                Ok(i) => self.at(i).unwrap_or("").to_string(),
-            })
-        });
-        let re = Regex::new(r"\$\$").unwrap();
-        re.replace_all(&text, NoExpand("$"))
+        let mut dst = String::with_capacity(text.len());
+        expand_str(self, text, &mut dst);
+        dst
     }
 
     /// Returns the number of captured groups.
@@ -886,6 +2257,46 @@ impl<'t> Captures<'t> {
     pub fn is_empty(&self) -> bool { self.len() == 0 }
 }
 
+/// Get a group by index.
+///
+/// # Panics
+///
+/// If there is no group at the given index, or if the group didn't
+/// participate in the match.
+impl<'t> Index<usize> for Captures<'t> {
+    type Output = str;
+
+    fn index(&self, i: usize) -> &str {
+        match self.at(i) {
+            Some(text) => text,
+            None if self.has_group(i) => {
+                panic!("group {} did not participate in the match", i)
+            }
+            None => panic!("no group at index '{}'", i),
+        }
+    }
+}
+
+/// Get a group by name.
+///
+/// # Panics
+///
+/// If there is no group with the given name, or if the group didn't
+/// participate in the match.
+impl<'t, 'i> Index<&'i str> for Captures<'t> {
+    type Output = str;
+
+    fn index(&self, name: &'i str) -> &str {
+        match self.name(name) {
+            Some(text) => text,
+            None if self.has_name(name) => {
+                panic!("group '{}' did not participate in the match", name)
+            }
+            None => panic!("no group named '{}'", name),
+        }
+    }
+}
+
 /// An iterator over capture groups for a particular match of a regular
 /// expression.
 ///
@@ -935,20 +2346,19 @@ impl<'t> Iterator for SubCapturesPos<'t> {
 /// An Iterator over named capture groups as a tuple with the group
 /// name and the value.
 ///
+/// The groups are yielded in order of appearance in the regular expression.
+///
 /// `'t` is the lifetime of the matched text.
 pub struct SubCapturesNamed<'t>{
     caps: &'t Captures<'t>,
-    inner: Option<Iter<'t, String, usize>>,
+    inner: NamedGroupsIter<'t>,
 }
 
 impl<'t> Iterator for SubCapturesNamed<'t> {
     type Item = (&'t str, Option<&'t str>);
 
     fn next(&mut self) -> Option<(&'t str, Option<&'t str>)> {
-        match self.inner.as_mut().map(|it| it.next()).unwrap_or(None) {
-            Some((name, pos)) => Some((name, self.caps.at(*pos))),
-            None => None
-        }
+        self.inner.next().map(|(name, i)| (name, self.caps.at(i)))
     }
 }
 
@@ -962,88 +2372,295 @@ impl<'t> Iterator for SubCapturesNamed<'t> {
 pub struct FindCaptures<'r, 't> {
     re: &'r Regex,
     search: &'t str,
-    last_match: Option<usize>,
-    last_end: usize,
+    matches: Successive<'t>,
 }
 
 impl<'r, 't> Iterator for FindCaptures<'r, 't> {
     type Item = Captures<'t>;
 
     fn next(&mut self) -> Option<Captures<'t>> {
-        if self.last_end > self.search.len() {
-            return None
+        let (re, text) = (self.re, self.search);
+        match self.matches.next(|at| re.search_from(Submatches, text, at,
+                                                    None)) {
+            Some(Ok(caps)) => Captures::new(re, text, caps),
+            _ => None,
         }
+    }
+}
 
-        let caps = exec_slice(self.re, Submatches, self.search,
-                              self.last_end, self.search.len());
-        let (s, e) =
-            if !has_match(&caps) {
-                return None
-            } else {
-                (caps[0].unwrap(), caps[1].unwrap())
-            };
+/// Finds successive non-overlapping matches in some text. All of the
+/// iterators over such matches are built on it.
+///
+/// Every search begins where the last match ended, but an empty match that
+/// immediately follows a match isn't accepted, so that iteration always
+/// makes progress.
+///
+/// A search may also read the text backwards, from its end. The positions
+/// it keeps are then offsets from the end of the text, but the locations
+/// it returns are byte indices as usual.
+pub struct Successive<'t, H: ?Sized + 't = str> {
+    input: &'t H,
+    reverse: bool,
+    // Where the next search begins, or past the end of the text once the
+    // iteration is over.
+    next: usize,
+    last_match: Option<usize>,
+}
+
+impl<'t, H: ?Sized + Haystack> Successive<'t, H> {
+    /// Starts a search from the beginning of `input`.
+    pub fn new(input: &'t H) -> Successive<'t, H> {
+        Successive { input: input, reverse: false, next: 0, last_match: None }
+    }
+
+    /// Starts a search that reads `input` backwards, from its end.
+    pub fn new_reversed(input: &'t H) -> Successive<'t, H> {
+        Successive { reverse: true, ..Successive::new(input) }
+    }
 
-        // Don't accept empty matches immediately following a match.
-        // i.e., no infinite loops please.
-        if e == s && Some(self.last_end) == self.last_match {
-            if self.last_end >= self.search.len() {
-                return None;
+    /// Returns the locations of the next match, which `search` finds when
+    /// it's called with the position where the search begins. (For a
+    /// reverse search, it's an offset from the end of the text.)
+    ///
+    /// If `search` returns `None` because it was cancelled, then an error
+    /// is returned with the position where it began and the iteration ends.
+    pub fn next<F>(&mut self, mut search: F)
+                  -> Option<Result<CaptureLocs, Cancelled>>
+            where F: FnMut(usize) -> Option<CaptureLocs> {
+        let len = self.input.len();
+        while self.next <= len {
+            let caps = match search(self.next) {
+                None => {
+                    let err = Cancelled::new(self.next);
+                    // Stop for good after reporting the cancellation.
+                    self.next = len + 1;
+                    return Some(Err(err))
+                }
+                Some(caps) => caps,
+            };
+            if !has_match(&caps) {
+                break
             }
-            self.last_end += self.search[self.last_end..].chars()
-                                 .next().unwrap().len_utf8();
-            return self.next()
+            let (s, e) = (caps[0].unwrap(), caps[1].unwrap());
+            let (s, e) = if self.reverse { (len - e, len - s) } else { (s, e) };
+
+            // Don't accept empty matches immediately following a match.
+            // i.e., no infinite loops please.
+            if e == s && Some(self.next) == self.last_match {
+                if self.next >= len {
+                    break
+                }
+                let mut chars = if self.reverse {
+                    CharReader::new_reversed(self.input)
+                } else {
+                    CharReader::new(self.input)
+                };
+                self.next = chars.set(self.next);
+                continue
+            }
+            self.next = e;
+            self.last_match = Some(e);
+            return Some(Ok(caps))
         }
-        self.last_end = e;
-        self.last_match = Some(self.last_end);
-        Captures::new(self.re, self.search, caps)
+        self.next = len + 1;
+        None
+    }
+}
+
+/// An iterator over all non-overlapping matches for a particular string,
+/// which can be cancelled.
+///
+/// `'r` is the lifetime of the compiled expression, `'t` is the lifetime of
+/// the matched string and `'c` is the lifetime of the cancellation token.
+pub struct CancellableMatches<'r, 't, 'c> {
+    re: &'r Regex,
+    search: &'t str,
+    token: &'c CancelToken,
+    matches: Successive<'t>,
+}
+
+impl<'r, 't, 'c> Iterator for CancellableMatches<'r, 't, 'c> {
+    type Item = Result<Match<'t>, Cancelled>;
+
+    fn next(&mut self) -> Option<Result<Match<'t>, Cancelled>> {
+        let (re, text, token) = (self.re, self.search, self.token);
+        self.matches.next(|at| re.search_from(Location, text, at, Some(token)))
+                    .map(|r| r.map(|caps| {
+            Match::new(text, caps[0].unwrap(), caps[1].unwrap())
+        }))
+    }
+}
+
+/// An iterator over the capture groups of all non-overlapping matches for a
+/// particular string, which can be cancelled.
+///
+/// `'r` is the lifetime of the compiled expression, `'t` is the lifetime of
+/// the matched string and `'c` is the lifetime of the cancellation token.
+pub struct CancellableCaptures<'r, 't, 'c> {
+    re: &'r Regex,
+    search: &'t str,
+    token: &'c CancelToken,
+    matches: Successive<'t>,
+}
+
+impl<'r, 't, 'c> Iterator for CancellableCaptures<'r, 't, 'c> {
+    type Item = Result<Captures<'t>, Cancelled>;
+
+    fn next(&mut self) -> Option<Result<Captures<'t>, Cancelled>> {
+        let (re, text, token) = (self.re, self.search, self.token);
+        self.matches.next(|at| {
+            re.search_from(Submatches, text, at, Some(token))
+        }).map(|r| r.map(|caps| {
+            // The locations always describe a match.
+            Captures::new(re, text, caps).unwrap()
+        }))
     }
 }
 
 /// An iterator over all non-overlapping matches for a particular string.
 ///
-/// The iterator yields a tuple of integers corresponding to the start and end
-/// of the match. The indices are byte offsets. The iterator stops when no more
-/// matches can be found.
+/// The iterator yields a `Match` for each match found. Its indices are byte
+/// offsets. The iterator stops when no more matches can be found.
 ///
 /// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
 /// of the matched string.
 pub struct FindMatches<'r, 't> {
     re: &'r Regex,
     search: &'t str,
-    last_match: Option<usize>,
-    last_end: usize,
+    matches: Successive<'t>,
 }
 
 impl<'r, 't> Iterator for FindMatches<'r, 't> {
-    type Item = (usize, usize);
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        let (re, text) = (self.re, self.search);
+        match self.matches.next(|at| re.search_from(Location, text, at,
+                                                    None)) {
+            Some(Ok(caps)) => {
+                Some(Match::new(text, caps[0].unwrap(), caps[1].unwrap()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An iterator over the leftmost-first match at every position of a string.
+///
+/// The iterator yields a `Match` for each match found, in order of their
+/// start. Matches may overlap.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct FindOverlappingMatches<'r, 't> {
+    re: &'r Regex,
+    search: &'t str,
+    next_start: usize,
+    // Set by `all_ends`: the program to run at each start, along with the
+    // start and the ends of the matches there that are left to yield.
+    all_ends: Option<(Arc<Program>, usize, vec::IntoIter<usize>)>,
+}
+
+impl<'r, 't> FindOverlappingMatches<'r, 't> {
+    /// Makes the iterator yield every match at each start position, instead
+    /// of only the leftmost-first one.
+    ///
+    /// The matches at a start are yielded from shortest to longest, with one
+    /// match for every position where the regex can end. For example,
+    /// `a|ab|abc` yields `a`, `ab` and `abc` at the start of `abc`, where
+    /// `find` only reports `a`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate regex; use regex::Regex;
+    /// # fn main() {
+    /// let re = Regex::new(r"a+").unwrap();
+    /// let ms: Vec<&str> = re.find_overlapping_iter("aa")
+    ///                       .all_ends()
+    ///                       .map(|m| m.as_str())
+    ///                       .collect();
+    /// assert_eq!(ms, vec!["a", "aa", "a"]);
+    /// # }
+    /// ```
+    pub fn all_ends(mut self) -> FindOverlappingMatches<'r, 't> {
+        if self.all_ends.is_none() {
+            self.all_ends = Some((program(self.re), 0, vec![].into_iter()));
+        }
+        self
+    }
+}
 
-    fn next(&mut self) -> Option<(usize, usize)> {
-        if self.last_end > self.search.len() {
+impl<'r, 't> Iterator for FindOverlappingMatches<'r, 't> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        if let Some((_, s, ref mut ends)) = self.all_ends {
+            if let Some(e) = ends.next() {
+                return Some(Match::new(self.search, s, e))
+            }
+        }
+        if self.next_start > self.search.len() {
             return None
         }
 
-        let caps = exec_slice(self.re, Location, self.search,
-                              self.last_end, self.search.len());
-        let (s, e) =
-            if !has_match(&caps) {
-                return None
-            } else {
-                (caps[0].unwrap(), caps[1].unwrap())
-            };
+        // The leftmost-first match of an unanchored search begins at the
+        // first position that has a match at all, and it's the same match an
+        // anchored search at that position would find. So positions without
+        // a match are skipped in a single search.
+        let caps = exec_slice(self.re, Location, Unanchored, self.search,
+                              self.next_start, self.search.len());
+        if !has_match(&caps) {
+            self.next_start = self.search.len() + 1;
+            return None
+        }
+        let (s, e) = (caps[0].unwrap(), caps[1].unwrap());
+        self.next_start = match self.search[s..].chars().next() {
+            None => s + 1,
+            Some(c) => s + c.len_utf8(),
+        };
+        if let Some((ref prog, ref mut start, ref mut ends)) = self.all_ends {
+            // There's at least one match at `s`, so there's at least one end.
+            let mut all = vm::run_all_ends(prog, self.search, s).into_iter();
+            let first = all.next().unwrap();
+            *start = s;
+            *ends = all;
+            return Some(Match::new(self.search, s, first))
+        }
+        Some(Match::new(self.search, s, e))
+    }
+}
+
+/// An iterator over all non-overlapping matches for a particular string,
+/// starting from its end.
+///
+/// The iterator yields a `Match` for each match found, from last to first.
+/// Its indices are byte offsets into the original string. The iterator stops
+/// when no more matches can be found.
+///
+/// `'t` is the lifetime of the matched string.
+pub struct RFindMatches<'t> {
+    // The reversed program, which reads `search` backwards.
+    prog: Arc<Program>,
+    search: &'t str,
+    matches: Successive<'t>,
+}
 
-        // Don't accept empty matches immediately following a match.
-        // i.e., no infinite loops please.
-        if e == s && Some(self.last_end) == self.last_match {
-            if self.last_end >= self.search.len() {
-                return None;
+impl<'t> Iterator for RFindMatches<'t> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        let (prog, text) = (&self.prog, self.search);
+        let next = self.matches.next(|at| {
+            Some(vm::run_reversed(Location, Unanchored, prog, text, 0,
+                                  text.len() - at))
+        });
+        match next {
+            Some(Ok(caps)) => {
+                Some(Match::new(text, caps[0].unwrap(), caps[1].unwrap()))
             }
-            self.last_end += self.search[self.last_end..].chars()
-                                 .next().unwrap().len_utf8();
-            return self.next()
+            _ => None,
         }
-        self.last_end = e;
-        self.last_match = Some(self.last_end);
-        Some((s, e))
     }
 }
 
@@ -1052,6 +2669,11 @@ pub struct RegexSearcher<'r, 't> {
     it: FindMatches<'r, 't>,
     last_step_end: usize,
     next_match: Option<(usize, usize)>,
+    // The reverse iterator is only created once it's needed, since that may
+    // require compiling the reversed expression.
+    rev: Option<RFindMatches<'t>>,
+    last_step_start: usize,
+    next_match_back: Option<(usize, usize)>,
 }
 
 #[cfg(feature = "pattern")]
@@ -1063,6 +2685,9 @@ impl<'r, 't> Pattern<'t> for &'r Regex {
             it: self.find_iter(haystack),
             last_step_end: 0,
             next_match: None,
+            rev: None,
+            last_step_start: haystack.len(),
+            next_match_back: None,
         }
     }
 }
@@ -1091,7 +2716,8 @@ unsafe impl<'r, 't> Searcher<'t> for RegexSearcher<'r, 't> {
                     SearchStep::Done
                 }
             }
-            Some((s, e)) => {
+            Some(m) => {
+                let (s, e) = (m.start(), m.end());
                 if s == self.last_step_end {
                     self.last_step_end = e;
                     SearchStep::Match(s, e)
@@ -1106,18 +2732,117 @@ unsafe impl<'r, 't> Searcher<'t> for RegexSearcher<'r, 't> {
     }
 }
 
+#[cfg(feature = "pattern")]
+unsafe impl<'r, 't> ReverseSearcher<'t> for RegexSearcher<'r, 't> {
+    #[inline]
+    fn next_back(&mut self) -> SearchStep {
+        if let Some((s, e)) = self.next_match_back {
+            self.next_match_back = None;
+            self.last_step_start = s;
+            return SearchStep::Match(s, e);
+        }
+        if self.rev.is_none() {
+            self.rev = Some(self.it.re.rfind_iter(self.it.search));
+        }
+        match self.rev.as_mut().unwrap().next() {
+            None => {
+                if self.last_step_start > 0 {
+                    let last = self.last_step_start;
+                    self.last_step_start = 0;
+                    SearchStep::Reject(0, last)
+                } else {
+                    SearchStep::Done
+                }
+            }
+            Some(m) => {
+                let (s, e) = (m.start(), m.end());
+                if e == self.last_step_start {
+                    self.last_step_start = s;
+                    SearchStep::Match(s, e)
+                } else {
+                    self.next_match_back = Some((s, e));
+                    let last = self.last_step_start;
+                    self.last_step_start = e;
+                    SearchStep::Reject(e, last)
+                }
+            }
+        }
+    }
+}
+
 fn exec(re: &Regex, which: MatchKind, input: &str) -> CaptureLocs {
-    exec_slice(re, which, input, 0, input.len())
+    exec_slice(re, which, Unanchored, input, 0, input.len())
+}
+
+fn exec_anchored(re: &Regex, which: MatchKind, anchor: Anchor,
+                 input: &str) -> CaptureLocs {
+    exec_slice(re, which, anchor, input, 0, input.len())
 }
 
-fn exec_slice(re: &Regex, which: MatchKind,
+fn exec_slice(re: &Regex, which: MatchKind, anchor: Anchor,
               input: &str, s: usize, e: usize) -> CaptureLocs {
     match *re {
-        Dynamic(ExDynamic { ref prog, .. }) => vm::run(which, prog, input, s, e),
-        Native(ExNative { ref prog, .. }) => (*prog)(which, input, s, e),
+        Dynamic(ExDynamic { ref prog, .. }) => {
+            vm::run(which, anchor, prog, input, s, e)
+        }
+        Native(ExNative { ref prog, .. }) => {
+            (*prog)(which, anchor, input, s, e)
+        }
+    }
+}
+
+/// Returns the expression a regex was compiled from.
+pub fn expr(re: &Regex) -> syntax::Expr {
+    match *re {
+        Dynamic(ExDynamic { ref expr, .. }) => (**expr).clone(),
+        Native(ExNative { original, .. }) => {
+            // The pattern was already checked by the `regex!` macro.
+            syntax::Expr::parse(original).unwrap()
+        }
+    }
+}
+
+/// Returns the modes a regex was compiled with.
+pub fn modes(re: &Regex) -> Modes {
+    match *re {
+        Dynamic(ExDynamic { modes, .. }) => modes,
+        Native(_) => Modes::default(),
     }
 }
 
+/// Returns the compiled program of a regex.
+pub fn program(re: &Regex) -> Arc<Program> {
+    match *re {
+        Dynamic(ExDynamic { ref prog, .. }) => prog.clone(),
+        Native(ExNative { original, .. }) => native_program(original, false),
+    }
+}
+
+/// Returns the program of the native regex with the pattern `original`,
+/// or its reversed program if `reversed` is true.
+///
+/// A native regex doesn't keep a program around, so one is compiled from
+/// the pattern the first time it's needed. It's then shared by every
+/// native regex with the same pattern.
+fn native_program(original: &'static str, reversed: bool) -> Arc<Program> {
+    type Programs = HashMap<(&'static str, bool), Arc<Program>>;
+    static PROGRAMS: Mutex<Option<Programs>> = Mutex::new(None);
+
+    let mut progs = PROGRAMS.lock().unwrap();
+    let progs = progs.get_or_insert_with(HashMap::new);
+    progs.entry((original, reversed)).or_insert_with(|| {
+        // The pattern was already checked by the `regex!` macro.
+        let ast = syntax::Expr::parse(original).unwrap();
+        let size = ::std::usize::MAX;
+        let (prog, _) = if reversed {
+            Program::new_reversed(ast, size).unwrap()
+        } else {
+            Program::new(ast, size).unwrap()
+        };
+        Arc::new(prog)
+    }).clone()
+}
+
 #[inline]
 fn has_match(caps: &CaptureLocs) -> bool {
     caps.len() >= 2 && caps[0].is_some() && caps[1].is_some()
@@ -0,0 +1,280 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::ops::Deref;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use re::{DEFAULT_SIZE_LIMIT, Error, Modes, Regex, compile_with_options,
+         modes};
+
+/// A `Regex` serializes as its pattern, i.e., the string returned by
+/// `as_str`.
+///
+/// A regex compiled with a mode that isn't part of its pattern (like case
+/// insensitivity given as an option, full case folding or canonical
+/// equivalence) serializes as a map instead, with the `pattern` and a field
+/// for each mode, as in `RegexOptions`.
+impl Serialize for Regex {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let modes = modes(self);
+        if modes == Modes::default() {
+            return s.serialize_str(self.as_str())
+        }
+        let mut st = try!(s.serialize_struct("Regex", 4));
+        try!(st.serialize_field("pattern", self.as_str()));
+        try!(st.serialize_field("case_insensitive",
+                                &modes.case_insensitive));
+        try!(st.serialize_field("full_case_folding",
+                                &modes.full_case_folding));
+        try!(st.serialize_field("canonical_equivalence",
+                                &modes.canonical_equivalence));
+        st.end()
+    }
+}
+
+/// A `Regex` deserializes from a pattern string, which is compiled with
+/// `Regex::new`, or from a map in the format of `RegexOptions`. If
+/// compilation fails, then the error message includes the pattern and the
+/// position of the syntax error.
+impl<'de> Deserialize<'de> for Regex {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Regex, D::Error> {
+        d.deserialize_any(RegexVisitor)
+    }
+}
+
+struct RegexVisitor;
+
+impl<'de> Visitor<'de> for RegexVisitor {
+    type Value = Regex;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a regular expression")
+    }
+
+    fn visit_str<E: de::Error>(self, pattern: &str) -> Result<Regex, E> {
+        Regex::new(pattern).map_err(|err| compile_error(pattern, err))
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Regex, A::Error>
+            where A: MapAccess<'de> {
+        RegexOptionsVisitor.visit_map(map).map(RegexOptions::into_regex)
+    }
+}
+
+fn compile_error<E: de::Error>(pattern: &str, err: Error) -> E {
+    E::custom(format_args!("invalid regex '{}': {}", pattern, err))
+}
+
+/// A regular expression along with the options used to compile it.
+///
+/// This is useful in configuration files, where a regex may need options
+/// that can't be expressed by a plain pattern string. It serializes as a
+/// map (or struct) with these fields:
+///
+/// * `pattern`: the pattern, as given to `new`. This field is required.
+/// * `case_insensitive`: whether to match case insensitively, as if the
+///   pattern started with `(?i)`. Defaults to `false`.
+/// * `full_case_folding`: whether case insensitive parts match with full
+///   case folding, as in `Regex::with_full_case_folding`. Defaults to
+///   `false`.
+/// * `canonical_equivalence`: whether literals and classes match
+///   canonically equivalent text, as in
+///   `Regex::with_canonical_equivalence`. Defaults to `false`.
+/// * `size_limit`: the size limit of the compiled expression in bytes, as
+///   in `Regex::with_size_limit`. Defaults to the limit used by
+///   `Regex::new`.
+///
+/// It dereferences to the compiled `Regex`.
+///
+/// This type is only available with the `serde` Cargo feature enabled.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; extern crate serde_json;
+/// use regex::RegexOptions;
+/// # fn main() {
+/// let json = r#"{"pattern": "^abc$", "case_insensitive": true}"#;
+/// let re: RegexOptions = serde_json::from_str(json).unwrap();
+/// assert!(re.is_match("ABC"));
+/// assert_eq!(re.pattern(), "^abc$");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RegexOptions {
+    regex: Regex,
+    pattern: String,
+    modes: Modes,
+    size_limit: usize,
+}
+
+const FIELDS: &'static [&'static str] = &[
+    "pattern", "case_insensitive", "full_case_folding",
+    "canonical_equivalence", "size_limit",
+];
+
+impl RegexOptions {
+    /// Compiles `pattern` with the options given.
+    ///
+    /// If an invalid expression is given, then an error is returned.
+    pub fn new(pattern: &str, case_insensitive: bool,
+               full_case_folding: bool, canonical_equivalence: bool,
+               size_limit: usize) -> Result<RegexOptions, Error> {
+        let modes = Modes {
+            case_insensitive: case_insensitive,
+            full_case_folding: full_case_folding,
+            canonical_equivalence: canonical_equivalence,
+        };
+        let regex = try!(compile_with_options(pattern, modes, size_limit));
+        Ok(RegexOptions {
+            regex: regex,
+            pattern: pattern.to_string(),
+            modes: modes,
+            size_limit: size_limit,
+        })
+    }
+
+    /// Returns the pattern, without any flags added for the options.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns true if the regex matches case insensitively.
+    pub fn case_insensitive(&self) -> bool {
+        self.modes.case_insensitive
+    }
+
+    /// Returns true if the case insensitive parts of the regex match with
+    /// full case folding.
+    pub fn full_case_folding(&self) -> bool {
+        self.modes.full_case_folding
+    }
+
+    /// Returns true if the literals and classes of the regex also match
+    /// canonically equivalent text.
+    pub fn canonical_equivalence(&self) -> bool {
+        self.modes.canonical_equivalence
+    }
+
+    /// Returns the size limit the regex was compiled with.
+    pub fn size_limit(&self) -> usize {
+        self.size_limit
+    }
+
+    /// Returns the compiled regex.
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Returns the compiled regex, discarding the options.
+    pub fn into_regex(self) -> Regex {
+        self.regex
+    }
+}
+
+impl Deref for RegexOptions {
+    type Target = Regex;
+
+    fn deref(&self) -> &Regex {
+        &self.regex
+    }
+}
+
+impl Serialize for RegexOptions {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut st = try!(s.serialize_struct("RegexOptions", FIELDS.len()));
+        try!(st.serialize_field("pattern", &self.pattern));
+        try!(st.serialize_field("case_insensitive",
+                                &self.modes.case_insensitive));
+        try!(st.serialize_field("full_case_folding",
+                                &self.modes.full_case_folding));
+        try!(st.serialize_field("canonical_equivalence",
+                                &self.modes.canonical_equivalence));
+        try!(st.serialize_field("size_limit", &self.size_limit));
+        st.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for RegexOptions {
+    fn deserialize<D: Deserializer<'de>>(d: D)
+                                        -> Result<RegexOptions, D::Error> {
+        d.deserialize_struct("RegexOptions", FIELDS, RegexOptionsVisitor)
+    }
+}
+
+struct RegexOptionsVisitor;
+
+impl<'de> Visitor<'de> for RegexOptionsVisitor {
+    type Value = RegexOptions;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map with a regex pattern and its options")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<RegexOptions, A::Error>
+            where A: MapAccess<'de> {
+        let mut pattern: Option<String> = None;
+        let mut casei: Option<bool> = None;
+        let mut full_case_folding: Option<bool> = None;
+        let mut canonical_equivalence: Option<bool> = None;
+        let mut size_limit: Option<usize> = None;
+        while let Some(key) = try!(map.next_key::<String>()) {
+            match &*key {
+                "pattern" => {
+                    if pattern.is_some() {
+                        return Err(de::Error::duplicate_field("pattern"));
+                    }
+                    pattern = Some(try!(map.next_value()));
+                }
+                "case_insensitive" => {
+                    if casei.is_some() {
+                        return Err(de::Error::duplicate_field(
+                            "case_insensitive"));
+                    }
+                    casei = Some(try!(map.next_value()));
+                }
+                "full_case_folding" => {
+                    if full_case_folding.is_some() {
+                        return Err(de::Error::duplicate_field(
+                            "full_case_folding"));
+                    }
+                    full_case_folding = Some(try!(map.next_value()));
+                }
+                "canonical_equivalence" => {
+                    if canonical_equivalence.is_some() {
+                        return Err(de::Error::duplicate_field(
+                            "canonical_equivalence"));
+                    }
+                    canonical_equivalence = Some(try!(map.next_value()));
+                }
+                "size_limit" => {
+                    if size_limit.is_some() {
+                        return Err(de::Error::duplicate_field("size_limit"));
+                    }
+                    size_limit = Some(try!(map.next_value()));
+                }
+                _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+            }
+        }
+        let pattern = match pattern {
+            None => return Err(de::Error::missing_field("pattern")),
+            Some(pattern) => pattern,
+        };
+        RegexOptions::new(&pattern,
+                          casei.unwrap_or(false),
+                          full_case_folding.unwrap_or(false),
+                          canonical_equivalence.unwrap_or(false),
+                          size_limit.unwrap_or(DEFAULT_SIZE_LIMIT))
+            .map_err(|err| compile_error(&pattern, err))
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Range;
+
+use compile::Inst;
+use re::{Regex, program};
+use vm;
+use vm::Anchor::Unanchored;
+use vm::MatchKind::Submatches;
+
+/// What the VM did at a single position in the input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The byte offset in the input.
+    pub position: usize,
+    /// The program counters of the live threads, in priority order.
+    pub threads: Vec<usize>,
+    /// The program counters of the threads whose instructions were stepped
+    /// (i.e., those that consume a character or match), in order.
+    pub stepped: Vec<usize>,
+    /// The capture slots written while following threads from this step.
+    pub saves: Vec<SlotUpdate>,
+    /// The program counter of the thread that matched at this position, if
+    /// any. The threads after it are dropped.
+    pub matched: Option<usize>,
+}
+
+/// A write to a capture slot made by a `Save` instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotUpdate {
+    /// The program counter of the `Save` instruction.
+    pub pc: usize,
+    /// The slot written. Slots `2 * i` and `2 * i + 1` hold the start and
+    /// end of capture group `i`.
+    pub slot: usize,
+    /// The byte offset saved in the slot.
+    pub position: usize,
+}
+
+/// A record of everything the VM did while searching for a match.
+///
+/// It's returned by `Regex::trace`. Its `Display` implementation gives a
+/// human readable dump of the program and of every step.
+///
+/// Only capture groups are tied back to the pattern: the instructions
+/// compiled from inside a group, including the `Save` instructions that
+/// record it, are shown with the group's text in the pattern. Instructions
+/// outside of every group are only described, since the compiler doesn't
+/// keep the spans of smaller parts of the pattern.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate regex; use regex::Regex;
+/// # fn main() {
+/// let re = Regex::new(r"(a|ab)").unwrap();
+/// let trace = re.trace("ab");
+/// // The first alternative wins, even though the second is longer.
+/// assert_eq!(trace.captures()[..2], [Some(0), Some(1)]);
+/// let winner = trace.steps().iter().filter_map(|s| s.matched).next();
+/// assert_eq!(trace.describe(winner.unwrap()), "match");
+/// assert!(trace.to_string().contains("char 'b' in `(a|ab)`"));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Trace {
+    pattern: String,
+    input: String,
+    insts: Vec<Inst>,
+    group_spans: Vec<Range<usize>>,
+    // The innermost group that each instruction was compiled from.
+    groups: Vec<Option<usize>>,
+    steps: Vec<TraceStep>,
+    captures: Vec<Option<usize>>,
+}
+
+impl Trace {
+    /// Returns the steps of the VM, one for each position it visited.
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    /// Returns the capture slots of the match found, or all `None` if there
+    /// is no match.
+    pub fn captures(&self) -> &[Option<usize>] {
+        &self.captures
+    }
+
+    /// Returns the number of instructions in the program.
+    pub fn num_instructions(&self) -> usize {
+        self.insts.len()
+    }
+
+    /// Returns the innermost capture group that the instruction at `pc` was
+    /// compiled from, or `None` if it's outside of every group (other than
+    /// group `0`, which is the entire pattern).
+    ///
+    /// # Panics
+    ///
+    /// If `pc` isn't less than `num_instructions()`.
+    pub fn group(&self, pc: usize) -> Option<usize> {
+        self.groups[pc]
+    }
+
+    /// Returns a description of the instruction at `pc`.
+    ///
+    /// # Panics
+    ///
+    /// If `pc` isn't less than `num_instructions()`.
+    pub fn describe(&self, pc: usize) -> String {
+        match self.insts[pc] {
+            Inst::Match => "match".to_string(),
+            Inst::OneChar { c, casei: false } => format!("char {:?}", c),
+            Inst::OneChar { c, casei: true } => format!("char (?i){:?}", c),
+            Inst::CharClass(ref cls) => format!("class {}", cls),
+            Inst::Any => "any char".to_string(),
+            Inst::AnyNoNL => "any char except \\n".to_string(),
+            Inst::StartLine => "start of line".to_string(),
+            Inst::EndLine => "end of line".to_string(),
+            Inst::StartText => "start of text".to_string(),
+            Inst::EndText => "end of text".to_string(),
+            Inst::WordBoundary => "word boundary".to_string(),
+            Inst::NotWordBoundary => "not a word boundary".to_string(),
+            Inst::Save(slot) => {
+                let group = slot / 2;
+                let which = if slot % 2 == 0 { "start" } else { "end" };
+                match self.group_spans.get(group) {
+                    None => format!("save {} of group {}", which, group),
+                    Some(span) => {
+                        format!("save {} of group {} `{}`", which, group,
+                                &self.pattern[span.clone()])
+                    }
+                }
+            }
+            Inst::Jump(to) => format!("jump to {}", to),
+            Inst::Split(x, y) => format!("split to {}, then {}", x, y),
+            Inst::MatchRule(rule) => format!("match rule {}", rule),
+        }
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "pattern: {}", self.pattern));
+        try!(writeln!(f, "program:"));
+        for pc in 0..self.insts.len() {
+            try!(write!(f, "  {:3}: {}", pc, self.describe(pc)));
+            let span = self.group(pc).and_then(|i| self.group_spans.get(i));
+            match (&self.insts[pc], span) {
+                // A save already shows its group.
+                (&Inst::Save(_), _) | (_, None) => {}
+                (_, Some(span)) => {
+                    try!(write!(f, " in `{}`", &self.pattern[span.clone()]))
+                }
+            }
+            try!(writeln!(f, ""));
+        }
+        try!(writeln!(f, "steps:"));
+        for step in &self.steps {
+            let next = self.input[step.position..].chars().next();
+            match next {
+                None => try!(writeln!(f, "  at {} (end):", step.position)),
+                Some(c) => try!(writeln!(f, "  at {} {:?}:", step.position, c)),
+            }
+            try!(writeln!(f, "    threads: {:?}", step.threads));
+            for &pc in &step.stepped {
+                try!(writeln!(f, "    step {}: {}", pc, self.describe(pc)));
+            }
+            for save in &step.saves {
+                try!(writeln!(f, "    slot {} = {} (by {})",
+                              save.slot, save.position, save.pc));
+            }
+            if let Some(pc) = step.matched {
+                try!(writeln!(f, "    matched by thread at {}", pc));
+            }
+        }
+        match (self.captures.get(0), self.captures.get(1)) {
+            (Some(&Some(s)), Some(&Some(e))) => {
+                write!(f, "result: match at {}..{} {:?}",
+                       s, e, &self.input[s..e])
+            }
+            _ => write!(f, "result: no match"),
+        }
+    }
+}
+
+impl Regex {
+    /// Searches `text` for the leftmost-first match like `captures`, and
+    /// returns a trace of every step taken by the VM.
+    ///
+    /// This is meant for debugging patterns, and is much slower than a
+    /// normal search.
+    pub fn trace(&self, text: &str) -> Trace {
+        let prog = program(self);
+        let steps = RefCell::new(vec![]);
+        let captures = vm::run_traced(Submatches, Unanchored, &prog, text, 0,
+                                      text.len(), &steps);
+        Trace {
+            pattern: self.as_str().to_string(),
+            input: text.to_string(),
+            insts: prog.insts.clone(),
+            group_spans: self.capture_spans(),
+            groups: innermost_groups(&prog.insts),
+            steps: steps.into_inner(),
+            captures: captures,
+        }
+    }
+}
+
+/// Returns the innermost capture group, other than group `0`, that each
+/// instruction was compiled from.
+///
+/// The instructions of a group are compiled between the `Save`s of its start
+/// and end, and groups nest, so a stack of the open groups is enough.
+fn innermost_groups(insts: &[Inst]) -> Vec<Option<usize>> {
+    let mut open = vec![];
+    insts.iter().map(|inst| {
+        match *inst {
+            Inst::Save(slot) if slot > 1 && slot % 2 == 0 => {
+                open.push(slot / 2);
+                open.last().cloned()
+            }
+            Inst::Save(slot) if slot > 1 => open.pop(),
+            _ => open.last().cloned(),
+        }
+    }).collect()
+}
@@ -33,15 +33,21 @@
 //
 // [1] - http://swtch.com/~rsc/regex/regex3.html
 
+use self::Anchor::*;
 use self::MatchKind::*;
 use self::StepState::*;
 
+use std::cell::RefCell;
 use std::cmp;
+use std::fmt;
 use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use compile::Program;
+use compile::{Inst, Program};
 use compile::Inst::*;
+use haystack::Haystack;
 use syntax;
+use trace::{SlotUpdate, TraceStep};
 
 pub type CaptureLocs = Vec<Option<usize>>;
 
@@ -57,6 +63,18 @@ pub enum MatchKind {
     Submatches,
 }
 
+/// Indicates where a match must begin and end.
+#[derive(Copy, Clone)]
+pub enum Anchor {
+    /// The match may begin and end anywhere in the range searched.
+    Unanchored,
+    /// The match must begin where the search begins.
+    AnchoredStart,
+    /// The match must begin where the search begins and end where the search
+    /// ends.
+    AnchoredBoth,
+}
+
 /// Runs an NFA simulation on the compiled expression given on the search text
 /// `input`. The search begins at byte index `start` and ends at byte index
 /// `end`. (The range is specified here so that zero-width assertions will work
@@ -66,27 +84,208 @@ pub enum MatchKind {
 /// wants. There are three choices: match existence only, the location of the
 /// entire match or the locations of the entire match in addition to the
 /// locations of each submatch.
-pub fn run<'r, 't>(which: MatchKind, prog: &'r Program, input: &'t str,
-                   start: usize, end: usize) -> CaptureLocs {
+///
+/// The `anchor` parameter indicates whether the match must begin at `start`
+/// (and possibly end at `end`). An anchored search doesn't simulate a
+/// preceding `.*?`, so it never looks for a match at a later position.
+///
+/// The input may be any `Haystack`, so text split into several chunks can
+/// be searched without copying it. All indices are byte offsets from the
+/// beginning of the entire input.
+pub fn run<'r, 't, H>(which: MatchKind, anchor: Anchor, prog: &'r Program,
+                      input: &'t H, start: usize, end: usize) -> CaptureLocs
+        where H: ?Sized + Haystack {
     Nfa {
         which: which,
+        anchor: anchor,
         prog: prog,
         input: input,
         start: start,
         end: end,
         ic: 0,
         chars: CharReader::new(input),
+        cancel: None,
+        cancelled: false,
+        trace: None,
+        nonempty: false,
     }.run()
 }
 
-struct Nfa<'r, 't> {
+/// Does the same as `run`, except the search is abandoned soon after `cancel`
+/// is set, in which case `None` is returned.
+///
+/// The flag is checked before the first character is read and periodically
+/// after that.
+pub fn run_cancellable<'r, 't, H>(which: MatchKind, anchor: Anchor,
+                                  prog: &'r Program, input: &'t H,
+                                  start: usize, end: usize,
+                                  cancel: &'r AtomicBool)
+                                 -> Option<CaptureLocs>
+        where H: ?Sized + Haystack {
+    let mut nfa = Nfa {
+        which: which,
+        anchor: anchor,
+        prog: prog,
+        input: input,
+        start: start,
+        end: end,
+        ic: 0,
+        chars: CharReader::new(input),
+        cancel: Some(cancel),
+        cancelled: false,
+        trace: None,
+        nonempty: false,
+    };
+    let caps = nfa.run();
+    if nfa.cancelled { None } else { Some(caps) }
+}
+
+/// Does the same as `run`, except every step of the simulation is recorded
+/// in `trace`. One `TraceStep` is appended for each position in the input
+/// that the VM visits.
+pub fn run_traced<'r, 't>(which: MatchKind, anchor: Anchor, prog: &'r Program,
+                          input: &'t str, start: usize, end: usize,
+                          trace: &'r RefCell<Vec<TraceStep>>) -> CaptureLocs {
+    Nfa {
+        which: which,
+        anchor: anchor,
+        prog: prog,
+        input: input,
+        start: start,
+        end: end,
+        ic: 0,
+        chars: CharReader::new(input),
+        cancel: None,
+        cancelled: false,
+        trace: Some(trace),
+        nonempty: false,
+    }.run()
+}
+
+/// Does the same as `run`, except the input is read backwards, from `end`
+/// toward `start`. The program should be one compiled by
+/// `Program::new_reversed`, so it matches text in reverse.
+///
+/// The locations returned are byte indices into the input, with the start
+/// of each capture group before its end, just as `run` returns them.
+pub fn run_reversed<'r, 't, H>(which: MatchKind, anchor: Anchor,
+                               prog: &'r Program, input: &'t H,
+                               start: usize, end: usize) -> CaptureLocs
+        where H: ?Sized + Haystack {
+    let len = input.len();
+    let mut caps = Nfa {
+        which: which,
+        anchor: anchor,
+        prog: prog,
+        input: input,
+        start: len - end,
+        end: len - start,
+        ic: 0,
+        chars: CharReader::new_reversed(input),
+        cancel: None,
+        cancelled: false,
+        trace: None,
+        nonempty: false,
+    }.run();
+    // The VM works with offsets from the end of the input, so a group's
+    // start is recorded after its end.
+    for pair in caps.chunks_mut(2) {
+        let (s, e) = (pair[1].map(|i| len - i), pair[0].map(|i| len - i));
+        pair[0] = s;
+        pair[1] = e;
+    }
+    caps
+}
+
+/// The number of characters the VM reads between checks of its cancellation
+/// flag.
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+/// Runs an NFA simulation of a program built with `Program::new_rules`,
+/// anchored at byte index `start` of `input`.
+///
+/// Rather than stopping at the first match, every thread is run to
+/// completion, so the longest match wins. The result is the end of that
+/// match and the index of the rule that matched it. If several rules match
+/// the same text, then the rule with the lowest index is reported.
+pub fn run_longest<'r, 't>(prog: &'r Program, input: &'t str, start: usize)
+                          -> Option<(usize, usize)> {
+    if prog.insts.is_empty() {
+        return None
+    }
+    let mut longest: Option<(usize, usize)> = None;
+    Nfa::exhaustive(prog, input, start).run_all(|at, inst| {
+        if let MatchRule(rule) = *inst {
+            // Threads are visited in priority order, so the first rule to
+            // match at a position has the lowest index among them.
+            let better = match longest {
+                None => true,
+                Some((end, _)) => at > end,
+            };
+            if better {
+                longest = Some((at, rule));
+            }
+        }
+    });
+    longest
+}
+
+/// Runs an NFA simulation anchored at byte index `start` of `input` and
+/// returns every position at which a match ends, in increasing order.
+///
+/// Like `run_longest`, every thread is run to completion instead of
+/// stopping at the first match.
+pub fn run_all_ends<'r, 't>(prog: &'r Program, input: &'t str, start: usize)
+                           -> Vec<usize> {
+    let mut ends = vec![];
+    Nfa::exhaustive(prog, input, start).run_all(|at, inst| {
+        if let Match = *inst {
+            if ends.last() != Some(&at) {
+                ends.push(at);
+            }
+        }
+    });
+    ends
+}
+
+/// Returns the start of the leftmost non-empty match of `prog` that begins
+/// at or after byte index `start` of `input`.
+///
+/// Empty matches are skipped over, so a program that only matches the empty
+/// string somewhere doesn't stop the search there.
+pub fn run_nonempty<'r, 't>(prog: &'r Program, input: &'t str, start: usize)
+                           -> Option<usize> {
+    Nfa {
+        which: Location,
+        anchor: Unanchored,
+        prog: prog,
+        input: input,
+        start: start,
+        end: input.len(),
+        ic: 0,
+        chars: CharReader::new(input),
+        cancel: None,
+        cancelled: false,
+        trace: None,
+        nonempty: true,
+    }.run()[0]
+}
+
+struct Nfa<'r, 't, H: ?Sized + 't = str> {
     which: MatchKind,
+    anchor: Anchor,
     prog: &'r Program,
-    input: &'t str,
+    input: &'t H,
     start: usize,
     end: usize,
     ic: usize,
-    chars: CharReader<'t>,
+    chars: CharReader<'t, H>,
+    cancel: Option<&'r AtomicBool>,
+    cancelled: bool,
+    trace: Option<&'r RefCell<Vec<TraceStep>>>,
+    /// When set, a thread that reaches `Match` without consuming any input
+    /// is dropped instead of reporting a match.
+    nonempty: bool,
 }
 
 /// Indicates the next action to take after a single non-empty instruction
@@ -106,6 +305,28 @@ pub enum StepState {
 }
 
 impl<'r, 't> Nfa<'r, 't> {
+    /// Returns an NFA anchored at `start` that tracks no capture groups, for
+    /// use with `run_all`.
+    fn exhaustive(prog: &'r Program, input: &'t str, start: usize)
+                 -> Nfa<'r, 't> {
+        Nfa {
+            which: Exists,
+            anchor: AnchoredStart,
+            prog: prog,
+            input: input,
+            start: start,
+            end: input.len(),
+            ic: 0,
+            chars: CharReader::new(input),
+            cancel: None,
+            cancelled: false,
+            trace: None,
+            nonempty: false,
+        }
+    }
+}
+
+impl<'r, 't, H: ?Sized + Haystack> Nfa<'r, 't, H> {
     fn run(&mut self) -> CaptureLocs {
         let ncaps = match self.which {
             Exists => 0,
@@ -126,10 +347,37 @@ impl<'r, 't> Nfa<'r, 't> {
             StartText => true,
             _ => false,
         };
+        // If the caller asked for an anchored match, then the program is
+        // only started at the beginning of the search.
+        let anchored = match self.anchor {
+            Unanchored => false,
+            AnchoredStart | AnchoredBoth => true,
+        };
+
+        // Invalid UTF-8 in a haystack is read as U+FFFD, so a prefix with
+        // that character can't be searched for by its bytes.
+        let use_prefix = self.prog.prefix.len() > 0 && !anchored
+                         && !self.prog.prefix.contains(REPLACEMENT);
+        // Read backwards, the prefix is found by searching the input for its
+        // characters in reverse order.
+        let rev_prefix: String = if use_prefix && self.chars.reverse {
+            self.prog.prefix.chars().rev().collect()
+        } else {
+            String::new()
+        };
 
         self.ic = self.start;
         let mut next_ic = self.chars.set(self.start);
+        let mut steps = 0;
         while self.ic <= self.end {
+            if let Some(cancel) = self.cancel {
+                if steps % CANCEL_CHECK_INTERVAL == 0
+                   && cancel.load(Ordering::Relaxed) {
+                    self.cancelled = true;
+                    break
+                }
+                steps += 1;
+            }
             if clist.size == 0 {
                 // We have a match and we're done exploring alternatives.
                 // Time to quit.
@@ -143,15 +391,27 @@ impl<'r, 't> Nfa<'r, 't> {
                     break;
                 }
 
+                // Similarly, an anchored search is over as soon as the
+                // threads started at the beginning of the search are dead.
+                if self.ic != self.start && anchored {
+                    break;
+                }
+
                 // If there are no threads to try, then we'll have to start
                 // over at the beginning of the regex.
                 // BUT, if there's a literal prefix for the program, try to
                 // jump ahead quickly. If it can't be found, then we can bail
-                // out early.
-                if self.prog.prefix.len() > 0 {
-                    let needle = self.prog.prefix.as_bytes();
-                    let haystack = &self.input.as_bytes()[self.ic..];
-                    match find_prefix(needle, haystack) {
+                // out early. (An anchored search can't jump ahead.)
+                if use_prefix {
+                    let found = if self.chars.reverse {
+                        let to = self.input.len() - self.ic;
+                        rfind_prefix_chunked(rev_prefix.as_bytes(),
+                                             self.input, to)
+                    } else {
+                        let needle = self.prog.prefix.as_bytes();
+                        find_prefix_chunked(needle, self.input, self.ic)
+                    };
+                    match found {
                         None => break,
                         Some(i) => {
                             self.ic += i;
@@ -161,12 +421,27 @@ impl<'r, 't> Nfa<'r, 't> {
                 }
             }
 
+            if let Some(trace) = self.trace {
+                trace.borrow_mut().push(TraceStep {
+                    position: self.ic,
+                    threads: vec![],
+                    stepped: vec![],
+                    saves: vec![],
+                    matched: None,
+                });
+            }
+
             // This simulates a preceding '.*?' for every regex by adding
             // a state starting at the current position in the input for the
             // beginning of the program only if we don't already have a match.
-            if clist.size == 0 || (!prefix_anchor && !matched) {
+            // (Read backwards, a match ends where its thread starts.)
+            if (clist.size == 0 || (!prefix_anchor && !anchored && !matched))
+               && !(self.chars.reverse && self.splits_cluster()) {
                 self.add(&mut clist, 0, &mut groups)
             }
+            self.record(|step| {
+                step.threads = (0..clist.size).map(|i| clist.pc(i)).collect();
+            });
 
             // Now we try to read the next character.
             // As a result, the 'step' method will look at the previous
@@ -178,6 +453,9 @@ impl<'r, 't> Nfa<'r, 't> {
                 let pc = clist.pc(i);
                 let step_state = self.step(&mut groups, &mut nlist,
                                            clist.groups(i), pc);
+                if self.trace.is_some() {
+                    self.record_step(pc, step_state);
+                }
                 match step_state {
                     StepMatchEarlyReturn => return vec![Some(0), Some(0)],
                     StepMatch => { matched = true; break },
@@ -194,15 +472,84 @@ impl<'r, 't> Nfa<'r, 't> {
         }
     }
 
+    /// Runs every thread to completion, calling `on_match` with the
+    /// position and instruction of each thread that reaches `Match` or
+    /// `MatchRule`.
+    fn run_all<F>(&mut self, mut on_match: F) where F: FnMut(usize, &Inst) {
+        let ninsts = self.prog.insts.len();
+        let mut clist = Threads::new(Exists, ninsts, 0);
+        let mut nlist = Threads::new(Exists, ninsts, 0);
+        let mut groups = vec![];
+
+        self.ic = self.start;
+        let mut next_ic = self.chars.set(self.start);
+        self.add(&mut clist, 0, &mut groups);
+        while clist.size > 0 {
+            let at = self.ic;
+            self.ic = next_ic;
+            next_ic = self.chars.advance();
+
+            for i in 0..clist.size {
+                let pc = clist.pc(i);
+                match self.prog.insts[pc] {
+                    Match if self.splits_cluster() => {}
+                    ref inst @ Match | ref inst @ MatchRule(_) => {
+                        on_match(at, inst)
+                    }
+                    _ => {
+                        self.step(&mut groups, &mut nlist,
+                                  clist.groups(i), pc);
+                    }
+                }
+            }
+            mem::swap(&mut clist, &mut nlist);
+            nlist.empty();
+            if at >= self.end {
+                break
+            }
+        }
+    }
+
+    /// Applies `f` to the step of the trace being recorded, if any.
+    #[inline]
+    fn record<F: FnOnce(&mut TraceStep)>(&self, f: F) {
+        if let Some(trace) = self.trace {
+            if let Some(step) = trace.borrow_mut().last_mut() {
+                f(step);
+            }
+        }
+    }
+
+    /// Records that the thread at `pc` was stepped, if its instruction does
+    /// anything when stepped.
+    fn record_step(&self, pc: usize, state: StepState) {
+        match self.prog.insts[pc] {
+            StartLine | EndLine | StartText | EndText
+            | WordBoundary | NotWordBoundary
+            | Save(_) | Jump(_) | Split(_, _) => return,
+            Match | OneChar{..} | CharClass(_) | Any | AnyNoNL
+            | MatchRule(_) => {}
+        }
+        self.record(|step| {
+            step.stepped.push(pc);
+            match state {
+                StepMatchEarlyReturn | StepMatch => step.matched = Some(pc),
+                StepContinue => {}
+            }
+        });
+    }
+
     fn step(&self, groups: &mut [Option<usize>], nlist: &mut Threads,
             caps: &mut [Option<usize>], pc: usize)
            -> StepState {
         match self.prog.insts[pc] {
+            Match if !self.chars.reverse && self.splits_cluster() => {}
             Match => {
                 match self.which {
                     Exists => {
                         return StepMatchEarlyReturn
                     }
+                    Location if self.nonempty && caps[0] == caps[1] => {}
                     Location => {
                         groups[0] = caps[0];
                         groups[1] = caps[1];
@@ -234,11 +581,22 @@ impl<'r, 't> Nfa<'r, 't> {
             }
             StartLine | EndLine | StartText | EndText
             | WordBoundary | NotWordBoundary
-            | Save(_) | Jump(_) | Split(_, _) => {},
+            | Save(_) | Jump(_) | Split(_, _) | MatchRule(_) => {},
         }
         StepContinue
     }
 
+    /// Returns true if a match can't end before the character last read,
+    /// because the program keeps characters and their combining marks
+    /// together.
+    #[inline]
+    fn splits_cluster(&self) -> bool {
+        self.prog.whole_clusters
+            && self.chars.prev.map_or(false, |c| {
+                syntax::combining_class(c) != 0
+            })
+    }
+
     fn add(&self, nlist: &mut Threads, pc: usize, groups: &mut [Option<usize>]) {
         if nlist.contains(pc) {
             return
@@ -298,12 +656,14 @@ impl<'r, 't> Nfa<'r, 't> {
                 nlist.add(pc, groups, true);
                 match self.which {
                     Location if slot <= 1 => {
+                        self.record_save(pc, slot);
                         let old = groups[slot];
                         groups[slot] = Some(self.ic);
                         self.add(nlist, pc + 1, groups);
                         groups[slot] = old;
                     }
                     Submatches => {
+                        self.record_save(pc, slot);
                         let old = groups[slot];
                         groups[slot] = Some(self.ic);
                         self.add(nlist, pc + 1, groups);
@@ -321,12 +681,31 @@ impl<'r, 't> Nfa<'r, 't> {
                 self.add(nlist, x, groups);
                 self.add(nlist, y, groups);
             }
-            Match | OneChar{..} | CharClass(_) | Any | AnyNoNL => {
+            Match => {
+                // A match that must end where the search ends is dropped
+                // anywhere else, which lets lower priority threads continue.
+                match self.anchor {
+                    AnchoredBoth if self.ic != self.end => {}
+                    _ => nlist.add(pc, groups, false),
+                }
+            }
+            OneChar{..} | CharClass(_) | Any | AnyNoNL | MatchRule(_) => {
                 nlist.add(pc, groups, false);
             }
         }
     }
 
+    fn record_save(&self, pc: usize, slot: usize) {
+        let position = self.ic;
+        self.record(|step| {
+            step.saves.push(SlotUpdate {
+                pc: pc,
+                slot: slot,
+                position: position,
+            });
+        });
+    }
+
     // Use Unicode simple case folding for case insensitive comparisons,
     // as we’re matching individual code points.
     #[inline]
@@ -348,29 +727,72 @@ impl<'r, 't> Nfa<'r, 't> {
 /// CharReader is responsible for maintaining a "previous" and a "current"
 /// character. This one-character lookahead is necessary for assertions that
 /// look one character before or after the current position.
-pub struct CharReader<'t> {
+///
+/// The input may be split into chunks. The reader decodes characters that
+/// straddle chunk boundaries, and decodes each byte that isn't part of valid
+/// UTF-8 as `U+FFFD`.
+///
+/// A reader may also read its input backwards, from the end to the
+/// beginning. Every index given to or returned by such a reader is an offset
+/// from the end of the input, so to its user, the input looks as if its
+/// characters were in reverse order.
+pub struct CharReader<'t, H: ?Sized + 't = str> {
     /// The previous character read. It is None only when processing the first
     /// character of the input.
     pub prev: Option<char>,
     /// The current character.
     pub cur: Option<char>,
-    input: &'t str,
+    input: &'t H,
+    len: usize,
+    // The chunk holding the most recently decoded byte, and its offset.
+    chunk: &'t [u8],
+    chunk_start: usize,
     next: usize,
+    reverse: bool,
+}
+
+impl<'t, H: ?Sized> Copy for CharReader<'t, H> {}
+
+impl<'t, H: ?Sized> Clone for CharReader<'t, H> {
+    fn clone(&self) -> CharReader<'t, H> {
+        *self
+    }
 }
 
-impl<'t> CharReader<'t> {
+impl<'t, H: ?Sized> fmt::Debug for CharReader<'t, H> {
+    /// Shows the characters around the reader's position, but not its input.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CharReader")
+         .field("prev", &self.prev)
+         .field("cur", &self.cur)
+         .field("next", &self.next)
+         .field("reverse", &self.reverse)
+         .finish()
+    }
+}
+
+impl<'t, H: ?Sized + Haystack> CharReader<'t, H> {
     /// Returns a new CharReader that advances through the input given.
     /// Note that a CharReader has no knowledge of the range in which to search
     /// the input.
-    pub fn new(input: &'t str) -> CharReader<'t> {
+    pub fn new(input: &'t H) -> CharReader<'t, H> {
         CharReader {
             prev: None,
             cur: None,
             input: input,
+            len: input.len(),
+            chunk: &[],
+            chunk_start: 0,
             next: 0,
+            reverse: false,
        }
     }
 
+    /// Returns a new CharReader that reads the input given backwards.
+    pub fn new_reversed(input: &'t H) -> CharReader<'t, H> {
+        CharReader { reverse: true, ..CharReader::new(input) }
+    }
+
     /// Sets the previous and current character given any arbitrary byte
     /// index (at a Unicode codepoint boundary).
     #[inline]
@@ -379,20 +801,20 @@ impl<'t> CharReader<'t> {
         self.cur = None;
         self.next = 0;
 
-        if self.input.len() == 0 {
+        if self.len == 0 {
             return 1
         }
         if ic > 0 {
-            let i = cmp::min(ic, self.input.len());
-            self.prev = self.input[..i].chars().rev().next();
+            let i = cmp::min(ic, self.len);
+            self.prev = Some(self.read_before(i));
         }
-        if ic < self.input.len() {
-            let cur = self.input[ic..].chars().next().unwrap();
+        if ic < self.len {
+            let (cur, n) = self.read(ic);
             self.cur = Some(cur);
-            self.next = ic + cur.len_utf8();
+            self.next = ic + n;
             self.next
         } else {
-            self.input.len() + 1
+            self.len + 1
         }
     }
 
@@ -401,17 +823,103 @@ impl<'t> CharReader<'t> {
     #[inline]
     pub fn advance(&mut self) -> usize {
         self.prev = self.cur;
-        if self.next < self.input.len() {
-            let cur = self.input[self.next..].chars().next().unwrap();
+        if self.next < self.len {
+            let next = self.next;
+            let (cur, n) = self.read(next);
             self.cur = Some(cur);
-            self.next += cur.len_utf8();
+            self.next += n;
         } else {
             self.cur = None;
-            self.next = self.input.len() + 1;
+            self.next = self.len + 1;
         }
         self.next
     }
 
+    /// Returns the character following index `i` in the order the input is
+    /// read, along with its length in bytes.
+    #[inline]
+    fn read(&mut self, i: usize) -> (char, usize) {
+        if self.reverse {
+            let i = self.len - i;
+            self.decode_before(i)
+        } else {
+            self.decode(i)
+        }
+    }
+
+    /// Returns the character preceding index `i` in the order the input is
+    /// read.
+    #[inline]
+    fn read_before(&mut self, i: usize) -> char {
+        if self.reverse {
+            let i = self.len - i;
+            self.decode(i).0
+        } else {
+            self.decode_before(i).0
+        }
+    }
+
+    /// Returns the byte at index `i`, which must be less than the length of
+    /// the input.
+    #[inline]
+    fn byte(&mut self, i: usize) -> u8 {
+        if i < self.chunk_start || i - self.chunk_start >= self.chunk.len() {
+            let (chunk, start) = self.input.chunk_at(i);
+            self.chunk = chunk;
+            self.chunk_start = start;
+        }
+        self.chunk[i - self.chunk_start]
+    }
+
+    /// Decodes the character starting at byte index `i` and returns it
+    /// along with its length in bytes.
+    #[inline]
+    fn decode(&mut self, i: usize) -> (char, usize) {
+        let b0 = self.byte(i);
+        if b0 < 0x80 {
+            return (b0 as char, 1)
+        }
+        let (n, init) = match b0 {
+            b if b >= 0xC2 && b <= 0xDF => (2, (b & 0x1F) as u32),
+            b if b >= 0xE0 && b <= 0xEF => (3, (b & 0x0F) as u32),
+            b if b >= 0xF0 && b <= 0xF4 => (4, (b & 0x07) as u32),
+            _ => return (REPLACEMENT, 1),
+        };
+        if i + n > self.len {
+            return (REPLACEMENT, 1)
+        }
+        let mut c = init;
+        for j in 1..n {
+            let b = self.byte(i + j);
+            if b & 0xC0 != 0x80 {
+                return (REPLACEMENT, 1)
+            }
+            c = (c << 6) | (b & 0x3F) as u32;
+        }
+        // Reject overlong encodings, surrogates and values past U+10FFFF.
+        let min = match n { 2 => 0x80, 3 => 0x800, _ => 0x10000 };
+        if c < min {
+            return (REPLACEMENT, 1)
+        }
+        match ::std::char::from_u32(c) {
+            None => (REPLACEMENT, 1),
+            Some(c) => (c, n),
+        }
+    }
+
+    /// Decodes the character ending at byte index `i`, which must be
+    /// greater than zero, and returns it along with its length in bytes.
+    fn decode_before(&mut self, i: usize) -> (char, usize) {
+        // Back up over at most three continuation bytes to find the start of
+        // the character, then decode forward from there.
+        let mut start = i - 1;
+        while start > 0 && i - start < 4 && self.byte(start) & 0xC0 == 0x80 {
+            start -= 1;
+        }
+        let (c, n) = self.decode(start);
+        if start + n == i { (c, n) } else { (REPLACEMENT, 1) }
+    }
+
     /// Returns true if and only if this is the beginning of the input
     /// (ignoring the range of the input to search).
     #[inline]
@@ -512,6 +1020,70 @@ impl Threads {
     }
 }
 
+const REPLACEMENT: char = '\u{FFFD}';
+
+/// Returns the offset, relative to `from`, of the first occurrence of
+/// `needle` in `input` at or after `from`. Occurrences may straddle chunks.
+pub fn find_prefix_chunked<H>(needle: &[u8], input: &H, from: usize)
+                             -> Option<usize>
+        where H: ?Sized + Haystack {
+    let len = input.len();
+    let mut at = from;
+    while at < len {
+        let (chunk, start) = input.chunk_at(at);
+        if let Some(i) = find_prefix(needle, &chunk[at - start..]) {
+            return Some(at + i - from)
+        }
+        // Occurrences that begin in this chunk but end in a later one.
+        let chunk_end = start + chunk.len();
+        let first = cmp::max(at, (chunk_end + 1).saturating_sub(needle.len()));
+        for s in first..chunk_end {
+            if s + needle.len() <= len
+               && needle.iter().enumerate()
+                        .all(|(j, &b)| byte_at(input, s + j) == b) {
+                return Some(s - from)
+            }
+        }
+        at = chunk_end;
+    }
+    None
+}
+
+/// Returns the offset, relative to `to`, of the end of the last occurrence
+/// of `needle` in `input` that ends at or before `to`. Occurrences may
+/// straddle chunks.
+pub fn rfind_prefix_chunked<H>(needle: &[u8], input: &H, to: usize)
+                              -> Option<usize>
+        where H: ?Sized + Haystack {
+    let n = needle.len();
+    if n == 0 {
+        return None
+    }
+    let mut at = to;
+    while at >= n {
+        let (chunk, start) = input.chunk_at(at - 1);
+        let hay = &chunk[..at - start];
+        if let Some(i) = hay.windows(n).rposition(|w| w == needle) {
+            return Some(to - (start + i + n))
+        }
+        // Occurrences that end in this chunk but begin in an earlier one.
+        let last = cmp::min(at, start + n - 1);
+        for e in (start + 1..last + 1).rev() {
+            if e >= n && needle.iter().enumerate()
+                               .all(|(j, &b)| byte_at(input, e - n + j) == b) {
+                return Some(to - e)
+            }
+        }
+        at = start;
+    }
+    None
+}
+
+fn byte_at<H: ?Sized + Haystack>(input: &H, i: usize) -> u8 {
+    let (chunk, start) = input.chunk_at(i);
+    chunk[i - start]
+}
+
 /// Returns the starting location of `needle` in `haystack`.
 /// If `needle` is not in `haystack`, then `None` is returned.
 ///